@@ -7,41 +7,140 @@ import asyncio
 import websockets
 import json
 import sys
+import copy
 import certifi
 import ssl
 from typing import Optional
 from textual.app import App, ComposeResult
 from textual.containers import Container, Vertical
-from textual.widgets import Input, RichLog, Static, Label
+from textual.widgets import Input, RichLog, Static, Label, TextArea, Select, OptionList, Button, Tabs, Tab
+from textual.widgets.option_list import Option
 from textual.binding import Binding
-from textual.screen import Screen
+from textual.screen import Screen, ModalScreen
+from textual.message import Message
+from textual.command import Provider, Hit
 from rich.markup import escape
+from rich.color import Color, ColorParseError
 import os
 import platform
 import subprocess
 import shlex
+import tempfile
+import argparse
+import re
+import time
+import threading
 import aiohttp
+import base64
+import hashlib
+import colorsys
+import unicodedata
+import math
+import inspect
+from functools import partial
+from collections import deque
+from importlib import metadata
+import errno
+import ipaddress
+import socket
+from cryptography.exceptions import InvalidSignature, InvalidTag
+from cryptography.hazmat.primitives.ciphers.aead import ChaCha20Poly1305
+from cryptography.hazmat.primitives import serialization
+from cryptography.hazmat.primitives.asymmetric.ed25519 import Ed25519PrivateKey, Ed25519PublicKey
+from urllib.parse import urlencode, urlparse, urlunparse, parse_qs
 
 
 def is_in_terminal():
     # Heuristically check if we're running in a real terminal.
     return sys.stdin.isatty()
 
+# Flags whose values must stay off a relaunched terminal's command line, where other users can see them in ps
+SECRET_FLAGS = ("--e2e-key", "--password", "--token")
+SECRET_ARGS_FLAG = "--secret-args"  # Internal: a file of secret flags left by launch_new_terminal
+
+def split_secret_args(argv: list) -> tuple:
+    # (everything else, the secret flags with their values), for both --flag value and --flag=value
+    public, secret = [], []
+    args = iter(argv)
+    for arg in args:
+        name = arg.split("=", 1)[0]
+        if name in SECRET_FLAGS:
+            secret.append(arg)
+            if "=" not in arg:
+                value = next(args, None)
+                if value is not None:
+                    secret.append(value)
+        else:
+            public.append(arg)
+    return public, secret
+
+def stash_secret_args(secret: list) -> list:
+    # Hand secret flags over through a file only we can read (mkstemp makes it 0600) instead of the command line
+    if not secret:
+        return []
+    fd, path = tempfile.mkstemp(prefix="termchat-", suffix=".json")
+    with os.fdopen(fd, "w", encoding="utf-8") as f:
+        json.dump(secret, f)
+    return [SECRET_ARGS_FLAG, path]
+
+def is_stashed_secret_file(path: str) -> bool:
+    # Only a file stash_secret_args could have made is read and deleted, whatever path the flag names
+    name = os.path.basename(path)
+    return (name.startswith("termchat-") and name.endswith(".json")
+            and os.path.dirname(os.path.realpath(path)) == os.path.realpath(tempfile.gettempdir())
+            and os.path.isfile(path) and not os.path.islink(path))
+
+def restore_secret_args(argv: list) -> list:
+    # The reverse of stash_secret_args, run by the relaunched instance; the file is deleted once read
+    if SECRET_ARGS_FLAG not in argv:
+        return argv
+    index = argv.index(SECRET_ARGS_FLAG)
+    rest = argv[:index] + argv[index + 2:]
+    if index + 1 >= len(argv) or not is_stashed_secret_file(argv[index + 1]):
+        return rest
+    path = argv[index + 1]
+    try:
+        with open(path, "r", encoding="utf-8") as f:
+            secret = json.load(f)
+    except (OSError, ValueError):
+        secret = []
+    finally:
+        try:
+            os.remove(path)
+        except OSError:
+            pass
+    return rest + [str(arg) for arg in secret] if isinstance(secret, list) else rest
+
+CMD_SPECIAL_RE = re.compile(r'([()%!^"<>&|])')
+
+def cmd_quote(arg: str) -> str:
+    # Quote for the C runtime's argument parsing, then caret-escape everything cmd.exe would act on
+    # (quotes included, so cmd never sees a quoted section and every & or % stays literal)
+    quoted = '"' + re.sub(r'(\\*)"', r'\1\1\\"', re.sub(r'(\\+)$', r'\1\1', arg)) + '"'
+    return CMD_SPECIAL_RE.sub(r"^\1", quoted)
+
+def applescript_string(text: str) -> str:
+    return text.replace("\\", "\\\\").replace('"', '\\"')
+
 def launch_new_terminal():
     # Attempt to launch a new terminal window running this script.
     script_path = os.path.abspath(__file__)
+    public, secret = split_secret_args(sys.argv[1:])
+    forwarded = public + stash_secret_args(secret)  # Forward command line flags to the relaunched instance
 
     if platform.system() == "Windows":
-        python = shlex.quote(sys.executable)
         if script_path.lower().endswith(".py"):
-            cmd = f'start cmd /k {python} "{script_path}"'
+            command = [sys.executable, script_path] + forwarded
         else:
-            cmd = f'start cmd /k "{script_path}"'
-        os.system(cmd)
+            command = [script_path] + forwarded
+        # Started directly rather than through os.system, so only the new cmd parses the line
+        subprocess.Popen(f"cmd /k {' '.join(cmd_quote(arg) for arg in command)}", creationflags=subprocess.CREATE_NEW_CONSOLE)
         sys.exit(0)
 
     elif platform.system() == "Darwin":
-        python = shlex.quote(sys.executable)
+        python = applescript_string(shlex.quote(sys.executable))
+        args = applescript_string(" ".join(shlex.quote(arg) for arg in forwarded))
+        script = applescript_string(shlex.quote(script_path))
         # Set bounds for the window (pixels)
         left, top = 100, 100
         right, bottom = left + 912, top + 520
@@ -53,14 +152,14 @@ def launch_new_terminal():
             activate
             if (count of windows) = 0 then
                 -- no windows: create a new window and run the command
-                set theResult to do script "{python} {script_path}"
+                set theResult to do script "{python} {script} {args}"
                 delay 0.12
                 try
                     set bounds of front window to {{{left}, {top}, {right}, {bottom}}}
                 end try
             else
                 -- there is an existing window: run in the front window (creates a new tab in that window)
-                do script "{python} {script_path}" in front window
+                do script "{python} {script} {args}" in front window
             end if
         end tell
         '''
@@ -70,11 +169,12 @@ def launch_new_terminal():
 
     elif platform.system() == "Linux":
         python = shlex.quote(sys.executable)
+        args = " ".join(shlex.quote(arg) for arg in forwarded)
         terminals = [
-            f'gnome-terminal -- {python} "{script_path}"',
-            f'konsole -e {python} "{script_path}"',
-            f'xfce4-terminal -e "{python} {script_path}"',
-            f'xterm -e {python} "{script_path}"'
+            f'gnome-terminal -- {python} "{script_path}" {args}',
+            f'konsole -e {python} "{script_path}" {args}',
+            f'xfce4-terminal -e "{python} {script_path} {args}"',
+            f'xterm -e {python} "{script_path}" {args}'
         ]
         for term in terminals:
             try:
@@ -88,654 +188,5082 @@ def launch_new_terminal():
         print("Unsupported OS.")
         sys.exit(1)
         
-async def get_general_count(server_url: str) -> int:
-    # Converts wss://... to https://... and gets /general-count using certifi-backed SSL.
-    http_host = server_url.replace("wss://", "https://").split("/")[2]
-    endpoint = f"https://{http_host}/general-count"
-    ssl_context = ssl.create_default_context(cafile=certifi.where())
+# End-to-end encryption: message content is sealed with a key derived from a passphrase
+# shared out of band, so the server only ever relays ciphertext.
+E2E_PREFIX = "enc:v1:"
+
+def derive_e2e_key(passphrase: str, chat_name: str) -> bytes:
+    # The chat name salts the key so one passphrase gives different keys in different rooms
+    return hashlib.scrypt(
+        passphrase.encode("utf-8"),
+        salt=b"termchat-e2e:" + chat_name.encode("utf-8"),
+        n=2**14, r=8, p=1, dklen=32,
+    )
+
+def encrypt_content(key: bytes, content: str) -> str:
+    nonce = os.urandom(12)
+    sealed = ChaCha20Poly1305(key).encrypt(nonce, content.encode("utf-8"), None)
+    return E2E_PREFIX + base64.b64encode(nonce + sealed).decode("ascii")
+
+def wire_length(key: Optional[bytes], content: str) -> int:
+    # How long content is as sent: ciphertext is the base64 of nonce, text and tag, so it's worked out without encrypting
+    if key is None:
+        return len(content)
+    return len(E2E_PREFIX) + 4 * math.ceil((12 + len(content.encode("utf-8")) + 16) / 3)
+
+def decrypt_content(key: bytes, content: str) -> Optional[str]:
+    # None when the content was sealed with a different key or is corrupt
     try:
-        connector = aiohttp.TCPConnector(ssl=ssl_context)
-        async with aiohttp.ClientSession(connector=connector) as session:
-            async with session.get(endpoint, timeout=3) as resp:
-                if resp.status == 200:
-                    data = await resp.json()
-                    return data.get("userCount", 0)
-    except Exception:
+        raw = base64.b64decode(content[len(E2E_PREFIX):], validate=True)
+        return ChaCha20Poly1305(key).decrypt(raw[:12], raw[12:], None).decode("utf-8")
+    except (ValueError, InvalidTag):
+        return None
+
+# Optional message signing: an ed25519 key kept next to the settings (never inside them, so
+# /config export can't leak it) signs username, content and time; others verify with our public key.
+def signing_key_path() -> str:
+    return os.path.join(get_config_dir(), "signing_key.pem")
+
+def load_or_create_signing_key(path: str) -> Ed25519PrivateKey:
+    try:
+        with open(path, "rb") as f:
+            key = serialization.load_pem_private_key(f.read(), password=None)
+        if isinstance(key, Ed25519PrivateKey):
+            return key
+    except (OSError, ValueError):
         pass
-    return 0
+    key = Ed25519PrivateKey.generate()
+    pem = key.private_bytes(serialization.Encoding.PEM, serialization.PrivateFormat.PKCS8, serialization.NoEncryption())
+    try:
+        os.makedirs(os.path.dirname(path), exist_ok=True)
+        fd = os.open(path, os.O_WRONLY | os.O_CREAT | os.O_TRUNC, 0o600)
+        with os.fdopen(fd, "wb") as f:
+            f.write(pem)
+    except OSError:
+        pass  # Signing still works for this run, with a key that won't survive a restart
+    return key
 
-# User colors for cycling through usernames
-USER_COLORS = [
-    "red", "green", "yellow", "magenta", 
-    "cyan", "bright_red", "bright_yellow", 
-    "bright_magenta", "bright_cyan"
-]
+def public_key_text(key: Ed25519PrivateKey) -> str:
+    raw = key.public_key().public_bytes(serialization.Encoding.Raw, serialization.PublicFormat.Raw)
+    return base64.b64encode(raw).decode("ascii")
 
-# ASCII Art for TERMCHAT
-TERMCHAT_ASCII = """
-████████ ███████ ██████  ███    ███  ██████ ██   ██  █████  ████████ 
-   ██    ██      ██   ██ ████  ████ ██      ██   ██ ██   ██    ██    
-   ██    █████   ██████  ██ ████ ██ ██      ███████ ███████    ██    
-   ██    ██      ██   ██ ██  ██  ██ ██      ██   ██ ██   ██    ██    
-   ██    ███████ ██   ██ ██      ██  ██████ ██   ██ ██   ██    ██    
-"""
+def signed_bytes(username: str, content: str, signed_at: int) -> bytes:
+    # Newlines can't appear in a username, so the fields can't be shifted into each other
+    return f"{username}\n{signed_at}\n{content}".encode("utf-8")
 
-class SplashScreen(Screen):
-    # ASCII Art splash screen shown with a slide-up + fade-in animation
-    
-    CSS = """
-    SplashScreen {
-        align: center middle;
-        background: black;
-        color: #87CEEB;
-    }
-    
-    #splash {
-        width: auto;
-        height: auto;
-        content-align: center middle;
-        text-style: bold;
-        color: #87CEEB;
-    }
-    """
+def sign_message(key: Ed25519PrivateKey, username: str, content: str, signed_at: int) -> str:
+    return base64.b64encode(key.sign(signed_bytes(username, content, signed_at))).decode("ascii")
 
-    def compose(self) -> ComposeResult:
-        # Create the Static so we can animate it in on_mount.
-        yield Static(TERMCHAT_ASCII, id="splash")
+def verify_signature(public_key: str, username: str, content: str, signed_at: int, signature: str) -> bool:
+    try:
+        key = Ed25519PublicKey.from_public_bytes(base64.b64decode(public_key, validate=True))
+        key.verify(base64.b64decode(signature, validate=True), signed_bytes(username, content, signed_at))
+        return True
+    except (ValueError, InvalidSignature):
+        return False
 
-    def on_mount(self):
-        # Start the splash animation asynchronously and advance when done.
-        asyncio.create_task(self._animate_and_advance())
+SIGNATURE_MAX_AGE = 300  # Seconds a live signed message may be older (or newer) than our clock
+SEEN_SIGNATURES_LIMIT = 5000  # (username, signed_at) pairs remembered to catch replayed messages
 
-    async def _animate_and_advance(self):
-        # Animate the splash logo: slide up from below and fade in with a strong ease-out.
-        # After the animation completes we wait a short moment then move to the connection screen.
-        
-        # Strong ease-out function (exponential) for a noticeable fast-then-slow effect
-        def ease_out_expo(t: float) -> float:
-            if t >= 1.0:
-                return 1.0
-            return 1 - pow(2, -20 * t)
+def signature_state(data: dict, trusted_keys: dict, seen: Optional[dict] = None, now: Optional[float] = None) -> Optional[str]:
+    # None for unsigned messages, "verified" for a good signature from a key the user trusts, else "unverified".
+    # `seen` maps (username, signed_at) to the message id that first used it, so a copy resent under another
+    # id is caught; `now` rejects live messages signed too long ago. Backlog passes no `now`, it's old by design.
+    signature, signed_at = data.get("signature"), data.get("signed_at")
+    if not isinstance(signature, str):
+        return None
+    key = trusted_keys.get(data.get("username"))
+    if key is None or not isinstance(signed_at, int) or isinstance(signed_at, bool):
+        return "unverified"
+    if now is not None and abs(now * 1000 - signed_at) > SIGNATURE_MAX_AGE * 1000:
+        return "unverified"
+    if not verify_signature(key, str(data.get("username")), str(data.get("content", "")), signed_at, signature):
+        return "unverified"
+    if seen is not None:
+        seen_key, message_id = (data.get("username"), signed_at), data.get("id")
+        if seen_key in seen:
+            # The same signature can only be shown again for the same message, e.g. in a resumed backlog
+            return "verified" if message_id is not None and seen[seen_key] == message_id else "unverified"
+        seen[seen_key] = message_id
+        if len(seen) > SEEN_SIGNATURES_LIMIT:
+            del seen[next(iter(seen))]
+    return "verified"
 
-        # 60fps animation parameters
-        duration = 1.2
-        target_fps = 60
-        frame_time = 1.0 / target_fps
-        total_frames = int(duration * target_fps)
-        start_offset_y = 25  # start a few rows lower (slides up to 0)
-        
-        try:
-            splash = self.query_one("#splash", Static)
-            
-            # Initialize starting position and color fade
-            splash.styles.opacity = 0.0
-            splash.styles.offset = (0, start_offset_y)
-            splash.styles.color = "#000000"  # Start black for color fade
-            
-            self.refresh()
-            await asyncio.sleep(0.016)
-            
-            # 60fps animation loop - slide up AND color fade
-            for i in range(total_frames + 1):
-                t = i / total_frames
-                eased = ease_out_expo(t)
-                
-                # Slide animation
-                y = int(start_offset_y * (1 - eased))
-                opacity = float(eased)
-                
-                # Color fade animation (black to cyan)
-                r = int(135 * eased)  # #87CEEB = 135, 206, 235
-                g = int(206 * eased)
-                b = int(235 * eased)
-                color = f"#{r:02x}{g:02x}{b:02x}"
-                
-                # Apply all animations
-                splash.styles.offset = (0, y)
-                splash.styles.opacity = opacity
-                splash.styles.color = color
-                
-                splash.refresh()
-                await asyncio.sleep(frame_time)
+class Glyphs:
+    # Decorative characters used across the UI, so terminals without good Unicode fonts can use ASCII
 
-            # Ensure final exact values
-            splash.styles.offset = (0, 0)
-            splash.styles.opacity = 1.0
-            splash.styles.color = "#87CEEB"
-            splash.refresh()
-            
-        except Exception:
-            await asyncio.sleep(duration)
+    def __init__(self, dash: str, ellipsis: str, times: str, separator: str, indicator: str, check: str, cross: str, warning: str, reply: str, block: str, border: str, smiley: str, emoji: bool, spark: str):
+        self.dash = dash
+        self.ellipsis = ellipsis
+        self.times = times
+        self.separator = separator
+        self.indicator = indicator
+        self.check = check
+        self.cross = cross
+        self.warning = warning
+        self.reply = reply
+        self.block = block  # Fill character for the splash logo
+        self.border = border  # Textual border style for boxed widgets
+        self.smiley = smiley  # Label of the emoji picker button
+        self.emoji = emoji  # Whether :shortcodes: are expanded to emoji
+        self.spark = spark  # Sparkline levels, lowest first
 
-        await asyncio.sleep(0.25)
-        self.show_connection()
+    @classmethod
+    def unicode(cls) -> "Glyphs":
+        return cls(dash="—", ellipsis="…", times="×", separator="·", indicator="●", check="✓", cross="✗", warning="⚠", reply="↳", block="█", border="solid", smiley="☺", emoji=True, spark="▁▂▃▄▅▆▇█")
 
-    def show_connection(self):
-        self.app.push_screen("connection")
+    @classmethod
+    def ascii(cls) -> "Glyphs":
+        return cls(dash="-", ellipsis="...", times="x", separator="|", indicator="*", check="ok", cross="!", warning="?", reply="->", block="#", border="ascii", smiley=":)", emoji=False, spark="_.-=+*#")
 
+glyphs = Glyphs.unicode()  # Swapped for Glyphs.ascii() by --ascii before the app starts
 
-class ConnectionScreen(Screen):
-    # Modal screen for connection details 
-    
-    def __init__(self):
-        super().__init__()
-        self.connecting = False
-        self.general_count = None
-        
-    CSS = """
-    ConnectionScreen {
-        align: center middle;
-        background: black;
-    }
-    
-    #dialog {
-        width: 80;
-        height: 25;
-        border: solid #87CEEB;
-        background: black;
-        color: white;
-    }
-    #title {
-        dock: top;
-        width: 100%;
-        content-align: center middle;
-        text-style: bold;
-        color: #87CEEB;
-        background: black;
-        padding: 1 0;
-    }
-    #indicator_row {
-        layout: horizontal;
-        width: 100%;
-        align: center middle;
-        margin-bottom: 1;
-    }
-    #indicator_light {
-        width: 2;
-        content-align: center middle;
-    }
-    #indicator_text {
-        margin-left: 1;
-        color: white;
-    }
+def use_glyphs(new_glyphs: Glyphs):
+    global glyphs
+    glyphs = new_glyphs
 
-    #hint_row {
-        width: 100%;
-        content-align: center middle;
-        margin-top: 2;
-    }
-    #form {
-        layout: vertical;
-        height: auto;
-        margin: 2;
-        padding: 1;
-    }
-    .form-row {
-        height: 3;
-        layout: horizontal;
-        margin-bottom: 1;
-    }
-    .label {
-        width: 15;
-        content-align: right middle;
-        margin-right: 2;
-        color: #cccccc;
-        text-style: bold;
-    }
-    .input {
-        width: 35;
-        height: 3;
-        color: white;
-        border: solid #333333;
-    }
-    .input:focus {
-        border: solid #87CEEB;
-    }
-    #status_label {
-        width: 40;
-        content-align: center middle;
-        color: yellow;
-        text-style: bold;
-    }
-    """
-    
-    BINDINGS = [
-        Binding("ctrl+c", "quit", "Quit"),
-        Binding("escape", "quit", "Quit"),
-        Binding("tab", "focus_next", "Next Field"),
-        Binding("shift+tab", "focus_previous", "Previous Field"),
-        Binding("enter", "connect", "Connect"),
-    ]
+def apply_glyph_borders(root):
+    # CSS borders are drawn with box characters, so redraw them in the active border style
+    if glyphs.border == "solid":
+        return
+    for widget in [root, *root.query("*")]:
+        for edge in ("border_top", "border_right", "border_bottom", "border_left"):
+            kind, color = getattr(widget.styles, edge)
+            if kind not in ("", "none", "hidden"):
+                setattr(widget.styles, edge, (glyphs.border, color))
 
-    def compose(self) -> ComposeResult:
-        with Container(id="dialog"):
-            yield Label("TERMCHAT Connection", id="title")
-            with Container(id="indicator_row"):
-                yield Static("●", id="indicator_light")
-                yield Label("Checking server...", id="indicator_text")
-            yield Container(
-                Label(
-                    "       Keep the [#cccccc]chat name[/#cccccc] and [#cccccc]password[/#cccccc] blank to join the [#90ee90]general chat[/#90ee90]",
-                    markup=True
-                ),
-                id="hint_row"
-            )
-            with Container(id="form"):
-                with Container(classes="form-row"):
-                    yield Label("Username:", classes="label")
-                    yield Input(placeholder="Enter username", id="username_input", classes="input")
-                with Container(classes="form-row"):
-                    yield Label("Chat name:", classes="label")
-                    yield Input(placeholder="Enter chat name", id="chatname_input", classes="input")
-                with Container(classes="form-row"):
-                    yield Label("Password:", classes="label")
-                    yield Input(placeholder="Enter password", password=True, id="password_input", classes="input")
-                with Container(classes="form-row"):
-                    yield Label("", id="status_label", classes="label")
-        yield Label("", id="general_count_label")
+def display_content(key: Optional[bytes], content: str) -> str:
+    # What to show for received content, decrypting it when it is an encrypted blob
+    if not content.startswith(E2E_PREFIX):
+        return content
+    if key is None:
+        return f"[encrypted {glyphs.dash} no key]"
+    plaintext = decrypt_content(key, content)
+    return plaintext if plaintext is not None else f"[encrypted {glyphs.dash} wrong key]"
 
-    def on_mount(self):
-        self.query_one("#username_input").focus()
-        self.set_timer(0.1, self.check_server_status)
-        asyncio.create_task(self.update_general_count())
+def parse_bind_address(value: str) -> str:
+    # argparse type for --bind: a literal local IPv4 or IPv6 address
+    try:
+        return str(ipaddress.ip_address(value))
+    except ValueError:
+        raise argparse.ArgumentTypeError(f"'{value}' is not an IP address")
 
-    async def update_general_count(self):
-        count = await get_general_count(self.app.server_url)
-        self.general_count = count
-        label = self.query_one("#general_count_label")
-        label.update(f"[#90ee90]{count}[/#90ee90] user/s in general chat")
+def bind_options(bind_address: Optional[str]) -> dict:
+    # Extra connect arguments that pin the local source address; empty lets the OS choose
+    return {"local_addr": (bind_address, 0)} if bind_address else {}
 
-    async def check_server_status(self):
-    # Foolproof: check server reachability using a certifi-backed SSL context
-        try:
-            import websockets  # keep local import if desired
-            ssl_context = ssl.create_default_context(cafile=certifi.where())
-            # short timeout/ping to keep this check fast
-            ws = await websockets.connect(self.app.server_url, ssl=ssl_context, ping_timeout=2)
-            await ws.close()
-            self.server_available = True
-        except Exception:
-            self.server_available = False
-        self.update_indicator()
+def tls_options(url: str) -> dict:
+    # The certifi-backed TLS context for wss:// servers; websockets refuses one for a plain ws:// URL
+    if urlparse(url).scheme != "wss":
+        return {}
+    return {"ssl": ssl.create_default_context(cafile=certifi.where())}
 
-    def update_indicator(self):
-        indicator_light = self.query_one("#indicator_light")
-        indicator_text = self.query_one("#indicator_text")
-        if self.server_available:
-            indicator_light.update("●")
-            indicator_light.styles.color = "#00ff00"
-            indicator_text.update("Server available")
-            indicator_text.styles.color = "#00ff00"
+def is_bind_error(error: OSError, bind_address: Optional[str]) -> bool:
+    # The OS refuses a source address the machine doesn't own, or one of the wrong IP family
+    return bool(bind_address) and error.errno in (errno.EADDRNOTAVAIL, errno.EINVAL, errno.EAFNOSUPPORT)
+
+# Friendly text for connect failures we can recognise, keyed by classify_connect_error
+CONNECT_ERROR_MESSAGES = {
+    "dns": "Can't reach server - check your internet connection",
+    "refused": "Server refused the connection",
+    "timeout": "Timed out connecting to server",
+}
+DNS_RETRIES = 2  # Extra attempts after a failed lookup, which is often a passing network blip
+DNS_RETRY_DELAY = 3.0
+
+# Failures a retry would only repeat, so reconnecting stops instead of backing off forever
+FATAL_CONNECT_ERRORS = {"auth", "forbidden"}
+
+def classify_connect_error(error: BaseException) -> str:
+    # Sort a connect failure into dns, refused, timeout, auth (the join was turned down), forbidden
+    # (HTTP 401/403) or other by its type, falling back to the message for libraries that wrap the original error
+    if isinstance(error, JoinRejected):
+        return "auth"
+    status = getattr(error, "status_code", None) or getattr(getattr(error, "response", None), "status_code", None)
+    if status in (401, 403):
+        return "forbidden"
+    if isinstance(error, socket.gaierror):
+        return "dns"
+    if isinstance(error, ConnectionRefusedError):
+        return "refused"
+    if isinstance(error, (asyncio.TimeoutError, TimeoutError, socket.timeout)):
+        return "timeout"
+    text = str(error)
+    if "Name or service not known" in text or "getaddrinfo failed" in text or "nodename nor servname" in text:
+        return "dns"
+    if "Connection refused" in text:
+        return "refused"
+    if "server rejected WebSocket connection" in text and ("401" in text or "403" in text):
+        return "forbidden"
+    return "other"
+
+# Seconds to wait for the WebSocket close handshake when quitting
+SHUTDOWN_TIMEOUT = 2.0
+
+# Backend server URL (HTTPS WebSocket on port 443)
+DEFAULT_SERVER_URL = "wss://termchat-f9cgabe4ajd9djb9.australiaeast-01.azurewebsites.net"
+
+def parse_server_url(value: str) -> str:
+    # argparse type for --server: a ws:// or wss:// URL, or a bare host that gets wss://
+    url = value.strip()
+    if "://" not in url:
+        url = "wss://" + url
+    parsed = urlparse(url)
+    if parsed.scheme not in ("ws", "wss") or not parsed.netloc:
+        raise argparse.ArgumentTypeError(f"'{value}' is not a ws:// or wss:// server URL")
+    return url
+
+def resolve_server_url(server_url: str, default_path: str) -> str:
+    # The WebSocket endpoint to connect to. A path in the URL is kept (minus a trailing slash);
+    # a bare host gets the websocket_path setting, which is empty unless the server wants one.
+    parsed = urlparse(server_url)
+    path = parsed.path.rstrip("/") or default_path
+    return urlunparse((parsed.scheme, parsed.netloc, path, "", parsed.query, ""))
+
+def http_endpoint(server_url: str, path: str) -> str:
+    # An HTTP endpoint on the same host as the WebSocket server, whatever path that is on
+    parsed = urlparse(server_url)
+    scheme = "http" if parsed.scheme == "ws" else "https"
+    return f"{scheme}://{parsed.netloc}{path}"
+
+def build_join_uri(server_url: str, chat_name: str) -> str:
+    # Shareable link that pre-fills the connection screen; never includes the password
+    return "termchat://join?" + urlencode({"server": server_url, "chat": chat_name})
+
+def build_message_link(server_url: str, chat_name: str, message_id) -> str:
+    # Points at one message, for servers whose messages can be looked up by id
+    return "termchat://message?" + urlencode({"server": server_url, "chat": chat_name, "id": message_id})
+
+def format_message_markdown(username: str, content: str, sent_at: float) -> str:
+    # The content already is markdown; name and time go in front, and later lines are indented to match
+    stamp = time.strftime("%H:%M", time.localtime(sent_at))
+    return f"**{username}** ({stamp}): " + content.replace("\n", "\n    ")
+
+AUTH_METHODS = ("password", "token", "none")
+
+def build_join_payload(username: str, chat_name: str, auth_method: str, secret: str, session_token: Optional[str] = None) -> dict:
+    # Only the field the auth method needs is included, so password-only servers see what they always did
+    payload = {"type": "join", "username": username, "chatname": chat_name}
+    if session_token:
+        payload["session_token"] = session_token
+    elif auth_method == "password":
+        payload["password"] = secret
+    elif auth_method == "token":
+        payload["token"] = secret
+    return payload
+
+def parse_join_uri(uri: str) -> Optional[dict]:
+    # Parse termchat://join?server=...&chat=... into {"server", "chat"}, or None if malformed
+    try:
+        parsed = urlparse(uri)
+    except ValueError:
+        return None
+    if parsed.scheme != "termchat" or parsed.netloc != "join":
+        return None
+    query = parse_qs(parsed.query)
+    server = query.get("server", [""])[0].strip()
+    chat = query.get("chat", [""])[0].strip()
+    if not server or not chat or not server.startswith("wss://"):
+        return None
+    return {"server": server, "chat": chat}
+
+async def get_general_count(session: aiohttp.ClientSession, server_url: str, path: str = "/general-count") -> int:
+    # Gets the general chat head count over the shared HTTP session; an empty path skips the request.
+    if not path:
+        return 0
+    endpoint = http_endpoint(server_url, path)
+    try:
+        async with session.get(endpoint, timeout=aiohttp.ClientTimeout(total=3)) as resp:
+            if resp.status == 200:
+                data = await resp.json()
+                return data.get("userCount", 0)
+    except Exception:
+        pass
+    return 0
+
+MAX_BANNER_LENGTH = 200  # Characters of a server banner shown on the splash
+BANNER_TIMEOUT = 3.0
+
+def clean_banner(text: str) -> Optional[str]:
+    # Up to three non-empty lines, trimmed to a length that fits under the logo
+    lines = [line.strip() for line in text.splitlines() if line.strip()][:3]
+    banner = "\n".join(lines)
+    if len(banner) > MAX_BANNER_LENGTH:
+        banner = banner[:MAX_BANNER_LENGTH].rstrip() + "..."
+    return banner or None
+
+async def get_banner(session: aiohttp.ClientSession, server_url: str, path: str = "/banner") -> Optional[str]:
+    # The server's message of the day from GET /banner, as plain text. Any failure means no banner.
+    if not path:
+        return None
+    endpoint = http_endpoint(server_url, path)
+    try:
+        async with session.get(endpoint, timeout=aiohttp.ClientTimeout(total=BANNER_TIMEOUT)) as resp:
+            if resp.status == 200:
+                return clean_banner(await resp.text())
+    except Exception:
+        pass
+    return None
+
+def get_config_dir() -> str:
+    # Per-user config directory following each platform's convention.
+    if platform.system() == "Windows":
+        base = os.environ.get("APPDATA", os.path.expanduser("~"))
+    elif platform.system() == "Darwin":
+        base = os.path.expanduser("~/Library/Application Support")
+    else:
+        base = os.environ.get("XDG_CONFIG_HOME", os.path.expanduser("~/.config"))
+    return os.path.join(base, "termchat")
+
+SETTINGS_PATH = os.path.join(get_config_dir(), "settings.json")
+
+# Defaults for every persisted setting; unknown keys in the file are ignored.
+DEFAULT_SETTINGS = {
+    "enter_sends": True,
+    "theme": "auto",  # auto follows the desktop's dark or light mode
+    "group_gap_minutes": 10,
+    "sender_separators": True,
+    "splash_duration_ms": 1200,
+    "retry_failed_sends": True,
+    "join_timeout_seconds": 8,
+    "optimistic_send": True,
+    "dnd": False,
+    "dnd_schedule": "",  # Daily quiet hours as HH:MM-HH:MM, empty for none
+    "presence_summary_threshold": 100,  # Above this many people, join/leave lines become a summary; 0 never does
+    "sign_messages": False,  # Sign messages with the key in signing_key.pem
+    "normalize_outgoing": True,  # Tidy whitespace in messages before sending them
+    "timestamp_style": "hidden",  # inline, gutter (at the start of each group) or hidden
+    "auto_focus": True,  # Focus the input on launch, on connect and after each send
+    "scroll_tolerance_lines": 2,  # Counts as at the bottom of the log within this many lines
+    "forward_unknown_commands": False,  # Send /commands we don't know to the server to run
+    "websocket_path": "",  # Added to server URLs that are just a host, e.g. /ws
+    "general_count_path": "/general-count",
+    "banner_path": "/banner",
+    "profiles": [],
+    "recent_rooms": [],
+    "ignored": [],  # Usernames whose messages are hidden, see /ignore
+    "color_overrides": {},  # Lowercased username -> color picked with /color
+    "trusted_keys": {},  # Username -> signing key confirmed with /trust; only these get the verified mark
+    "blocked_rooms": [],  # {"server", "chat"} pairs Termchat refuses to join, see /block-room
+}
+
+def parse_bool_setting(value: str) -> bool:
+    lowered = value.lower()
+    if lowered in ("on", "true", "yes", "1"):
+        return True
+    if lowered in ("off", "false", "no", "0"):
+        return False
+    raise ValueError("expected on or off")
+
+def int_setting(minimum: int, maximum: int):
+    # Parser for whole numbers within an inclusive range
+    def parse(value: str) -> int:
+        try:
+            number = int(value)
+        except ValueError:
+            raise ValueError(f"expected a number from {minimum} to {maximum}")
+        if not minimum <= number <= maximum:
+            raise ValueError(f"expected a number from {minimum} to {maximum}")
+        return number
+    return parse
+
+def choice_setting(*choices: str):
+    # Parser for settings limited to a fixed set of names
+    def parse(value: str) -> str:
+        lowered = value.lower()
+        if lowered not in choices:
+            raise ValueError(f"expected one of: {', '.join(choices)}")
+        return lowered
+    return parse
+
+QUIET_HOURS_RE = re.compile(r"^([01]?\d|2[0-3]):([0-5]\d)-([01]?\d|2[0-3]):([0-5]\d)$")
+
+def parse_quiet_hours(schedule: str) -> Optional[tuple]:
+    # "22:00-08:00" -> (start, end) in minutes after midnight, or None if malformed
+    match = QUIET_HOURS_RE.match(schedule.strip())
+    if not match:
+        return None
+    start_hour, start_minute, end_hour, end_minute = (int(group) for group in match.groups())
+    return (start_hour * 60 + start_minute, end_hour * 60 + end_minute)
+
+def in_quiet_window(start: int, end: int, minute: int) -> bool:
+    # Windows whose end is before their start wrap past midnight; equal ends mean an empty window
+    if start <= end:
+        return start <= minute < end
+    return minute >= start or minute < end
+
+def in_quiet_hours(schedule: str, now: time.struct_time) -> bool:
+    window = parse_quiet_hours(schedule) if schedule else None
+    return bool(window) and in_quiet_window(*window, now.tm_hour * 60 + now.tm_min)
+
+def parse_schedule_setting(value: str) -> str:
+    if value.lower() in ("off", "none", ""):
+        return ""
+    if parse_quiet_hours(value) is None:
+        raise ValueError("expected HH:MM-HH:MM, e.g. 22:00-08:00, or off")
+    return value.strip()
+
+def parse_path_setting(value: str) -> str:
+    # URL paths like /ws; "off" or nothing means none
+    value = value.strip()
+    if value.lower() in ("off", "none", ""):
+        return ""
+    if any(char.isspace() or char in "?#" for char in value):
+        raise ValueError("expected a URL path like /ws, or off")
+    return "/" + value.strip("/")
+
+def parse_theme_setting(value: str) -> str:
+    if value.lower() != "auto" and resolve_theme(value) is None:
+        raise ValueError(f"expected #RRGGBB, auto or one of: {', '.join(THEMES)}")
+    return value.lower()
+
+# Maps each /set key to the parser that validates and converts typed values.
+SETTING_PARSERS = {
+    "enter_sends": parse_bool_setting,
+    "theme": parse_theme_setting,
+    "group_gap_minutes": int_setting(0, 1440),
+    "sender_separators": parse_bool_setting,
+    "splash_duration_ms": int_setting(0, 10000),
+    "retry_failed_sends": parse_bool_setting,
+    "join_timeout_seconds": int_setting(1, 120),
+    "optimistic_send": parse_bool_setting,
+    "dnd": parse_bool_setting,
+    "dnd_schedule": parse_schedule_setting,
+    "presence_summary_threshold": int_setting(0, 100000),
+    "sign_messages": parse_bool_setting,
+    "normalize_outgoing": parse_bool_setting,
+    "timestamp_style": choice_setting("inline", "gutter", "hidden"),
+    "auto_focus": parse_bool_setting,
+    "scroll_tolerance_lines": int_setting(0, 50),
+    "forward_unknown_commands": parse_bool_setting,
+    "websocket_path": parse_path_setting,
+    "general_count_path": parse_path_setting,
+    "banner_path": parse_path_setting,
+}
+
+def format_setting(value) -> str:
+    if isinstance(value, bool):
+        return "on" if value else "off"
+    if value == "":
+        return "off"
+    return str(value)
+
+def load_settings() -> dict:
+    # Load settings from disk, falling back to defaults for anything missing or invalid.
+    settings = copy.deepcopy(DEFAULT_SETTINGS)
+    try:
+        with open(SETTINGS_PATH, "r", encoding="utf-8") as f:
+            stored = json.load(f)
+        if isinstance(stored, dict):
+            for key, value in stored.items():
+                if key in DEFAULT_SETTINGS and type(value) is type(DEFAULT_SETTINGS[key]):
+                    settings[key] = value
+    except (OSError, ValueError):
+        pass
+    return settings
+
+def save_settings(settings: dict):
+    # Persist settings; failures are non-fatal since the client works without a config file.
+    try:
+        os.makedirs(os.path.dirname(SETTINGS_PATH), exist_ok=True)
+        with open(SETTINGS_PATH, "w", encoding="utf-8") as f:
+            json.dump(settings, f, indent=2)
+    except OSError:
+        pass
+
+CONFIG_BUNDLE_VERSION = 1  # Bumped when the /config export format changes incompatibly
+
+# List settings whose entries could pick up a password or token, e.g. a hand-edited profile
+SECRET_BEARING_SETTINGS = ("profiles", "recent_rooms", "blocked_rooms")
+
+def strip_secrets(key: str, value):
+    # Drop secret fields from those entries, so a bundle is safe to copy between machines. Only there:
+    # in maps like trusted_keys the keys are usernames, and someone may well be called "token"
+    if key not in SECRET_BEARING_SETTINGS or not isinstance(value, list):
+        return value
+    return [{field: item for field, item in entry.items() if field not in SECRET_FIELDS} if isinstance(entry, dict) else entry
+            for entry in value]
+
+def export_config(settings: dict) -> dict:
+    return {
+        "termchat_config": CONFIG_BUNDLE_VERSION,
+        "settings": {key: strip_secrets(key, copy.deepcopy(settings[key])) for key in DEFAULT_SETTINGS},
+    }
+
+def is_number(value) -> bool:
+    return isinstance(value, (int, float)) and not isinstance(value, bool)
+
+def valid_profile(entry) -> bool:
+    try:
+        make_profile(entry["name"], entry["url"], entry.get("default_chat", ""), entry.get("default_username", ""))
+    except (TypeError, KeyError, AttributeError, ValueError):
+        return False
+    return isinstance(entry["name"], str) and bool(entry["name"]) and all(
+        isinstance(entry.get(key, ""), str) for key in ("default_chat", "default_username"))
+
+def valid_room(entry) -> bool:
+    return isinstance(entry, dict) and all(isinstance(entry.get(key), str) and entry[key] for key in ("server", "chat"))
+
+# What each item of a list setting must look like to be imported
+CONFIG_ENTRY_VALIDATORS = {
+    "profiles": valid_profile,
+    "recent_rooms": lambda entry: valid_room(entry) and is_number(entry.get("last_joined")),
+    "blocked_rooms": valid_room,
+    "ignored": lambda entry: isinstance(entry, str) and bool(entry),
+}
+
+def import_config(bundle, current: dict, replace: bool) -> tuple:
+    # Validate a bundle and return (new settings, keys that were skipped). Replacing starts from the
+    # defaults; merging keeps whatever the bundle doesn't mention. Raises ValueError if it isn't a bundle.
+    if not isinstance(bundle, dict) or not isinstance(bundle.get("settings"), dict):
+        raise ValueError("not a Termchat config export")
+    version = bundle.get("termchat_config")
+    if not isinstance(version, int) or isinstance(version, bool):
+        raise ValueError("missing config version")
+    if version > CONFIG_BUNDLE_VERSION:
+        raise ValueError(f"made by a newer Termchat (config version {version})")
+    settings = copy.deepcopy(DEFAULT_SETTINGS if replace else current)
+    skipped = []
+    for key, value in bundle["settings"].items():
+        if key not in DEFAULT_SETTINGS or type(value) is not type(DEFAULT_SETTINGS[key]):
+            skipped.append(key)
+            continue
+        if key in SETTING_PARSERS:
+            try:
+                value = SETTING_PARSERS[key](format_setting(value))
+            except ValueError:
+                skipped.append(key)
+                continue
+        if key in CONFIG_ENTRY_VALIDATORS:
+            # Bad entries are dropped one by one, so one broken profile doesn't lose the rest
+            valid = CONFIG_ENTRY_VALIDATORS[key]
+            skipped.extend(f"{key}[{index}]" for index, entry in enumerate(value) if not valid(entry))
+            value = [entry for entry in value if valid(entry)]
+        elif isinstance(value, dict):
+            skipped.extend(f"{key}[{name}]" for name, item in value.items() if not isinstance(item, str))
+            value = {name: item for name, item in value.items() if isinstance(item, str)}
+        settings[key] = strip_secrets(key, copy.deepcopy(value))
+    return settings, skipped
+
+DEFAULT_THEME_COLOR = "#87CEEB"
+DEFAULT_BACKGROUND_COLOR = "#000000"
+
+# Named starting themes as (theme color, background color)
+THEMES = {
+    "default": (DEFAULT_THEME_COLOR, DEFAULT_BACKGROUND_COLOR),
+    "classic-green": ("#33FF33", "#000000"),
+    "amber": ("#FFB000", "#000000"),
+    "solarized-dark": ("#268BD2", "#002B36"),
+    "mono": ("#FFFFFF", "#000000"),
+    "light": ("#005F87", "#EEEEEE"),
+}
+
+OS_SCHEME_POLL_INTERVAL = 30.0  # Seconds between checks for a desktop dark/light switch
+
+def detect_os_color_scheme() -> Optional[str]:
+    # "dark" or "light" from the desktop's setting, or None where it can't be told
+    try:
+        if platform.system() == "Darwin":
+            # The key only exists while dark mode is on
+            result = subprocess.run(["defaults", "read", "-g", "AppleInterfaceStyle"],
+                                    capture_output=True, text=True, timeout=1)
+            return "dark" if "dark" in result.stdout.lower() else "light"
+        if platform.system() == "Windows":
+            import winreg
+            with winreg.OpenKey(winreg.HKEY_CURRENT_USER,
+                                r"Software\Microsoft\Windows\CurrentVersion\Themes\Personalize") as key:
+                return "light" if winreg.QueryValueEx(key, "AppsUseLightTheme")[0] else "dark"
+        result = subprocess.run(["gsettings", "get", "org.gnome.desktop.interface", "color-scheme"],
+                                capture_output=True, text=True, timeout=1)
+        if result.returncode == 0:
+            if "dark" in result.stdout:
+                return "dark"
+            if "light" in result.stdout:
+                return "light"
+    except (OSError, ValueError, subprocess.SubprocessError):
+        pass
+    return None
+
+def pick_theme(requested: Optional[str], setting: str, os_scheme: Optional[str]) -> str:
+    # --theme beats the theme setting, which beats the desktop; "auto" follows the desktop,
+    # falling back to the dark default when it can't be detected
+    if requested:
+        return requested
+    if setting != "auto":
+        return setting
+    return "light" if os_scheme == "light" else "default"
+
+def text_color_for(background: str) -> str:
+    # Plain text that stays readable on a light background as well as the usual dark ones
+    bg = hex_to_rgb(background) if HEX_COLOR_RE.match(background) else (0, 0, 0)
+    return "black" if contrast_ratio((0, 0, 0), bg) > contrast_ratio((255, 255, 255), bg) else "white"
+
+HEX_COLOR_RE = re.compile(r"^#[0-9a-fA-F]{6}$")
+
+def resolve_theme(value: str) -> Optional[tuple]:
+    # Accepts a preset name or a #RRGGBB theme color (on the default background)
+    if value.lower() in THEMES:
+        return THEMES[value.lower()]
+    if HEX_COLOR_RE.match(value):
+        return (value.upper(), DEFAULT_BACKGROUND_COLOR)
+    return None
+
+def divider_for(prev: Optional[tuple], current: tuple, gap_minutes: int) -> Optional[str]:
+    # Decide what separates two consecutive (sender, timestamp) messages:
+    # "time" after a long enough gap, "sender" when the sender changes, else None.
+    # A gap of 0 disables time dividers.
+    if prev is None:
+        return None
+    prev_sender, prev_time = prev
+    sender, timestamp = current
+    if gap_minutes > 0 and timestamp - prev_time > gap_minutes * 60:
+        return "time"
+    if sender != prev_sender:
+        return "sender"
+    return None
+
+def normalize_message(text: str) -> Optional[str]:
+    # Trim trailing whitespace on every line, drop blank lines at either end and squash runs of
+    # three or more blank lines into one. Indentation is left alone. None if nothing is left.
+    lines = [line.rstrip() for line in text.split("\n")]
+    while lines and not lines[0]:
+        lines.pop(0)
+    while lines and not lines[-1]:
+        lines.pop()
+    if not lines:
+        return None
+    result = []
+    blanks = 0
+    for line in lines:
+        if line:
+            result.extend([""] * (1 if blanks >= 3 else blanks))
+            blanks = 0
+            result.append(line)
+        else:
+            blanks += 1
+    return "\n".join(result)
+
+def starts_group(prev: Optional[tuple], divider: Optional[str]) -> bool:
+    # A message opens a new group when it is the first one or anything divides it from the last
+    return prev is None or divider is not None
+
+def add_timestamp(markup: str, style: str, sent_at: float, group_start: bool) -> str:
+    # inline prefixes every message; gutter puts a faint time after the first message of a group
+    stamp = time.strftime("%H:%M", time.localtime(sent_at))
+    if style == "inline":
+        return f"[dim]\\[{stamp}][/dim] {markup}"
+    if style == "gutter" and group_start:
+        return f"{markup}  [dim]{stamp}[/dim]"
+    return markup
+
+def find_profile(profiles: list, name: str) -> Optional[dict]:
+    # Profile names are matched case-insensitively
+    for profile in profiles:
+        if profile.get("name", "").lower() == name.lower():
+            return profile
+    return None
+
+def make_profile(name: str, url: str, default_chat: str = "", default_username: str = "") -> dict:
+    # Server profiles never hold passwords
+    if not url.startswith(("wss://", "ws://")):
+        raise ValueError("server URL must start with wss:// or ws://")
+    return {"name": name, "url": url, "default_chat": default_chat, "default_username": default_username}
+
+def add_profile(profiles: list, profile: dict):
+    if find_profile(profiles, profile["name"]):
+        raise ValueError(f"a server named '{profile['name']}' already exists")
+    profiles.append(profile)
+
+def edit_profile(profiles: list, profile: dict):
+    existing = find_profile(profiles, profile["name"])
+    if existing is None:
+        raise ValueError(f"no server named '{profile['name']}'")
+    profiles[profiles.index(existing)] = profile
+
+def remove_profile(profiles: list, name: str) -> bool:
+    existing = find_profile(profiles, name)
+    if existing is None:
+        return False
+    profiles.remove(existing)
+    return True
+
+# Built-in :shortcode: emoji expanded in received messages
+EMOJI_SHORTCODES = {
+    "smile": "😄", "grin": "😁", "joy": "😂", "wink": "😉", "blush": "😊",
+    "heart_eyes": "😍", "thinking": "🤔", "sob": "😭", "angry": "😠", "sunglasses": "😎",
+    "scream": "😱", "skull": "💀", "thumbsup": "👍", "+1": "👍", "thumbsdown": "👎",
+    "-1": "👎", "clap": "👏", "wave": "👋", "pray": "🙏", "eyes": "👀",
+    "heart": "❤️", "fire": "🔥", "star": "⭐", "sparkles": "✨", "tada": "🎉",
+    "100": "💯", "rocket": "🚀", "check": "✅", "x": "❌", "warning": "⚠️",
+    "coffee": "☕", "pizza": "🍕", "hamburger": "🍔", "cat": "🐱", "dog": "🐶",
+}
+
+# Tabs in the emoji picker; "all" also includes the server's custom emoji
+EMOJI_CATEGORIES = {
+    "smileys": ["smile", "grin", "joy", "wink", "blush", "heart_eyes", "thinking", "sob", "angry", "sunglasses", "scream", "skull"],
+    "gestures": ["thumbsup", "thumbsdown", "clap", "wave", "pray", "eyes"],
+    "symbols": ["heart", "fire", "star", "sparkles", "tada", "100", "rocket", "check", "x", "warning"],
+    "food": ["coffee", "pizza", "hamburger", "cat", "dog"],
+}
+
+def search_emoji(query: str, category: str = "all", custom: Optional[dict] = None) -> list:
+    # (emoji, name) pairs whose shortcode contains query, one per emoji even when it has aliases
+    if category == "all":
+        available = dict(EMOJI_SHORTCODES)
+        for name, value in (custom or {}).items():
+            if not value.startswith(("http://", "https://")):
+                available.setdefault(name, value)
+    else:
+        available = {name: EMOJI_SHORTCODES[name] for name in EMOJI_CATEGORIES.get(category, [])}
+    query = query.strip().strip(":").lower()
+    names_by_emoji: dict = {}
+    for name, emoji in available.items():
+        names_by_emoji.setdefault(emoji, []).append(name)
+    return [
+        (emoji, names[0]) for emoji, names in names_by_emoji.items()
+        if any(query in name for name in names)
+    ]
+
+SHORTCODE_RE = re.compile(r":([a-z0-9_+\-]+):")
+
+def merge_custom_emoji(shortcodes: dict) -> dict:
+    # Normalize server-provided codes (with or without colons); built-ins always win
+    custom = {}
+    for code, value in shortcodes.items():
+        name = str(code).strip(":").lower()
+        if name and name not in EMOJI_SHORTCODES and isinstance(value, str):
+            custom[name] = value
+    return custom
+
+def expand_shortcodes(text: str, custom: Optional[dict] = None) -> str:
+    # Replace known :name: codes; image-valued custom codes stay as text since the terminal can't show them
+    if not glyphs.emoji:
+        return text
+    def replace(match):
+        name = match.group(1)
+        if name in EMOJI_SHORTCODES:
+            return EMOJI_SHORTCODES[name]
+        value = (custom or {}).get(name)
+        if value and not value.startswith(("http://", "https://")):
+            return value
+        return match.group(0)
+    return SHORTCODE_RE.sub(replace, text)
+
+# Inline markdown in chat messages: `code`, **bold**, ~~strike~~ and *italic* or _italic_
+INLINE_MARKDOWN_RE = re.compile(r"`([^`]+)`|\*\*(.+?)\*\*|~~(.+?)~~|\*([^*\s][^*]*?)\*|\b_([^_\s][^_]*?)_\b")
+
+def parse_inline_markdown(text: str) -> str:
+    # Turn inline markdown into Rich markup, escaping everything else so message text can't inject markup
+    parts = []
+    position = 0
+    for match in INLINE_MARKDOWN_RE.finditer(text):
+        parts.append(escape(text[position:match.start()]))
+        code, bold, strike, italic, underscored = match.groups()
+        if code is not None:
+            parts.append(f"[reverse]{escape(code)}[/reverse]")  # Code is shown literally, never parsed further
+        elif bold is not None:
+            parts.append(f"[bold]{parse_inline_markdown(bold)}[/bold]")
+        elif strike is not None:
+            parts.append(f"[strike]{parse_inline_markdown(strike)}[/strike]")
+        else:
+            parts.append(f"[italic]{parse_inline_markdown(italic or underscored)}[/italic]")
+        position = match.end()
+    parts.append(escape(text[position:]))
+    return "".join(parts)
+
+CODE_FENCE = "```"
+
+def split_code_blocks(text: str) -> list:
+    # Split a message into ("text", content, None) and ("code", content, language) parts, in order.
+    # A fence opens at the start of a line, optionally followed by a language; a fence that is never
+    # closed runs to the end of the message, the way most markdown renderers treat it. A fence can also
+    # close at the end of a code line, so ```code``` on one line is a block of its own.
+    parts = []
+    prose: list = []
+    code: Optional[list] = None
+    language = None
+    for line in text.split("\n"):
+        stripped = line.strip()
+        if code is None and stripped.startswith(CODE_FENCE):
+            if prose:
+                parts.append(("text", "\n".join(prose), None))
+                prose = []
+            rest = stripped[len(CODE_FENCE):]
+            if rest.endswith(CODE_FENCE):
+                parts.append(("code", rest[:-len(CODE_FENCE)].strip(), None))
+                continue
+            code = []
+            language = rest.strip().split(" ")[0] or None
+        elif code is not None and stripped.endswith(CODE_FENCE):
+            last = line.rstrip()[:-len(CODE_FENCE)]
+            if last.strip():
+                code.append(last)
+            parts.append(("code", "\n".join(code), language))
+            code = None
+        elif code is not None:
+            code.append(line)
+        else:
+            prose.append(line)
+    if code is not None:
+        parts.append(("code", "\n".join(code), language))
+    if prose:
+        parts.append(("text", "\n".join(prose), None))
+    return parts
+
+def render_code_block(code: str) -> str:
+    # Every line padded to the same width so the background reads as one block; nothing inside is parsed
+    lines = [line.expandtabs(4) for line in code.split("\n")]
+    width = max(len(line) for line in lines)
+    return "\n".join(f"  [white on grey15] {escape(line.ljust(width))} [/white on grey15]" for line in lines)
+
+def input_placeholder(state: str) -> str:
+    # Input placeholder for each connection state
+    return {
+        "connecting": f"Connecting{glyphs.ellipsis} (messages are sent once joined)",
+        "connected": "Type your message here...",
+        "disconnected": f"Disconnected {glyphs.dash} type /reconnect to rejoin",
+    }[state]
+
+# English text for system and error lines, looked up with sys_msg so the same event is always
+# worded the same way. Markup stays at the call site; values are escaped before they are passed in.
+SYSTEM_MESSAGES = {
+    "joined": "Successfully joined chat '{chat}'",
+    "reconnected": "reconnected {time}",
+    "session_expired": "Session expired, rejoining with password...",
+    "disconnected": "Disconnected: {reason}",
+    "connection_lost": "Connection lost",
+    "no_auto_reconnect": "Not reconnecting automatically. Type /reconnect to try again.",
+    "reconnect_scheduled": "Reconnecting in {seconds}s. Type /reconnect cancel to stay offline.",
+    "too_long": "Message is too long ({length} characters, the limit here is {limit}).",
+    "too_long_encrypted": "Message is too long once encrypted ({length} characters, the limit here is {limit}).",
+    "reconnecting": "Reconnecting...",
+    "waiting_for_network": "Can't reach the server. Waiting for the network to come back before reconnecting (/reconnect cancel to stop).",
+    "reconnect_cancelled": "Scheduled reconnect cancelled.",
+    "no_reconnect_scheduled": "No reconnect is scheduled.",
+    "already_connected": "Already connected.",
+    "not_connected": "Not connected to server. Cannot {action}.",
+    "disconnected_send": "Disconnected - type /reconnect to rejoin before sending.",
+    "still_joining": "Still joining - your message will be sent once the join is confirmed.",
+    "join_failed_unsent": "Not sent, the join failed: {message}",
+    "send_closed": "Cannot send message: Connection closed",
+    "send_queued": "Message not sent: connection closed. It will be retried once after reconnecting.",
+    "send_still_failed": "Message still not sent: {message}. Type /retry to try again.",
+    "send_error": "Error sending message: {error}",
+    "health_lost": "No reply to two pings in a row, dropping the connection.",
+    "processing_error": "Error processing message: {error}",
+    "receiving_error": "Error receiving messages: {error}",
+    "invalid_json": "Received invalid JSON: {data}...",
+    "incomplete_json_dropped": "Dropped {size} of incomplete JSON from the server.",
+    "user_joined": "A wild {username} has appeared.",
+    "user_left": "{username} has left the chat.",
+    "last_seen": "{username} was last active {ago}.",
+    "not_seen": "{username} hasn't been seen this session.",
+    "presence_summary": "{count} users online (+{joins} / -{leaves} in the last minute)",
+    "room_info_unavailable": "Room info unavailable.",
+    "report_submitted": "Report submitted.",
+    "report_failed": "Report not submitted: {reason}",
+    "server_error": "Error: {message}",
+    "command_result": "System: {text}",
+    "connection_failed": "Connection failed: {message}",
+    "auth_failed": "Authentication failed: {message}",
+    "dns_retry": "Can't reach server - check your internet connection. Retrying in {seconds}s...",
+    "slowmode_on": "Slow mode is on: you can send one message every {seconds}s.",
+    "slowmode_off": "Slow mode is off.",
+    "slowmode_wait": "Slow mode: wait {seconds}s",
+    "rate_limited": "Sending too fast - you can send again in {seconds}s.",
+    "rate_limited_wait": "Rate limited: wait {seconds}s",
+    "poll_gone": "That poll is no longer in the chat log.",
+    "no_polls": "There's no poll to vote on.",
+    "no_such_option": "Pick an option from 1 to {count}.",
+    "server_color": "The server's color can't be changed.",
+    "no_color_override": "You haven't picked a color for {username}.",
+    "color_cleared": "{username} is back to their assigned color.",
+    "bad_color": "{color} isn't a color. Use #RRGGBB or a name like red or bright_cyan.",
+    "color_set": "{username} will be shown in this color.",
+    "room_blocked": "You blocked '{chat}' on this server. Use /unblock-room {chat} to allow joining it again.",
+    "room_now_blocked": "Left and blocked '{chat}'.",
+    "no_blocked_rooms": "You haven't blocked any rooms.",
+    "room_not_blocked": "'{chat}' isn't blocked.",
+    "room_unblocked": "'{chat}' can be joined again.",
+    "ignore_self": "You can't ignore yourself.",
+    "ignored": "Ignoring {usernames}. Their messages won't be shown.",
+    "already_ignored": "Already ignoring them.",
+    "unignored": "No longer ignoring {usernames}.",
+    "not_ignored": "You aren't ignoring anyone by that name.",
+    "no_ignores": "You aren't ignoring anyone.",
+    "clear_ignores_confirm": "Stop ignoring all {count}? Type /clear-ignores again within {seconds}s to confirm.",
+    "ignores_cleared": "Ignore list cleared.",
+    "clear_confirm": "Clear the chat? Type /clear again within {seconds}s to confirm, or use /clear! to skip this.",
+    "cleared": "Chat cleared. Type /undo within {seconds}s to bring it back.",
+    "nothing_to_undo": "Nothing to undo.",
+    "diag_exported": "Diagnostics written to {path} (passwords, tokens and message text are left out).",
+    "diag_export_failed": "Could not write diagnostics: {error}",
+    "config_exported": "Settings exported to {path}.",
+    "config_export_failed": "Could not export settings: {error}",
+    "config_import_mode": "Add merge to keep settings the file doesn't mention, or replace to reset them to defaults: /config import {path} merge|replace",
+    "config_imported": "Settings imported, {count} changed.",
+    "config_import_failed": "Could not import settings: {error}",
+    "config_skipped": "Skipped unknown or invalid settings and entries: {keys}",
+    "unsupported": "This server doesn't support /{command}.",
+    "no_message_to_reply": "No message from {username} to reply to.",
+    "no_thread": "No thread to show.",
+    "bad_regex": "Invalid regex: {error}",
+    "no_matches": "No messages match {query}.",
+    "no_search": "Nothing to step through. Search with /find first.",
+    "search_match": "Match {index} of {count}",
+    "message_gone": "That message is no longer in the chat log.",
+    "already_in_room": "Already in '{chat}'.",
+    "no_other_rooms": "No other rooms on this server yet - use /go <room> to join one.",
+    "no_room_number": "There is no room {number} - see /recent.",
+    "unsent_kept": "{count} unsent message{s} will be sent when you go back to '{chat}'.",
+    "no_broadcasts": "No server notices to dismiss.",
+    "nothing_to_copy": "No message to copy.",
+    "no_message_links": "This server doesn't support links to messages.",
+    "no_clipboard": "Copying to the clipboard isn't available here.",
+    "copied": "Copied {what} to the clipboard.",
+    "nothing_to_resend": "Nothing to resend.",
+    "signing_off": "Signing is off. Turn it on with /set sign_messages on.",
+    "bad_public_key": "That isn't a valid signing key.",
+    "key_trusted": "Messages from {username} will be checked against that key.",
+    "key_changed": "{username} announced a different signing key from the one you trust; their messages won't get a {check}.",
+    "key_announced": "{username} announced a signing key. Check it with them, then type /trust {username} to trust it.",
+    "no_announced_key": "{username} hasn't announced a signing key. Ask them for it (/key shows theirs), then type /trust {username} <key>.",
+    "outbox_pending": "{count} message{s} waiting to be sent",
+    "outbox_empty": "No messages are waiting to be sent.",
+    "outbox_discarded": "Discarded {count} queued message{s}.",
+    "nothing_to_retry": "No failed message to retry.",
+    "retry_queued": "Not connected - the message will be retried after reconnecting.",
+}
+
+def sys_msg(key: str, **kwargs) -> str:
+    # Unknown keys come back as the key itself so a missing entry is visible rather than fatal
+    template = SYSTEM_MESSAGES.get(key)
+    if template is None:
+        return key
+    try:
+        return template.format(**kwargs)
+    except (KeyError, IndexError):
+        return template
+
+# Optional features a server can list in "capabilities" on our join echo; anything else it lists is ignored
+KNOWN_CAPABILITIES = {"room_info", "report", "status", "message_links", "polls"}
+
+class ServerCapabilities:
+    # What the server says it supports. A server that sends no list predates capabilities,
+    # so everything is assumed to work as it always did.
+
+    def __init__(self, advertised=None):
+        if isinstance(advertised, list):
+            self.advertised: Optional[set] = {name for name in advertised if isinstance(name, str) and name in KNOWN_CAPABILITIES}
+        else:
+            self.advertised = None
+
+    def supports(self, name: str) -> bool:
+        return self.advertised is None or name in self.advertised
+
+class CommandSpec:
+    # A slash command's metadata and how to run it. COMMANDS is read by both the input parser
+    # and the command palette, so a command added there shows up in both.
+
+    def __init__(self, name: str, description: str, run, aliases: tuple = (), arg_hint: str = "", needs_args: bool = False, capability: str = ""):
+        self.name = name
+        self.description = description
+        self.run = run  # Called with (chat screen, full command text); may return a coroutine
+        self.aliases = aliases
+        self.arg_hint = arg_hint  # Commands without one only match when typed on their own
+        self.needs_args = needs_args  # The palette fills these in for editing instead of running them
+        self.capability = capability  # Server capability the command needs, if any
+
+    @property
+    def usage(self) -> str:
+        return f"/{self.name} {self.arg_hint}".strip()
+
+def command_args(command: str) -> str:
+    # Everything after the command word
+    parts = command.split(maxsplit=1)
+    return parts[1].strip() if len(parts) > 1 else ""
+
+COMMANDS = [
+    CommandSpec("clear", "Clear the chat area (asks to confirm; /clear! doesn't)",
+                lambda screen, command: screen.handle_clear_command(command), aliases=("c", "clear!", "c!")),
+    CommandSpec("undo", "Bring back the chat you just cleared", lambda screen, command: screen.undo_clear()),
+    CommandSpec("color", "Pick the color someone's name is shown in",
+                lambda screen, command: screen.set_user_color(command_args(command)), aliases=("colour",),
+                arg_hint="<username> [#RRGGBB|name]", needs_args=True),
+    CommandSpec("block-room", "Leave this room and never rejoin it by accident", lambda screen, command: screen.block_current_room()),
+    CommandSpec("unblock-room", "Allow joining a blocked room again, or list blocked rooms",
+                lambda screen, command: screen.unblock_room_command(command_args(command)), arg_hint="[server] <chat>"),
+    CommandSpec("ignore", "Hide messages from one or more people",
+                lambda screen, command: screen.ignore_users(command_args(command)), arg_hint="<username> [username...]", needs_args=True),
+    CommandSpec("unignore", "Show messages from people you ignored again",
+                lambda screen, command: screen.unignore_users(command_args(command)), arg_hint="<username> [username...]", needs_args=True),
+    CommandSpec("ignores", "List the people you're ignoring", lambda screen, command: screen.show_ignores()),
+    CommandSpec("clear-ignores", "Stop ignoring everyone (asks to confirm)", lambda screen, command: screen.clear_ignores()),
+    CommandSpec("again", "Send your last message again, or the nth from last",
+                lambda screen, command: screen.send_again(command_args(command)), arg_hint="[n]"),
+    CommandSpec("dismiss", "Hide the server notices at the top of the chat", lambda screen, command: screen.dismiss_broadcasts()),
+    CommandSpec("outbox", "List, retry or discard messages waiting to be sent",
+                lambda screen, command: screen.handle_outbox_command(command_args(command)), arg_hint="[retry|discard]"),
+    CommandSpec("retry", "Send your last failed message again", lambda screen, command: screen.retry_message()),
+    CommandSpec("reply", "Reply to someone's latest message",
+                lambda screen, command: screen.reply_to_user(command_args(command)), arg_hint="<username> <message>", needs_args=True),
+    CommandSpec("find", "Search the chat log for text, or a regex with -r",
+                lambda screen, command: screen.find_messages(command_args(command)), arg_hint="[-r] <text>", needs_args=True),
+    CommandSpec("next", "Jump to the next /find match (F3)", lambda screen, command: screen.search_step(1)),
+    CommandSpec("prev", "Jump to the previous /find match (Shift+F3)", lambda screen, command: screen.search_step(-1)),
+    CommandSpec("focus", "Hide everything but the messages (F11, Escape leaves)", lambda screen, command: screen.action_toggle_focus_mode()),
+    CommandSpec("thread", "Show a thread of replies",
+                lambda screen, command: screen.open_thread(command_args(command)), arg_hint="[username]"),
+    CommandSpec("quit", "Exit Termchat", lambda screen, command: screen.app.action_quit(), aliases=("exit", "q")),
+    CommandSpec("reconnect", "Reconnect to the room, or cancel a scheduled reconnect",
+                lambda screen, command: screen.handle_reconnect_command(command), arg_hint="[cancel]"),
+    CommandSpec("diag", "Show connection diagnostics, or write them to a file for a bug report",
+                lambda screen, command: screen.handle_diag_command(command_args(command)), arg_hint="[export [path]]"),
+    CommandSpec("poll", "Start a poll in the room",
+                lambda screen, command: screen.create_poll(command_args(command)), arg_hint='"Question" "Option A" "Option B"', needs_args=True, capability="polls"),
+    CommandSpec("vote", "Vote on the latest poll (or click an option)",
+                lambda screen, command: screen.vote_command(command_args(command)), arg_hint="<option>", needs_args=True, capability="polls"),
+    CommandSpec("info", "Show details about the room", lambda screen, command: screen.request_room_info(), capability="room_info"),
+    CommandSpec("recent", "List recently joined rooms", lambda screen, command: screen.show_recent_rooms()),
+    CommandSpec("go", "Switch to another room on this server",
+                lambda screen, command: screen.go_to_room(command_args(command)), arg_hint="<room>", needs_args=True),
+    CommandSpec("report", "Privately report someone to the moderators",
+                lambda screen, command: screen.report_user(command_args(command)), arg_hint="<username> <reason>", needs_args=True, capability="report"),
+    CommandSpec("status", "Set or clear your status",
+                lambda screen, command: screen.set_status(command_args(command)), arg_hint="[text]", capability="status"),
+    CommandSpec("who", "List the people in the room", lambda screen, command: screen.show_roster()),
+    CommandSpec("whois", "Show what this client knows about someone",
+                lambda screen, command: screen.show_whois(command_args(command)), arg_hint="<username>", needs_args=True),
+    CommandSpec("seen", "Show when someone was last active",
+                lambda screen, command: screen.show_seen(command_args(command)), arg_hint="<username>", needs_args=True),
+    CommandSpec("copy", "Copy the latest message, or someone's, as text, markdown, a link or just the name",
+                lambda screen, command: screen.copy_message(command_args(command)), arg_hint="[text|markdown|link|username] [username]"),
+    CommandSpec("key", "Show your public signing key", lambda screen, command: screen.show_public_key()),
+    CommandSpec("trust", "Verify someone's signed messages with the key they announced, or one they gave you",
+                lambda screen, command: screen.trust_key(command_args(command)), arg_hint="<username> [key]", needs_args=True),
+    CommandSpec("invite", "Show and copy an invite link for this room", lambda screen, command: screen.show_invite()),
+    CommandSpec("server", "Manage saved servers",
+                lambda screen, command: screen.handle_server_command(command), arg_hint="[list|add|edit|remove]"),
+    CommandSpec("dnd", "Turn do not disturb on or off, or set quiet hours",
+                lambda screen, command: screen.handle_dnd_command(command_args(command)), arg_hint="[on|off|schedule HH:MM-HH:MM]"),
+    CommandSpec("config", "Export your settings to a file, or import them",
+                lambda screen, command: screen.handle_config_command(command_args(command)),
+                arg_hint="export|import <path> [merge|replace]", needs_args=True),
+    CommandSpec("set", "Show or change settings",
+                lambda screen, command: screen.handle_set_command(command), arg_hint="[setting] [value]"),
+]
+
+# Fake server events for exercising the UI without a server. Only reachable with --dev.
+SIMULATED_USERS = ["ada", "grace", "linus", "margaret"]
+SIMULATED_LINES = [
+    "hello everyone",
+    "has anyone tried the **new build**?",
+    "brb :coffee:",
+    "I think it's fixed now, try `git pull`",
+    "*waves*",
+    "see you all tomorrow",
+]
+SIMULATED_THEMES = ["#33FF33", "#FFB000", "#87CEEB"]
+SIMULATE_KINDS = ("message", "join", "leave", "theme", "broadcast", "slowmode", "kick", "chatter")
+SIMULATED_CHATTER_INTERVAL = 2.0
+
+def simulated_event(kind: str, step: int) -> Optional[dict]:
+    # The same step always gives the same event, so screenshots and QA runs can be repeated
+    user = SIMULATED_USERS[step % len(SIMULATED_USERS)]
+    if kind == "message":
+        return {"type": "message", "username": user, "content": SIMULATED_LINES[step % len(SIMULATED_LINES)], "id": f"sim-{step}"}
+    if kind == "join":
+        return {"type": "join", "username": user}
+    if kind == "leave":
+        return {"type": "leave", "username": user}
+    if kind == "theme":
+        return {"type": "colourshift", "color": SIMULATED_THEMES[step % len(SIMULATED_THEMES)]}
+    if kind == "broadcast":
+        return {"type": "broadcast", "content": "Simulated maintenance notice", "expires_ms": (time.time() + 30) * 1000}
+    if kind == "slowmode":
+        return {"type": "slowmode", "seconds": 0 if step % 2 else 5}
+    if kind == "kick":
+        return {"type": "kicked", "message": "You have been kicked (simulated)"}
+    return None
+
+def enable_dev_commands():
+    # --dev only: without it /simulate isn't registered, so it can't be typed or found in the palette
+    COMMANDS.append(CommandSpec(
+        "simulate", f"Inject a fake server event ({', '.join(SIMULATE_KINDS)})",
+        lambda screen, command: screen.simulate(command_args(command)), arg_hint="<kind>", needs_args=True,
+    ))
+
+SERVER_COMMAND_RE = re.compile(r"^/([A-Za-z][\w-]*)(?:\s+(.*))?$", re.DOTALL)
+
+def server_command(text: str) -> Optional[dict]:
+    # A slash command Termchat doesn't know, as the envelope for the server to run it, e.g.
+    # "/kick bob spam" -> {"type": "command", "name": "kick", "args": ["bob", "spam"]}
+    match = SERVER_COMMAND_RE.match(text)
+    if not match:
+        return None
+    name = match.group(1).lower()
+    if any(name == spec.name or name in spec.aliases for spec in COMMANDS):
+        return None
+    return {"type": "command", "name": name, "args": (match.group(2) or "").split()}
+
+def find_command(text: str) -> Optional[CommandSpec]:
+    # The command text invokes, or None if it should be sent as a chat message
+    if not text.startswith("/"):
+        return None
+    word, _, rest = text.partition(" ")
+    word = word[1:].lower()
+    for spec in COMMANDS:
+        if word == spec.name or word in spec.aliases:
+            return spec if spec.arg_hint or not rest.strip() else None
+    return None
+
+# Screen actions listed in the command palette next to the slash commands: (label, shortcut, action)
+PALETTE_ACTIONS = [
+    ("Toggle message preview", "Ctrl+P", "toggle_preview"),
+    ("Insert emoji", "Ctrl+E", "emoji_picker"),
+    ("Retry failed message", "Ctrl+R", "retry_message"),
+    ("Toggle focus mode", "F11", "toggle_focus_mode"),
+    ("Quit", "Ctrl+Q", "quit"),
+]
+
+MAX_STATUS_LENGTH = 64
+
+DEFAULT_MAX_MESSAGE_LENGTH = 2000  # Used unless the server says what it accepts on join
+LENGTH_WARNING_RATIO = 0.8
+
+def effective_max_length(server_limit) -> int:
+    # The server's max_message_length if it gave a sensible one, else our default
+    if isinstance(server_limit, int) and not isinstance(server_limit, bool) and server_limit > 0:
+        return server_limit
+    return DEFAULT_MAX_MESSAGE_LENGTH
+
+def length_level(length: int, limit: int) -> str:
+    # How close a draft is to the limit: "ok", "warning" from 80%, "full" at the limit and past it
+    if length >= limit:
+        return "full"
+    if length >= limit * LENGTH_WARNING_RATIO:
+        return "warning"
+    return "ok"
+
+LENGTH_LEVEL_STYLES = {"ok": "dim", "warning": "yellow", "full": "bold red"}
+
+# Largest message accepted from the server. Anything bigger makes the websockets library
+# close the connection with 1009 instead of buffering it, so a hostile server can't exhaust memory.
+MAX_FRAME_SIZE = 2**20
+
+# Friendly names for WebSocket close codes
+CLOSE_CODE_MESSAGES = {
+    1000: "Closed normally",
+    1001: "Server is going away",
+    1006: "Connection dropped",
+    1008: "Policy violation",
+    1009: f"Server sent a message over the {MAX_FRAME_SIZE // 2**20} MB limit",
+    1011: "Server error",
+    1012: "Server restarting",
+    1013: "Server busy, try again later",
+}
+
+# Close codes worth reconnecting after; policy/auth closes such as 1008 are deliberate
+RECONNECT_CLOSE_CODES = {1001, 1006, 1011, 1012, 1013}
+
+RECONNECT_BASE_DELAY = 2.0
+RECONNECT_MAX_DELAY = 30.0
+
+def describe_close(code: Optional[int], reason: Optional[str]) -> str:
+    # e.g. "banned (1008)", falling back to a friendly name when the server gave no reason
+    code = 1006 if code is None else code
+    text = reason or CLOSE_CODE_MESSAGES.get(code, "Connection closed")
+    return f"{text} ({code})"
+
+def should_auto_reconnect(code: Optional[int]) -> bool:
+    return (1006 if code is None else code) in RECONNECT_CLOSE_CODES
+
+def reconnect_delay(attempt: int) -> float:
+    # Exponential backoff: 2s, 4s, 8s, ... capped at RECONNECT_MAX_DELAY
+    return min(RECONNECT_MAX_DELAY, RECONNECT_BASE_DELAY * 2 ** attempt)
+
+REACHABILITY_TIMEOUT = 3.0  # Seconds a probe of the server's host may take before we count as offline
+OFFLINE_POLL_INTERVAL = 5.0  # Seconds between probes while offline
+
+async def host_reachable(server_url: str, bind_address: Optional[str] = None, timeout: float = REACHABILITY_TIMEOUT) -> bool:
+    # A plain TCP connect to the server's host: cheap, and fails fast while asleep or offline
+    parsed = urlparse(server_url)
+    if not parsed.hostname:
+        return False
+    port = parsed.port or (80 if parsed.scheme == "ws" else 443)
+    try:
+        _, writer = await asyncio.wait_for(asyncio.open_connection(parsed.hostname, port, **bind_options(bind_address)), timeout)
+    except (OSError, asyncio.TimeoutError):
+        return False
+    writer.close()
+    return True
+
+async def wait_until_reachable(reachable, poll_interval: float = OFFLINE_POLL_INTERVAL, sleep=asyncio.sleep) -> bool:
+    # Probe with reachable() until it says yes; True if we had to wait, so backoff can start over
+    waited = False
+    while not await reachable():
+        waited = True
+        await sleep(poll_interval)
+    return waited
+
+# Seconds to wait for a room_info reply before giving up
+ROOM_INFO_TIMEOUT = 5.0
+
+def format_room_info(data: dict) -> list:
+    # Lines describing a room_info payload; every field is optional
+    lines = []
+    member_count = data.get("member_count")
+    if isinstance(member_count, int):
+        lines.append(f"Members: {member_count}")
+    topic = data.get("topic")
+    lines.append(f"Topic: {topic}" if topic else "Topic: (none)")
+    created_ms = data.get("created_ms")
+    if isinstance(created_ms, (int, float)):
+        lines.append("Created: " + time.strftime("%Y-%m-%d %H:%M", time.localtime(created_ms / 1000)))
+    if "protected" in data:
+        lines.append("Password protected: " + ("yes" if data.get("protected") else "no"))
+    return lines
+
+# Largest partial JSON object we'll buffer while waiting for the rest of it
+MAX_PENDING_JSON = 2**20
+
+def extract_json_objects(buffer: str) -> tuple:
+    # Pull every complete top-level JSON object out of buffer, tolerating objects split across
+    # frames and several objects in one frame. Returns (objects, invalid, remainder) where invalid
+    # holds text that couldn't be parsed and remainder is an incomplete trailing object.
+    objects, invalid = [], []
+    depth = 0
+    in_string = escaped = False
+    start = None
+    garbage_start = None
+    for index, char in enumerate(buffer):
+        if depth == 0 and char != "{":
+            if not char.isspace() and garbage_start is None:
+                garbage_start = index
+            continue
+        if in_string:
+            if escaped:
+                escaped = False
+            elif char == "\\":
+                escaped = True
+            elif char == '"':
+                in_string = False
+            continue
+        if char == '"':
+            in_string = True
+        elif char == "{":
+            if depth == 0:
+                if garbage_start is not None:
+                    invalid.append(buffer[garbage_start:index].strip())
+                    garbage_start = None
+                start = index
+            depth += 1
+        elif char == "}":
+            depth -= 1
+            if depth == 0:
+                chunk = buffer[start:index + 1]
+                try:
+                    objects.append(json.loads(chunk))
+                except json.JSONDecodeError:
+                    invalid.append(chunk)
+                start = None
+    if garbage_start is not None:
+        invalid.append(buffer[garbage_start:].strip())
+    remainder = buffer[start:] if start is not None else ""
+    if len(remainder) > MAX_PENDING_JSON:
+        invalid.append(remainder)
+        remainder = ""
+    return objects, invalid, remainder
+
+# Join backlogs at least this long are written as one batch with a single scroll
+BACKLOG_BATCH_THRESHOLD = 20
+
+# Chat log entries kept for re-rendering, and how close together repeated notices collapse.
+# Past the limit the oldest are dropped LOG_TRIM_BATCH at a time, from the screen as well.
+MAX_LOG_ENTRIES = 1000
+LOG_TRIM_BATCH = 100
+NOTICE_DEDUP_WINDOW = 60.0
+
+def is_pinned(scroll_pos: float, content_height: int, viewport_height: int, tolerance: int) -> bool:
+    # Being a line or two short of the bottom still counts as following the log
+    return content_height - viewport_height - scroll_pos <= tolerance
+
+def should_collapse(last: Optional[dict], markup: str, kind: str, now: float, window: float) -> bool:
+    # Only notices collapse into a counter; chat messages can legitimately repeat
+    return (
+        kind == "notice"
+        and last is not None
+        and last["kind"] == "notice"
+        and last["markup"] == markup
+        and now - last["time"] <= window
+    )
+
+CONFIRM_TIMEOUT = 10.0  # Seconds an optimistically shown message waits for the server's echo
+
+def next_send_state(state: str, event: str) -> str:
+    # pending -> sent on the echo, pending -> failed on timeout or a lost connection.
+    # A late echo still confirms a failed message, and a retry puts it back to pending.
+    if event == "echo":
+        return "sent"
+    if event in ("timeout", "error") and state == "pending":
+        return "failed"
+    if event == "retry" and state == "failed":
+        return "pending"
+    return state
+
+def send_state_marker(state: str) -> str:
+    if state == "pending":
+        return f"[dim]{glyphs.ellipsis}[/dim]"
+    if state == "sent":
+        return f"[green]{glyphs.check}[/green]"
+    return f"[bold red]{glyphs.cross}[/bold red]"
+
+def render_entry(entry: dict) -> str:
+    markup = entry["markup"]
+    if entry.get("state"):
+        markup = f"{markup} {send_state_marker(entry['state'])}"
+    if entry["count"] > 1:
+        return f"{markup} [dim]({glyphs.times}{entry['count']})[/dim]"
+    return markup
+
+SENT_HISTORY_SIZE = 50
+
+def history_lookup(history, args: str) -> Optional[str]:
+    # /again resends the last message, /again <n> the nth from last; None if there's no such message
+    try:
+        back = int(args) if args else 1
+    except ValueError:
+        return None
+    if back < 1 or back > len(history):
+        return None
+    return history[-back]
+
+def search_pattern(query: str, use_regex: bool) -> re.Pattern:
+    # Case-insensitive either way; raises re.error for a bad regex so the caller can say so
+    return re.compile(query if use_regex else re.escape(query), re.IGNORECASE)
+
+def search_entries(entries: list, pattern: re.Pattern) -> list:
+    # Every chat message in the log whose text or sender matches, oldest first
+    return [
+        entry for entry in entries
+        if entry["kind"] == "message" and (pattern.search(entry["content"]) or pattern.search(entry["username"]))
+    ]
+
+REPLY_SNIPPET_LENGTH = 40
+
+def index_entries(entries: list) -> dict:
+    # Server id -> log entry, built once for passes that look up many messages; later entries win,
+    # matching find_entry's newest first
+    return {entry["id"]: entry for entry in entries if entry.get("id") is not None}
+
+def find_entry(entries: list, message_id) -> Optional[dict]:
+    # The log entry of the chat message with this server id, newest first
+    if message_id is None:
+        return None
+    return next((entry for entry in reversed(entries) if entry.get("id") == message_id), None)
+
+def edit_entry(entries: list, message_id, new_content: Optional[str], edited_at: float) -> Optional[dict]:
+    # Update a message's text in place, keeping its position; None if it isn't in the log
+    entry = find_entry(entries, message_id)
+    if entry is None or new_content is None:
+        return None
+    entry["content"] = new_content
+    entry["edited_at"] = edited_at
+    return entry
+
+def reply_snippet(content: str, limit: int = REPLY_SNIPPET_LENGTH) -> str:
+    text = " ".join(content.split())
+    return text if len(text) <= limit else text[:limit].rstrip() + glyphs.ellipsis
+
+def format_reply_header(parent: Optional[dict]) -> str:
+    # The line shown above a reply; the parent may have scrolled out of the log or never been seen
+    if parent is None:
+        return f"[dim]  {glyphs.reply} replying to an earlier message[/dim]"
+    return f"[dim]  {glyphs.reply} replying to {escape(parent['username'])}: {escape(reply_snippet(parent['content']))}[/dim]"
+
+def thread_entries(entries: list, root_id) -> list:
+    # The root message and every reply under it, directly or to another reply, in log order
+    if root_id is None:
+        return []
+    start = next((index for index in range(len(entries) - 1, -1, -1) if entries[index].get("id") == root_id), None)
+    if start is None:
+        return []
+    ids = {root_id}
+    thread = [entries[start]]
+    for entry in entries[start + 1:]:
+        if entry.get("reply_to") in ids:
+            thread.append(entry)
+            if entry.get("id") is not None:
+                ids.add(entry["id"])
+    return thread
+
+def thread_root(entries: list, entry: dict) -> dict:
+    # Walk up the reply chain as far as the log goes
+    by_id = index_entries(entries)
+    seen = set()
+    while entry.get("reply_to") is not None and entry["reply_to"] not in seen:
+        seen.add(entry["reply_to"])
+        parent = by_id.get(entry["reply_to"])
+        if parent is None:
+            break
+        entry = parent
+    return entry
+
+# An @mention being typed: the @ must start the text or follow whitespace, and run up to the cursor
+MENTION_QUERY_RE = re.compile(r"(?:^|\s)@([^\s@]*)$")
+MAX_MENTION_CANDIDATES = 8
+
+def mention_query(text_before_cursor: str) -> Optional[str]:
+    # The partial username after an @ right before the cursor, or None when not typing a mention
+    match = MENTION_QUERY_RE.search(text_before_cursor)
+    return match.group(1) if match else None
+
+def mention_candidates(prefix: str, roster, own_username: str) -> list:
+    # Present users whose name starts with prefix (case-insensitively), excluding ourselves
+    prefix = prefix.lower()
+    names = [name for name in roster if name != own_username and name.lower().startswith(prefix)]
+    return sorted(names, key=str.lower)[:MAX_MENTION_CANDIDATES]
+
+def parse_user_color(value: str) -> Optional[str]:
+    # #RRGGBB or any color name Rich knows, normalized; None if it isn't a color
+    try:
+        Color.parse(value)
+    except ColorParseError:
+        return None
+    return value.upper() if HEX_COLOR_RE.match(value) else value.lower()
+
+def is_ignored(ignored: list, username) -> bool:
+    return isinstance(username, str) and username.lower() in (name.lower() for name in ignored)
+
+def add_ignores(ignored: list, usernames: list) -> list:
+    # Add several names at once, skipping ones already there; returns those actually added
+    added = []
+    for username in usernames:
+        if not is_ignored(ignored, username):
+            ignored.append(username)
+            added.append(username)
+    return added
+
+def remove_ignores(ignored: list, usernames: list) -> list:
+    # Returns the names that were on the list, as they were stored
+    wanted = {username.lower() for username in usernames}
+    removed = [name for name in ignored if name.lower() in wanted]
+    ignored[:] = [name for name in ignored if name.lower() not in wanted]
+    return removed
+
+UNDO_WINDOW = 30.0  # Seconds a cleared chat can be brought back with /undo
+CLEAR_CONFIRM_WINDOW = 10.0  # Seconds to repeat /clear to confirm it
+
+class UndoBuffer:
+    # One level of undo for a cleared log, only valid for a short while
+    def __init__(self, window: float = UNDO_WINDOW):
+        self.window = window
+        self.saved = None
+        self.saved_at = 0.0
+
+    def store(self, entries: list, last_message, now: float):
+        self.saved = (entries, last_message)
+        self.saved_at = now
+
+    def take(self, now: float):
+        # The saved (entries, last_message), or None when nothing is saved or it has expired
+        saved, self.saved = self.saved, None
+        if saved is None or now - self.saved_at > self.window:
+            return None
+        return saved
+
+# How long to show the roster as updating after a (re)connect when no snapshot arrives
+ROSTER_SETTLE_SECONDS = 5.0
+
+PRESENCE_WINDOW = 60.0  # Seconds of joins and leaves counted in a presence summary
+PRESENCE_SUMMARY_DELAY = 30.0  # A summary is written at most this often while lines are held back
+
+def summarize_presence(roster_size: int, threshold: int) -> bool:
+    # Whether join/leave lines should be folded into a summary rather than shown one by one
+    return threshold > 0 and roster_size > threshold
+
+class PresenceWindow:
+    # Joins and leaves seen over the last `window` seconds
+
+    def __init__(self, window: float = PRESENCE_WINDOW):
+        self.window = window
+        self.events: deque = deque()  # (time, "join" or "leave"), oldest first
+
+    def record(self, kind: str, now: float):
+        self.events.append((now, kind))
+        self.prune(now)
+
+    def prune(self, now: float):
+        while self.events and now - self.events[0][0] > self.window:
+            self.events.popleft()
+
+    def counts(self, now: float) -> tuple:
+        self.prune(now)
+        joins = sum(1 for _, kind in self.events if kind == "join")
+        return joins, len(self.events) - joins
+
+def effective_cooldown(*limits: float) -> float:
+    # Several limits can apply to sending at once; the strictest one wins
+    return max([0.0, *limits])
+
+DEFAULT_BROADCAST_TTL = 300.0  # Seconds a broadcast without expires_ms stays up
+
+def broadcast_expiry(data: dict, now: float) -> float:
+    # expires_ms is a Unix time in milliseconds
+    expires_ms = data.get("expires_ms")
+    if isinstance(expires_ms, (int, float)) and not isinstance(expires_ms, bool):
+        return expires_ms / 1000
+    return now + DEFAULT_BROADCAST_TTL
+
+MAX_POLL_OPTIONS = 10
+POLL_BAR_WIDTH = 20
+POLL_ID_RE = re.compile(r"[A-Za-z0-9_-]+")
+
+def is_clickable_poll_id(poll_id) -> bool:
+    # The id goes into click markup, so only ones that can't break out of it get clickable options
+    if isinstance(poll_id, int) and not isinstance(poll_id, bool):
+        return True
+    return isinstance(poll_id, str) and POLL_ID_RE.fullmatch(poll_id) is not None
+POLL_USAGE = '/poll "Question" "Option A" "Option B" ...'
+
+def parse_poll_command(args: str) -> Optional[tuple]:
+    # /poll "Question" "Option A" "Option B" -> (question, options), or None if it doesn't parse
+    try:
+        parts = [part.strip() for part in shlex.split(args)]
+    except ValueError:
+        return None
+    if len(parts) < 3 or len(parts) > MAX_POLL_OPTIONS + 1 or not all(parts):
+        return None
+    return parts[0], parts[1:]
+
+class Poll:
+    # One poll card. The server's tallies are the truth; our own vote is applied straight away
+    # so the card responds to a click, and the next poll_update puts it right if that was off.
+
+    def __init__(self, poll_id, question: str, options: list, votes=None):
+        self.poll_id = poll_id
+        self.question = question
+        self.options = options
+        self.votes = [0] * len(options)
+        self.my_vote: Optional[int] = None
+        self.update(votes)
+
+    def update(self, votes, my_vote=None):
+        # Tallies that don't line up with the options are ignored
+        if isinstance(votes, list) and len(votes) == len(self.options) and all(
+                isinstance(count, int) and not isinstance(count, bool) and count >= 0 for count in votes):
+            self.votes = list(votes)
+        if isinstance(my_vote, int) and not isinstance(my_vote, bool) and 0 <= my_vote < len(self.options):
+            self.my_vote = my_vote
+
+    def vote(self, index: int) -> bool:
+        # Move our vote to option index; False if that's already our vote
+        if self.my_vote == index:
+            return False
+        if self.my_vote is not None and self.votes[self.my_vote] > 0:
+            self.votes[self.my_vote] -= 1
+        self.votes[index] += 1
+        self.my_vote = index
+        return True
+
+    def markup(self) -> str:
+        total = sum(self.votes)
+        lines = [f"[bold #87CEEB]Poll:[/bold #87CEEB] [bold]{escape(self.question)}[/bold] [dim]({total} vote{'' if total == 1 else 's'})[/dim]"]
+        for index, (option, count) in enumerate(zip(self.options, self.votes)):
+            filled = round(POLL_BAR_WIDTH * count / total) if total else 0
+            bar = glyphs.block * filled + "[dim]" + glyphs.dash * (POLL_BAR_WIDTH - filled) + "[/dim]"
+            mine = f" [green]{glyphs.check}[/green]" if index == self.my_vote else ""
+            label = escape(option)
+            if is_clickable_poll_id(self.poll_id):
+                # Clicking an option votes for it, same as /vote
+                label = f"[@click=screen.vote({json.dumps(self.poll_id)}, {index})]{label}[/]"
+            lines.append(f"  {index + 1}. {label} {bar} {count}{mine}")
+        return "\n".join(lines)
+
+class BroadcastStore:
+    # Server notices shown in a banner until they expire or are dismissed, oldest first
+
+    def __init__(self):
+        self.items: list = []  # (expires_at, content)
+
+    def add(self, content: str, expires_at: float):
+        self.items.append((expires_at, content))
+
+    def prune(self, now: float) -> list:
+        # Drop expired notices and return the text of those still up
+        self.items = [item for item in self.items if item[0] > now]
+        return [content for _, content in self.items]
+
+    def next_expiry(self) -> Optional[float]:
+        return min((expires_at for expires_at, _ in self.items), default=None)
+
+    def clear(self):
+        self.items = []
+
+RATE_LIMIT_DEFAULT = 5.0  # Seconds to back off when a rate_limited message gives no retry_after_ms
+MAX_RATE_LIMIT_WAIT = 300.0  # Never lock sending for longer than this on the server's say-so
+
+def parse_retry_after(data: dict) -> float:
+    # Seconds to hold off sending after {"type": "rate_limited", "retry_after_ms": N}
+    retry_after = data.get("retry_after_ms")
+    if not isinstance(retry_after, (int, float)) or isinstance(retry_after, bool) or retry_after < 0:
+        return RATE_LIMIT_DEFAULT
+    return min(retry_after / 1000, MAX_RATE_LIMIT_WAIT)
+
+# Link health is judged from our own pings, separate from the library's keepalive
+HEALTH_PING_INTERVAL = 10.0
+HEALTH_PONG_TIMEOUT = 5.0
+DEGRADED_RTT = 0.5  # Seconds
+HEALTH_COLORS = {"good": "#00ff00", "degraded": "#ffaa00", "lost": "#ff0000"}
+
+class HealthMonitor:
+    # good while pongs come back quickly, degraded after one miss or a slow pong,
+    # lost after two misses in a row
+    def __init__(self):
+        self.state = "good"
+        self.misses = 0
+        self.rtt: Optional[float] = None
+
+    def pong(self, rtt: float) -> str:
+        self.misses = 0
+        self.rtt = rtt
+        self.state = "degraded" if rtt > DEGRADED_RTT else "good"
+        return self.state
+
+    def miss(self) -> str:
+        self.misses += 1
+        self.state = "lost" if self.misses >= 2 else "degraded"
+        return self.state
+
+RTT_HISTORY_SIZE = 60  # Pings kept for the /diag latency graph, about ten minutes' worth
+
+class RttHistory:
+    # Recent ping round trips across reconnects. None marks a gap: a missed pong or a disconnect.
+
+    def __init__(self, size: int = RTT_HISTORY_SIZE):
+        self.samples: deque = deque(maxlen=size)  # (timestamp, rtt in seconds or None)
+
+    def add(self, rtt: Optional[float], now: Optional[float] = None):
+        self.samples.append((time.time() if now is None else now, rtt))
+
+    def gap(self, now: Optional[float] = None):
+        # One gap stands for however long the connection was down
+        if self.samples and self.samples[-1][1] is not None:
+            self.add(None, now)
+
+def sparkline_points(samples) -> list:
+    # Each rtt scaled to 0..1 between the fastest and slowest; gaps stay None. All-equal samples sit mid-way.
+    values = [rtt for _, rtt in samples if rtt is not None]
+    if not values:
+        return [None for _ in samples]
+    low, high = min(values), max(values)
+    return [None if rtt is None else (0.5 if high == low else (rtt - low) / (high - low)) for _, rtt in samples]
+
+def format_sparkline(points: list) -> str:
+    levels = glyphs.spark
+    return "".join(" " if point is None else levels[min(len(levels) - 1, int(point * len(levels)))] for point in points)
+
+class ThroughputWindow:
+    # Rolling byte counter reporting throughput over the last `window` seconds
+
+    def __init__(self, window: float = 10.0):
+        self.window = window
+        self.samples: list = []  # (timestamp, byte count)
+        self.total = 0
+
+    def add(self, byte_count: int, now: Optional[float] = None):
+        now = time.monotonic() if now is None else now
+        self.samples.append((now, byte_count))
+        self.total += byte_count
+        self.prune(now)
+
+    def prune(self, now: float):
+        cutoff = now - self.window
+        while self.samples and self.samples[0][0] < cutoff:
+            self.samples.pop(0)
+
+    def rate(self, now: Optional[float] = None) -> float:
+        # Bytes per second across the window
+        now = time.monotonic() if now is None else now
+        self.prune(now)
+        return sum(count for _, count in self.samples) / self.window
+
+def frame_size(frame) -> int:
+    # Byte size of a text or binary WebSocket frame
+    return len(frame.encode("utf-8")) if isinstance(frame, str) else len(frame)
+
+def format_bytes(count: float) -> str:
+    for unit in ("B", "KB", "MB"):
+        if count < 1024 or unit == "MB":
+            return f"{count:.0f} {unit}" if unit == "B" else f"{count:.1f} {unit}"
+        count /= 1024
+
+class UserHistory:
+    # What /whois and /seen know about each person, kept for as long as we're in the room so a
+    # reconnect doesn't wipe it
+
+    def __init__(self):
+        self.first_seen: dict = {}  # Username -> when they first showed up
+        self.message_counts: dict = {}  # Username -> messages received from them
+        self.last_active: dict = {}  # Username -> their last message, join, leave or status change
+
+    def see(self, username: str, now: float):
+        self.first_seen.setdefault(username, now)
+
+    def count_message(self, username: str):
+        self.message_counts[username] = self.message_counts.get(username, 0) + 1
+
+class SessionStats:
+    # Per-connection counters, summarized when the connection ends. Per-user details go to the
+    # UserHistory, which reset leaves alone
+
+    def __init__(self, own_username: str, now: Optional[float] = None, history: Optional[UserHistory] = None):
+        self.own_username = own_username
+        self.history = history if history is not None else UserHistory()
+        self.reset(now)
+
+    def reset(self, now: Optional[float] = None):
+        self.started = time.time() if now is None else now
+        self.sent = 0
+        self.received = 0
+        self.users: set = set()
+
+    def see(self, username: str, now: Optional[float] = None):
+        if username and username != self.own_username:
+            self.users.add(username)
+            self.history.see(username, time.time() if now is None else now)
+
+    def activity(self, username: str, now: Optional[float] = None):
+        now = time.time() if now is None else now
+        self.see(username, now)
+        if username and username != self.own_username:
+            self.history.last_active[username] = now
+
+    def count_message(self, username: str):
+        self.received += 1
+        self.activity(username)
+        self.history.count_message(username)
+
+    def summary(self, now: Optional[float] = None) -> str:
+        elapsed = (time.time() if now is None else now) - self.started
+        return format_session_summary(self.sent + self.received, len(self.users), elapsed)
+
+# Cyrillic and Greek letters that are drawn the same as Latin ones in most fonts
+LATIN_LOOKALIKES = set("аеорсухіјѕԁһӏԛԝАВЕКМНОРСТХІЈЅοαρνυικτΑΒΕΖΗΙΚΜΝΟΡΤΥΧ")
+
+def letter_script(char: str) -> Optional[str]:
+    # The script a letter belongs to, from the first word of its Unicode name; None for non-letters
+    if not char.isalpha():
+        return None
+    return unicodedata.name(char, "UNKNOWN").split(" ")[0]
+
+# Scripts with letters that look the same as each other's. Other mixes are normal writing
+# (Han with kana in Japanese names, say), so only mixing these is suspicious
+LOOKALIKE_SCRIPTS = {"LATIN", "CYRILLIC", "GREEK"}
+
+def is_potentially_confusable(username: str) -> bool:
+    # True for names that could pass for someone else's: letters from more than one of LOOKALIKE_SCRIPTS
+    # (Latin "a" next to Cyrillic "а"), a non-Latin name spelled only with Latin lookalikes,
+    # invisible characters, or compatibility forms such as fullwidth letters
+    if any(unicodedata.category(char) == "Cf" for char in username):
+        return True
+    if unicodedata.normalize("NFKC", username) != unicodedata.normalize("NFC", username):
+        return True  # A compatibility form; an accent typed as a combining mark is just NFD, and fine
+    letters = [char for char in username if char.isalpha()]
+    scripts = {letter_script(char) for char in letters}
+    if len(scripts & LOOKALIKE_SCRIPTS) > 1:
+        return True
+    return bool(letters) and scripts != {"LATIN"} and all(char in LATIN_LOOKALIKES for char in letters)
+
+def format_whois(username: str, present: bool, color: str, status: str, first_seen: Optional[float], messages: int, now: float) -> list:
+    # Lines for /whois, built only from what this client has seen; unknown users get a single line
+    if not present and first_seen is None:
+        return [f"No one called {escape(username)} has been seen this session."]
+    lines = [f"[{color}]{escape(username)}[/{color}]"]
+    if is_potentially_confusable(username):
+        lines.append(f"  [yellow]{glyphs.warning} Possible impersonation: this name mixes lookalike characters from other alphabets[/yellow]")
+    lines.append(f"  Presence: {'in the room' if present else 'left'}")
+    if status:
+        lines.append(f"  Status: {escape(status)}")
+    lines.append(f"  Color: {escape(color)}")
+    if first_seen is not None:
+        lines.append(f"  First seen: {time.strftime('%H:%M', time.localtime(first_seen))} ({format_duration(now - first_seen)} ago)")
+    lines.append(f"  Messages this session: {messages}")
+    return lines
+
+def format_relative(now: float, then: float) -> str:
+    # "2 minutes ago" style, in the largest whole unit
+    seconds = max(0, int(now - then))
+    if seconds < 10:
+        return "just now"
+    for unit, size in (("day", 86400), ("hour", 3600), ("minute", 60), ("second", 1)):
+        if seconds >= size:
+            count = seconds // size
+            return f"{count} {unit}{'' if count == 1 else 's'} ago"
+
+def format_duration(seconds: float) -> str:
+    seconds = max(0, int(seconds))
+    if seconds < 60:
+        return f"{seconds}s"
+    hours, minutes = divmod(seconds // 60, 60)
+    return f"{hours}h {minutes}m" if hours else f"{minutes}m"
+
+def format_session_summary(messages: int, users: int, seconds: float) -> str:
+    return (f"Session ended {glyphs.dash} {messages} message{'' if messages == 1 else 's'}, "
+            f"{users} user{'' if users == 1 else 's'} seen, {format_duration(seconds)} connected")
+
+MAX_RECENT_ROOMS = 10
+
+def touch_recent_room(recent: list, server: str, chat: str, now: float, limit: int = MAX_RECENT_ROOMS) -> list:
+    # Move (server, chat) to the front with a fresh timestamp, evicting the least recent past the limit
+    entries = [entry for entry in recent if (entry.get("server"), entry.get("chat")) != (server, chat)]
+    entries.insert(0, {"server": server, "chat": chat, "last_joined": now})
+    return entries[:limit]
+
+def describe_recent_room(entry: dict) -> str:
+    host = urlparse(entry["server"]).hostname or entry["server"]
+    joined = time.strftime("%Y-%m-%d %H:%M", time.localtime(entry["last_joined"]))
+    return f"{entry['chat']} @ {host} ({joined})"
+
+def same_room(entry: dict, server: str, chat: str) -> bool:
+    # A trailing slash on the server URL doesn't make it a different server
+    return entry.get("server", "").rstrip("/") == server.rstrip("/") and entry.get("chat") == chat
+
+def is_room_blocked(blocked: list, server: str, chat: str) -> bool:
+    return any(same_room(entry, server, chat) for entry in blocked)
+
+def block_room(blocked: list, server: str, chat: str) -> bool:
+    # False if it was already blocked
+    if is_room_blocked(blocked, server, chat):
+        return False
+    blocked.append({"server": server, "chat": chat})
+    return True
+
+def unblock_room(blocked: list, server: str, chat: str) -> bool:
+    # False if it wasn't blocked
+    kept = [entry for entry in blocked if not same_room(entry, server, chat)]
+    removed = len(kept) < len(blocked)
+    blocked[:] = kept
+    return removed
+
+def rooms_on_server(recent: list, server: str, blocked: Optional[list] = None) -> list:
+    # Rooms joined before on this server and not blocked since, in a stable order for cycling through them
+    return sorted({entry["chat"] for entry in recent
+                   if entry.get("server") == server and not is_room_blocked(blocked or [], server, entry["chat"])}, key=str.lower)
+
+def room_cycle(rooms: list, current: str, step: int) -> Optional[str]:
+    # The room `step` places along from the current one, wrapping around; None if there's nowhere else to go
+    others = [room for room in rooms if room != current]
+    if not others:
+        return None
+    index = rooms.index(current) if current in rooms else (-1 if step > 0 else 0)
+    return rooms[(index + step) % len(rooms)]
+
+EVENT_HISTORY_SIZE = 500  # Events kept in memory for a diagnostics export
+
+# Fields never written to a diagnostics export, at any depth
+SECRET_FIELDS = {"password", "token", "session_token", "e2e_passphrase"}
+
+def mask_secrets(value):
+    # A copy with passwords and tokens masked at any depth, for events that leave the client
+    if isinstance(value, dict):
+        return {key: "[redacted]" if key in SECRET_FIELDS else mask_secrets(item) for key, item in value.items()}
+    if isinstance(value, list):
+        return [mask_secrets(item) for item in value]
+    return value
+
+def redact(value):
+    # A copy safe to attach to a public bug report: secrets are masked and chat text is
+    # reduced to its length, which is usually all a connection problem needs
+    if isinstance(value, dict):
+        redacted = {}
+        for key, item in value.items():
+            if key in SECRET_FIELDS:
+                redacted[key] = "[redacted]"
+            elif key == "content" and isinstance(item, str):
+                redacted[key] = f"[{len(item)} chars]"
+            else:
+                redacted[key] = redact(item)
+        return redacted
+    if isinstance(value, list):
+        return [redact(item) for item in value]
+    return value
+
+def package_version(name: str) -> str:
+    try:
+        return metadata.version(name)
+    except metadata.PackageNotFoundError:
+        return "unknown"
+
+def format_diagnostic_report(diagnostics: list, settings: dict, events) -> str:
+    # Plain text for /diag export: versions, the /diag summary, settings, then the event history
+    lines = ["Termchat diagnostics", f"Generated: {time.strftime('%Y-%m-%d %H:%M:%S %z')}", ""]
+    lines.append(f"Python {platform.python_version()} on {platform.platform()}")
+    for package in ("textual", "websockets", "aiohttp", "cryptography"):
+        lines.append(f"{package} {package_version(package)}")
+    lines += ["", "Connection:"]
+    lines += [f"  {line}" for line in diagnostics]
+    lines += ["", "Settings:"]
+    lines += [f"  {key} = {json.dumps(value)}" for key, value in sorted(redact(settings).items())]
+    lines += ["", f"Recent events ({len(events)}):"]
+    for item in events:
+        item = redact(item)
+        stamp = time.strftime("%H:%M:%S", time.localtime(item.pop("ts")))
+        lines.append(f"  {stamp} {item.pop('event')} {json.dumps(item)}")
+    return "\n".join(lines) + "\n"
+
+# Events that only report the latest state of something, so a newer one makes older ones redundant.
+# A subscriber that falls behind sheds these; chat messages and errors are never dropped.
+LOW_PRIORITY_EVENTS = {"latency", "outbox", "server:roster"}
+
+def event_kind(item: dict) -> str:
+    if item["event"] == "server":
+        return f"server:{item['data'].get('type')}"
+    return item["event"]
+
+def shed_events(pending: list, item: dict) -> tuple:
+    # Make room in a full queue for item: (events to keep, whether item goes in too). With nothing
+    # low-priority to shed, an important item still goes in and the queue grows past its limit
+    kind = event_kind(item)
+    if kind in LOW_PRIORITY_EVENTS:
+        kept = [queued for queued in pending if event_kind(queued) != kind]
+        if len(kept) < len(pending):
+            return kept, True  # Coalesced with the older updates it supersedes
+    low = next((index for index, queued in enumerate(pending) if event_kind(queued) in LOW_PRIORITY_EVENTS), None)
+    if low is not None:
+        return pending[:low] + pending[low + 1:], True
+    return pending, kind not in LOW_PRIORITY_EVENTS
+
+class EventStream:
+    # Fan-out of client events to any number of subscribers (loggers, bots, embedding apps).
+    # Each subscriber gets its own queue, held to maxsize by shedding low-priority events (see shed_events)
+    # rather than blocking delivery to the others. Queues aren't bounded by asyncio, so messages always fit.
+
+    def __init__(self, maxsize: int = 256, history: int = EVENT_HISTORY_SIZE):
+        self.maxsize = maxsize
+        self.subscribers: list = []
+        self.recent: deque = deque(maxlen=history)  # Kept for /diag export
+
+    def subscribe(self) -> asyncio.Queue:
+        queue = asyncio.Queue()
+        self.subscribers.append(queue)
+        return queue
+
+    def unsubscribe(self, queue: asyncio.Queue):
+        if queue in self.subscribers:
+            self.subscribers.remove(queue)
+
+    def publish(self, event: str, **fields):
+        item = {"event": event, "ts": time.time(), **fields}
+        self.recent.append(item)
+        for queue in self.subscribers:
+            if queue.qsize() >= self.maxsize:
+                pending = [queue.get_nowait() for _ in range(queue.qsize())]
+                pending, accept = shed_events(pending, item)
+                for queued in pending:
+                    queue.put_nowait(queued)
+                if not accept:
+                    continue
+            queue.put_nowait(item)
+
+# User colors for cycling through usernames
+USER_COLORS = [
+    "red", "green", "yellow", "magenta", 
+    "cyan", "bright_red", "bright_yellow", 
+    "bright_magenta", "bright_cyan"
+]
+
+# Typical (xterm) RGB values for USER_COLORS, used to judge contrast
+USER_COLOR_RGB = {
+    "red": (205, 0, 0), "green": (0, 205, 0), "yellow": (205, 205, 0),
+    "magenta": (205, 0, 205), "cyan": (0, 205, 205), "bright_red": (255, 0, 0),
+    "bright_yellow": (255, 255, 0), "bright_magenta": (255, 0, 255), "bright_cyan": (0, 255, 255),
+}
+
+# WCAG AA contrast ratio for normal text
+MIN_CONTRAST = 4.5
+
+def hex_to_rgb(hex_color: str) -> tuple:
+    hex_color = hex_color.lstrip("#")
+    return tuple(int(hex_color[i:i + 2], 16) for i in (0, 2, 4))
+
+def relative_luminance(rgb: tuple) -> float:
+    def channel(value: int) -> float:
+        value = value / 255
+        return value / 12.92 if value <= 0.03928 else ((value + 0.055) / 1.055) ** 2.4
+    r, g, b = (channel(v) for v in rgb)
+    return 0.2126 * r + 0.7152 * g + 0.0722 * b
+
+def contrast_ratio(fg: tuple, bg: tuple) -> float:
+    lighter, darker = sorted((relative_luminance(fg), relative_luminance(bg)), reverse=True)
+    return (lighter + 0.05) / (darker + 0.05)
+
+def readable_color(fg: tuple, bg: tuple) -> tuple:
+    # Nudge fg's lightness away from bg until it meets MIN_CONTRAST, keeping its hue
+    if contrast_ratio(fg, bg) >= MIN_CONTRAST:
+        return fg
+    h, l, sat = colorsys.rgb_to_hls(*(v / 255 for v in fg))
+    step = 0.05 if relative_luminance(bg) < 0.5 else -0.05
+    while 0.0 <= l <= 1.0:
+        l += step
+        candidate = tuple(round(v * 255) for v in colorsys.hls_to_rgb(h, min(max(l, 0.0), 1.0), sat))
+        if contrast_ratio(candidate, bg) >= MIN_CONTRAST:
+            return candidate
+    return (255, 255, 255) if step > 0 else (0, 0, 0)
+
+# ASCII Art for TERMCHAT
+TERMCHAT_ASCII = """
+████████ ███████ ██████  ███    ███  ██████ ██   ██  █████  ████████ 
+   ██    ██      ██   ██ ████  ████ ██      ██   ██ ██   ██    ██    
+   ██    █████   ██████  ██ ████ ██ ██      ███████ ███████    ██    
+   ██    ██      ██   ██ ██  ██  ██ ██      ██   ██ ██   ██    ██    
+   ██    ███████ ██   ██ ██      ██  ██████ ██   ██ ██   ██    ██    
+"""
+
+class SplashScreen(Screen):
+    # ASCII Art splash screen shown with a slide-up + fade-in animation
+    
+    CSS = """
+    SplashScreen {
+        align: center middle;
+        background: black;
+        color: #87CEEB;
+    }
+    
+    #splash {
+        width: auto;
+        height: auto;
+        content-align: center middle;
+        text-style: bold;
+        color: #87CEEB;
+    }
+
+    #splash_info {
+        width: auto;
+        color: #cccccc;
+    }
+
+    #splash_banner {
+        width: auto;
+        max-width: 80;
+        margin-top: 1;
+        text-align: center;
+    }
+    """
+
+    def __init__(self):
+        super().__init__()
+        self.advanced = False
+        self.animation_task: Optional[asyncio.Task] = None
+
+    def compose(self) -> ComposeResult:
+        # Create the Static so we can animate it in on_mount.
+        yield Static(TERMCHAT_ASCII.replace("█", glyphs.block), id="splash")
+        yield Label(self.info_text(None), id="splash_info")
+        yield Label("", id="splash_banner")
+
+    def info_text(self, count: Optional[int]) -> str:
+        host = urlparse(self.app.server_url).hostname or self.app.server_url
+        text = f"{escape(host)}"
+        if count is not None:
+            text += f"  {glyphs.separator}  [#90ee90]{count}[/#90ee90] user/s in general chat"
+        return text + f"  {glyphs.separator}  press any key to skip"
+
+    def on_mount(self):
+        # A zero duration skips the splash entirely
+        if self.app.settings["splash_duration_ms"] == 0:
+            self.show_connection()
+            return
+        # Start the splash animation asynchronously and advance when done.
+        self.query_one("#splash_banner").styles.color = self.app.theme_color
+        self.animation_task = asyncio.create_task(self._animate_and_advance())
+        asyncio.create_task(self.update_general_count())
+        asyncio.create_task(self.update_banner())
+
+    async def update_banner(self):
+        # Fetched alongside the animation; nothing is shown if the server has no banner
+        banner = await get_banner(self.app.get_http_session(), self.app.server_url, self.app.settings["banner_path"])
+        if banner and self.is_mounted:
+            self.query_one("#splash_banner", Label).update(escape(banner))
+
+    async def update_general_count(self):
+        count = await self.app.fetch_general_count()
+        if self.is_mounted:
+            self.query_one("#splash_info", Label).update(self.info_text(count))
+
+    def on_key(self, event):
+        event.stop()
+        self.skip()
+
+    def on_click(self, event):
+        self.skip()
+
+    def skip(self):
+        # Skipping doesn't wait on anything: the connection screen runs its own server check
+        if self.animation_task:
+            self.animation_task.cancel()
+        self.show_connection()
+
+    async def _animate_and_advance(self):
+        # Animate the splash logo: slide up from below and fade in with a strong ease-out.
+        # After the animation completes we wait a short moment then move to the connection screen.
+        
+        # Strong ease-out function (exponential) for a noticeable fast-then-slow effect
+        def ease_out_expo(t: float) -> float:
+            if t >= 1.0:
+                return 1.0
+            return 1 - pow(2, -20 * t)
+
+        # 60fps animation parameters
+        duration = self.app.settings["splash_duration_ms"] / 1000
+        target_fps = 60
+        frame_time = 1.0 / target_fps
+        total_frames = max(1, int(duration * target_fps))
+        start_offset_y = 25  # start a few rows lower (slides up to 0)
+        theme_r, theme_g, theme_b = hex_to_rgb(self.app.theme_color)
+        
+        try:
+            splash = self.query_one("#splash", Static)
+            
+            # Initialize starting position and color fade
+            splash.styles.opacity = 0.0
+            splash.styles.offset = (0, start_offset_y)
+            splash.styles.color = "#000000"  # Start black for color fade
+            
+            self.refresh()
+            await asyncio.sleep(0.016)
+            
+            # 60fps animation loop - slide up AND color fade
+            for i in range(total_frames + 1):
+                t = i / total_frames
+                eased = ease_out_expo(t)
+                
+                # Slide animation
+                y = int(start_offset_y * (1 - eased))
+                opacity = float(eased)
+                
+                # Color fade animation (black to the theme color)
+                r = int(theme_r * eased)
+                g = int(theme_g * eased)
+                b = int(theme_b * eased)
+                color = f"#{r:02x}{g:02x}{b:02x}"
+                
+                # Apply all animations
+                splash.styles.offset = (0, y)
+                splash.styles.opacity = opacity
+                splash.styles.color = color
+                
+                splash.refresh()
+                await asyncio.sleep(frame_time)
+
+            # Ensure final exact values
+            splash.styles.offset = (0, 0)
+            splash.styles.opacity = 1.0
+            splash.styles.color = self.app.theme_color
+            splash.refresh()
+            
+        except Exception:
+            await asyncio.sleep(duration)
+
+        await asyncio.sleep(0.25)
+        self.show_connection()
+
+    def show_connection(self):
+        # Reached from both the end of the animation and a skip, so only advance once
+        if self.advanced:
+            return
+        self.advanced = True
+        self.app.push_screen("connection")
+
+
+class ConnectionScreen(Screen):
+    # Modal screen for connection details 
+    
+    def __init__(self):
+        super().__init__()
+        self.connecting = False
+        self.general_count = None
+        
+    CSS = """
+    ConnectionScreen {
+        align: center middle;
+        background: black;
+    }
+    
+    #dialog {
+        width: 80;
+        height: 29;
+        border: solid #87CEEB;
+        background: black;
+        color: white;
+    }
+    #title {
+        dock: top;
+        width: 100%;
+        content-align: center middle;
+        text-style: bold;
+        color: #87CEEB;
+        background: black;
+        padding: 1 0;
+    }
+    #indicator_row {
+        layout: horizontal;
+        width: 100%;
+        align: center middle;
+        margin-bottom: 1;
+    }
+    #indicator_light {
+        width: 2;
+        content-align: center middle;
+    }
+    #indicator_text {
+        margin-left: 1;
+        color: white;
+    }
+
+    #hint_row {
+        width: 100%;
+        content-align: center middle;
+        margin-top: 2;
+    }
+    #form {
+        layout: vertical;
+        height: auto;
+        margin: 2;
+        padding: 1;
+    }
+    .form-row {
+        height: 3;
+        layout: horizontal;
+        margin-bottom: 1;
+    }
+    .label {
+        width: 15;
+        content-align: right middle;
+        margin-right: 2;
+        color: #cccccc;
+        text-style: bold;
+    }
+    .input {
+        width: 35;
+        height: 3;
+        color: white;
+        border: solid #333333;
+    }
+    .input:focus {
+        border: solid #87CEEB;
+    }
+    #status_label {
+        width: 40;
+        content-align: center middle;
+        color: yellow;
+        text-style: bold;
+    }
+    """
+    
+    BINDINGS = [
+        Binding("ctrl+c", "quit", "Quit"),
+        Binding("escape", "quit", "Quit"),
+        Binding("tab", "focus_next", "Next Field"),
+        Binding("shift+tab", "focus_previous", "Previous Field"),
+        Binding("enter", "connect", "Connect"),
+    ]
+
+    def compose(self) -> ComposeResult:
+        with Container(id="dialog"):
+            yield Label("TERMCHAT Connection", id="title")
+            with Container(id="indicator_row"):
+                yield Static(glyphs.indicator, id="indicator_light")
+                yield Label("Checking server...", id="indicator_text")
+            yield Container(
+                Label(
+                    "       Keep the [#cccccc]chat name[/#cccccc] and [#cccccc]password[/#cccccc] blank to join the [#90ee90]general chat[/#90ee90]",
+                    markup=True
+                ),
+                id="hint_row"
+            )
+            with Container(id="form"):
+                if self.app.settings["profiles"]:
+                    with Container(classes="form-row"):
+                        yield Label("Server:", classes="label")
+                        yield Select(
+                            [(profile["name"], profile["name"]) for profile in self.app.settings["profiles"]],
+                            prompt="Default server",
+                            id="profile_select",
+                            classes="input",
+                        )
+                if self.app.settings["recent_rooms"]:
+                    with Container(classes="form-row"):
+                        yield Label("Recent:", classes="label")
+                        yield Select(
+                            [(describe_recent_room(entry), str(index)) for index, entry in enumerate(self.app.settings["recent_rooms"])],
+                            prompt="Recent rooms",
+                            id="recent_select",
+                            classes="input",
+                        )
+                with Container(classes="form-row"):
+                    yield Label("Username:", classes="label")
+                    yield Input(placeholder="Enter username", id="username_input", classes="input")
+                with Container(classes="form-row"):
+                    yield Label("Chat name:", classes="label")
+                    yield Input(placeholder="Enter chat name", id="chatname_input", classes="input")
+                with Container(classes="form-row"):
+                    yield Label("Auth:", classes="label")
+                    yield Select(
+                        [("Password", "password"), ("Token", "token"), ("None", "none")],
+                        value="password",
+                        allow_blank=False,
+                        id="auth_select",
+                        classes="input",
+                    )
+                with Container(classes="form-row", id="password_row"):
+                    yield Label("Password:", id="password_label", classes="label")
+                    yield Input(placeholder="Enter password", password=True, id="password_input", classes="input")
+                with Container(classes="form-row"):
+                    yield Label("", id="status_label", classes="label")
+        yield Label("", id="general_count_label")
+
+    def on_mount(self):
+        apply_glyph_borders(self)
+        # Pre-fill the chat name from a join link passed on the command line
+        if self.app.initial_chat_name:
+            self.query_one("#chatname_input").value = self.app.initial_chat_name
+        # Make room for the quick-connect rows
+        extra_rows = bool(self.app.settings["profiles"]) + bool(self.app.settings["recent_rooms"])
+        self.query_one("#dialog").styles.height = 29 + 4 * extra_rows
+        self.query_one("#username_input").focus()
+        self.set_timer(0.1, self.check_server_status)
+        asyncio.create_task(self.update_general_count())
+
+    async def update_general_count(self):
+        count = await self.app.fetch_general_count()
+        self.general_count = count
+        label = self.query_one("#general_count_label")
+        label.update(f"[#90ee90]{count}[/#90ee90] user/s in general chat")
+
+    async def check_server_status(self):
+    # Foolproof: check server reachability using a certifi-backed SSL context
+        try:
+            import websockets  # keep local import if desired
+            url = self.app.websocket_url()
+            # short timeout/ping to keep this check fast
+            ws = await websockets.connect(url, ping_timeout=2, **tls_options(url), **bind_options(self.app.bind_address))
+            await ws.close()
+            self.server_available = True
+        except Exception:
+            self.server_available = False
+        self.update_indicator()
+
+    async def on_select_changed(self, event: Select.Changed):
+        # Quick-connect: fill the form from a server profile or a recent room
+        if not isinstance(event.value, str):
+            return
+        if event.select.id == "auth_select":
+            self.update_auth_field(event.value)
+            return
+        if event.select.id == "profile_select":
+            profile = find_profile(self.app.settings["profiles"], event.value)
+            self.app.server_url = profile["url"] if profile else DEFAULT_SERVER_URL
+            if profile:
+                if profile["default_username"]:
+                    self.query_one("#username_input").value = profile["default_username"]
+                if profile["default_chat"]:
+                    self.query_one("#chatname_input").value = profile["default_chat"]
+        elif event.select.id == "recent_select":
+            entry = self.app.settings["recent_rooms"][int(event.value)]
+            self.app.server_url = entry["server"]
+            self.query_one("#chatname_input").value = entry["chat"]
+        else:
+            return
+        # Re-check reachability and the general count for the newly selected server
+        await self.check_server_status()
+        await self.update_general_count()
+
+    def update_auth_field(self, auth_method: str):
+        # The secret field is a password or a token, and isn't needed at all without auth
+        self.query_one("#password_row").display = auth_method != "none"
+        name = "Token" if auth_method == "token" else "Password"
+        self.query_one("#password_label", Label).update(f"{name}:")
+        self.query_one("#password_input", Input).placeholder = f"Enter {name.lower()}"
+
+    def update_indicator(self):
+        indicator_light = self.query_one("#indicator_light")
+        indicator_text = self.query_one("#indicator_text")
+        if self.server_available:
+            indicator_light.update(glyphs.indicator)
+            indicator_light.styles.color = "#00ff00"
+            indicator_text.update("Server available")
+            indicator_text.styles.color = "#00ff00"
+        else:
+            indicator_light.update(glyphs.indicator)
+            indicator_light.styles.color = "#ff0000"
+            indicator_text.update("Server unavailable")
+            indicator_text.styles.color = "#ff0000"
+    
+    async def on_input_submitted(self, event: Input.Submitted):
+        # Handle Enter key in any input field - navigate to next or connect
+        if event.input.id == "username_input":
+            self.query_one("#chatname_input").focus()
+        elif event.input.id == "chatname_input":
+            if self.query_one("#auth_select", Select).value == "none":
+                await self.action_connect()
+            else:
+                self.query_one("#password_input").focus()
+        elif event.input.id == "password_input":
+            await self.action_connect()
+
+    async def action_connect(self):
+        if self.connecting:
+            return  # Already connecting
+            
+        username = self.query_one("#username_input").value.strip()
+        chat_name = self.query_one("#chatname_input").value.strip()
+        password = self.query_one("#password_input").value.strip()
+        auth_method = self.query_one("#auth_select", Select).value
+        
+        # Only check for forbidden username, no empty field warnings
+        if username.lower() == "server":
+            self.app.notify("Username 'server' is forbidden!", severity="error")
+            self.query_one("#username_input").focus()
+            return
+        
+        # Use defaults if fields are empty
+        if not username:
+            username = "guest"
+        if not chat_name:
+            chat_name = "general"
+        if not password and auth_method == "password":
+            password = "default"
+
+        # Blocked rooms stay blocked however they're reached, including from Recent or a saved server
+        if is_room_blocked(self.app.settings["blocked_rooms"], self.app.server_url, chat_name):
+            self.app.notify(sys_msg("room_blocked", chat=escape(chat_name)), severity="error")
+            return
+        
+        # Start the chat directly - no separate test connection to avoid duplicate join/leave notifications
+        try:
+            self.app.start_chat(username, chat_name, password, auth_method)
+        except Exception as e:
+            # Connection failed, show error and reset
+            self.connecting = False
+            status_label.update(f"[red]Connection failed: {str(e)}[/red]")
+            self.app.notify(f"Could not connect to server: {str(e)}", severity="error")
+
+
+    def action_quit(self):
+        self.app.exit()
+
+
+class TransportClosed(Exception):
+    # Raised by a Transport when the connection is gone, carrying the close code if there was one
+    def __init__(self, code: Optional[int] = None, reason: Optional[str] = None):
+        super().__init__(describe_close(code, reason))
+        self.code = code
+        self.reason = reason
+
+
+class Transport:
+    # Everything the chat screen needs from a connection to the server. Errors while connecting
+    # are left to each transport; once connected, a dropped connection raises TransportClosed.
+
+    close_code: Optional[int] = None
+    close_reason: Optional[str] = None
+
+    async def send(self, frame: str):
+        raise NotImplementedError
+
+    async def recv(self):
+        raise NotImplementedError
+
+    async def ping(self, timeout: float) -> float:
+        # Round trip in seconds; raises asyncio.TimeoutError when no reply comes in time
+        raise NotImplementedError
+
+    async def close(self, code: int = 1000, reason: str = ""):
+        raise NotImplementedError
+
+    def __aiter__(self):
+        return self
+
+    async def __anext__(self):
+        # Iteration ends when the connection closes, like iterating a websocket does
+        try:
+            return await self.recv()
+        except TransportClosed:
+            raise StopAsyncIteration
+
+
+class WebSocketTransport(Transport):
+    # The default transport: one WebSocket connection from the websockets library
+
+    def __init__(self, connection):
+        self.connection = connection
+        self.local_close: Optional[tuple] = None  # (code, reason) when we failed the connection ourselves
+
+    @classmethod
+    async def connect(cls, url: str, **options) -> "WebSocketTransport":
+        return cls(await websockets.connect(url, **options))
+
+    @property
+    def close_code(self) -> Optional[int]:
+        return self.local_close[0] if self.local_close else self.connection.close_code
+
+    @property
+    def close_reason(self) -> Optional[str]:
+        return self.local_close[1] if self.local_close else self.connection.close_reason
+
+    def closed(self, error: websockets.exceptions.ConnectionClosed) -> TransportClosed:
+        # When the library closes the connection itself (e.g. 1009 for an oversized frame) and the
+        # server never answers, close_code would only say 1006; report the code that was actually sent
+        sent = getattr(error, "sent", None)
+        if getattr(error, "rcvd", None) is None and sent is not None:
+            self.local_close = (sent.code, sent.reason)
+        return TransportClosed(self.close_code, self.close_reason)
+
+    async def send(self, frame: str):
+        try:
+            await self.connection.send(frame)
+        except websockets.exceptions.ConnectionClosed as e:
+            raise self.closed(e) from e
+
+    async def recv(self):
+        try:
+            return await self.connection.recv()
+        except websockets.exceptions.ConnectionClosed as e:
+            raise self.closed(e) from e
+
+    async def ping(self, timeout: float) -> float:
+        started = time.monotonic()
+        try:
+            pong_waiter = await self.connection.ping()
+            await asyncio.wait_for(pong_waiter, timeout=timeout)
+        except websockets.exceptions.ConnectionClosed as e:
+            raise self.closed(e) from e
+        return time.monotonic() - started
+
+    async def close(self, code: int = 1000, reason: str = ""):
+        await self.connection.close(code=code, reason=reason)
+
+
+class ThreadView(ModalScreen):
+    # A thread's root and its replies on their own, with a composer whose messages reply to the root.
+    # Ctrl+G (or Enter outside the composer) jumps to the root in the chat, Escape closes.
+
+    CSS = """
+    ThreadView {
+        align: center middle;
+    }
+
+    #thread_dialog {
+        width: 80%;
+        height: 70%;
+        border: solid #87CEEB;
+        background: black;
+        padding: 0 1;
+    }
+
+    #thread_log {
+        height: 1fr;
+        background: black;
+        color: white;
+    }
+
+    #thread_input {
+        border: none;
+        background: #1c1c1c;
+        color: white;
+    }
+    """
+
+    BINDINGS = [
+        Binding("escape", "close_thread", "Close"),
+        Binding("enter", "jump_to_root", "Jump to message"),
+        Binding("ctrl+g", "jump_to_root", "Jump to message"),
+    ]
+
+    def __init__(self, thread: list, send=None):
+        super().__init__()
+        self.thread = thread
+        self.send = send  # Called with the composer's text; None when the root can't be replied to
+        self.ids = {entry["id"] for entry in thread if entry.get("id") is not None}
+
+    @property
+    def root_id(self):
+        return self.thread[0].get("id")
+
+    def compose(self) -> ComposeResult:
+        with Vertical(id="thread_dialog"):
+            yield RichLog(id="thread_log", wrap=True, markup=True)
+            if self.send is not None and self.root_id is not None:
+                yield Input(placeholder="Reply in this thread...", id="thread_input")
+
+    def on_mount(self):
+        apply_glyph_borders(self)
+        self.update_title()
+        log = self.query_one("#thread_log", RichLog)
+        for entry in self.thread:
+            log.write(render_entry(entry))
+        if self.query("#thread_input"):
+            self.query_one("#thread_input").focus()
+
+    def update_title(self):
+        dialog = self.query_one("#thread_dialog")
+        dialog.border_title = f"Thread {glyphs.dash} {len(self.thread) - 1} repl{'y' if len(self.thread) == 2 else 'ies'}"
+
+    def belongs(self, entry: dict) -> bool:
+        return entry.get("reply_to") is not None and entry["reply_to"] in self.ids
+
+    def add_entry(self, entry: dict):
+        # A reply that arrived while the thread is open, whether from us or anyone else
+        self.thread.append(entry)
+        if entry.get("id") is not None:
+            self.ids.add(entry["id"])
+        self.query_one("#thread_log", RichLog).write(render_entry(entry))
+        self.update_title()
+
+    async def on_input_submitted(self, event: Input.Submitted):
+        event.stop()  # Never reaches the chat screen's own input handling
+        event.input.clear()
+        await self.send(event.value)
+
+    def action_jump_to_root(self):
+        self.dismiss(self.thread[0])
+
+    def action_close_thread(self):
+        self.dismiss(None)
+
+
+class EmojiPicker(ModalScreen):
+    # Searchable emoji list opened from the chat input; dismisses with the chosen emoji or None
+
+    CSS = """
+    EmojiPicker {
+        align: center middle;
+    }
+
+    #emoji_dialog {
+        width: 50;
+        height: 22;
+        border: solid #87CEEB;
+        background: black;
+        padding: 0 1;
+    }
+
+    #emoji_search {
+        border: none;
+        background: black;
+        color: white;
+    }
+
+    #emoji_results {
+        height: 1fr;
+        background: black;
+        color: white;
+    }
+    """
+
+    BINDINGS = [
+        Binding("escape", "dismiss_picker", "Close"),
+    ]
+
+    def __init__(self, custom: dict):
+        super().__init__()
+        self.custom = custom
+        self.category = "all"
+        self.emoji_by_name: dict = {}
+
+    def compose(self) -> ComposeResult:
+        with Vertical(id="emoji_dialog"):
+            yield Tabs(
+                Tab("All", id="all"),
+                *(Tab(category.capitalize(), id=category) for category in EMOJI_CATEGORIES),
+                id="emoji_tabs",
+            )
+            yield Input(placeholder="Search by name, e.g. heart", id="emoji_search")
+            yield OptionList(id="emoji_results")
+
+    def on_mount(self):
+        apply_glyph_borders(self)
+        self.query_one("#emoji_search").focus()
+        self.refresh_results()
+
+    def refresh_results(self):
+        results = self.query_one("#emoji_results", OptionList)
+        results.clear_options()
+        query = self.query_one("#emoji_search", Input).value
+        matches = search_emoji(query, self.category, self.custom)
+        self.emoji_by_name = {name: emoji for emoji, name in matches}
+        results.add_options([Option(f"{emoji}  :{name}:", id=name) for emoji, name in matches])
+        if matches:
+            results.highlighted = 0
+
+    def on_tabs_tab_activated(self, event: Tabs.TabActivated):
+        self.category = event.tab.id
+        self.refresh_results()
+
+    def on_input_changed(self, event: Input.Changed):
+        self.refresh_results()
+
+    def on_input_submitted(self, event: Input.Submitted):
+        # Enter in the search box takes the highlighted match
+        results = self.query_one("#emoji_results", OptionList)
+        if results.highlighted is not None:
+            self.choose(results.get_option_at_index(results.highlighted).id)
+
+    def on_key(self, event):
+        # Arrow keys move through the results while typing in the search box
+        results = self.query_one("#emoji_results", OptionList)
+        if self.focused is self.query_one("#emoji_search") and event.key in ("up", "down"):
+            event.stop()
+            if event.key == "up":
+                results.action_cursor_up()
+            else:
+                results.action_cursor_down()
+
+    def on_option_list_option_selected(self, event: OptionList.OptionSelected):
+        self.choose(event.option.id)
+
+    def choose(self, name: str):
+        # With --ascii the shortcode goes in instead, since the emoji itself may not display
+        self.dismiss(self.emoji_by_name[name] if glyphs.emoji else f":{name}:")
+
+    def action_dismiss_picker(self):
+        self.dismiss(None)
+
+
+class SessionTokenRejected(Exception):
+    # Raised during the join handshake when the server refuses our session token
+    pass
+
+
+class JoinRejected(Exception):
+    # Raised during the join handshake when the server turns down our name, password or token
+    pass
+
+
+class MessageInput(Input):
+    # Single-line message input; lets the chat screen claim keys while the mention dropdown is open
+
+    async def _on_key(self, event):
+        if self.screen.mention_key(event.key):
+            event.stop()
+            event.prevent_default()
+            return
+        await super()._on_key(event)
+
+
+class MultilineInput(TextArea):
+    # Message input used when enter_sends is off: Enter inserts a newline, Ctrl+Enter sends.
+
+    class Submitted(Message):
+        def __init__(self, input: "MultilineInput", value: str):
+            super().__init__()
+            self.input = input
+            self.value = value
+
+    async def _on_key(self, event):
+        if self.screen.mention_key(event.key):
+            event.stop()
+            event.prevent_default()
+            return
+        # Most terminals report Ctrl+Enter as ctrl+j, so accept both
+        if event.key in ("ctrl+enter", "ctrl+j"):
+            event.stop()
+            event.prevent_default()
+            self.post_message(self.Submitted(self, self.text))
+            return
+        if event.key == "shift+enter":
+            event.stop()
+            event.prevent_default()
+            self.insert("\n")
+            return
+        await super()._on_key(event)
+
+    def clear(self):
+        self.load_text("")
+
+
+class ChatScreen(Screen):
+    # Main chat screen
+    
+    CSS = """
+    ChatScreen {
+        layout: vertical;
+        background: black;
+        color: white;
+    }
+    
+    #header {
+        dock: top;
+        height: 3;
+        background: black;
+        color: #87CEEB;
+        content-align: center middle;
+        text-style: bold;
+        padding: 1 0;
+    }
+    
+    #messages_container {
+        height: 1fr;
+        border: solid #87CEEB;
+        margin: 0;
+    }
+    
+    #messages {
+        height: 1fr;
+        scrollbar-gutter: stable;
+        border: none;
+        background: black;
+        color: white;
+        padding: 1;
+    }
+    
+    #input_container {
+        dock: bottom;
+        layout: horizontal;
+        height: 5;
+        border: solid #87CEEB;
+        margin: 0 0 1 0;
+        padding: 1;
+        content-align: center middle;
+    }
+    
+    #message_input {
+        width: 1fr;
+        height: 3;
+        border: none;
+        background: black;
+        color: white;
+        content-align: center middle;
+    }
+    
+    #message_input:focus {
+        border: none;
+    }
+
+    #emoji_button {
+        width: 4;
+        min-width: 4;
+        height: 1;
+        border: none;
+        background: black;
+    }
+
+    #broadcasts {
+        display: none;
+        height: auto;
+        background: black;
+        color: yellow;
+        padding: 0 1;
+    }
+
+    #broadcasts.open {
+        display: block;
+    }
+
+    #outbox_bar {
+        display: none;
+        height: 1;
+        background: black;
+        color: yellow;
+        padding: 0 1;
+    }
+
+    #outbox_bar.open {
+        display: block;
+    }
+
+    #preview {
+        display: none;
+        height: auto;
+        max-height: 6;
+        border: solid #87CEEB;
+        background: black;
+        color: white;
+        padding: 0 1;
+    }
+
+    #preview.open {
+        display: block;
+    }
+
+    #mention_list {
+        display: none;
+        height: auto;
+        max-height: 10;
+        border: solid #87CEEB;
+        background: black;
+        color: white;
+    }
+
+    #mention_list.open {
+        display: block;
+    }
+
+    .multiline #input_container {
+        height: 8;
+    }
+
+    .multiline #message_input {
+        height: 6;
+    }
+
+    .focus-mode #header, .focus-mode #broadcasts, .focus-mode #mention_list,
+    .focus-mode #preview, .focus-mode #outbox_bar, .focus-mode #input_container {
+        display: none;
+    }
+    """
+    
+    BINDINGS = [
+        Binding("ctrl+c", "quit", "Quit"),
+        Binding("ctrl+q", "quit", "Quit"),
+        Binding("ctrl+p", "toggle_preview", "Preview"),
+        Binding("f1", "app.command_palette", "Commands"),
+        Binding("ctrl+e", "emoji_picker", "Emoji"),
+        Binding("ctrl+r", "retry_message", "Retry"),
+        Binding("escape", "blur_input", "Unfocus", show=False),
+        Binding("f11", "toggle_focus_mode", "Focus mode", show=False),
+        Binding("f3", "search_step(1)", "Next match", show=False),
+        Binding("shift+f3", "search_step(-1)", "Previous match", show=False),
+        # Most terminals send Ctrl+Tab as a plain Tab, so Alt+arrows are the ones that always arrive
+        Binding("alt+right", "cycle_room(1)", "Next room", show=False),
+        Binding("alt+left", "cycle_room(-1)", "Previous room", show=False),
+        Binding("ctrl+tab", "cycle_room(1)", "Next room", show=False),
+        Binding("ctrl+shift+tab", "cycle_room(-1)", "Previous room", show=False),
+        *(Binding(f"alt+{number}", f"room_number({number})", f"Room {number}", show=False) for number in range(1, 10)),
+    ]
+
+    def __init__(self, username: str, chat_name: str, password: str, auth_method: str = "password"):
+        super().__init__()
+        self.username = username
+        self.chat_name = chat_name
+        self.password = password  # The password or token, depending on auth_method
+        self.auth_method = auth_method
+        self.last_message: Optional[tuple] = None  # (sender, timestamp) of the previous message
+        self.joining: bool = False  # True between opening the socket and the server's join echo
+        self.pending_sends: list = []  # Messages typed before the join was confirmed
+        self.outbox: list = []  # Messages whose send failed mid-flight, with how many attempts they've had
+        self.restore_scroll_offset: Optional[float] = None  # Lines from the bottom we'd scrolled to when we last left this room
+        self.stats = SessionStats(username)
+        self.preview_enabled: bool = False  # Ctrl+P shows the current draft rendered as it will be sent
+        self.health: Optional[HealthMonitor] = None  # Only set while connected
+        self.capabilities = ServerCapabilities()  # Replaced by what the server advertises on join
+        self.max_length = DEFAULT_MAX_MESSAGE_LENGTH  # Likewise the longest message it accepts
+        self.awaiting_echo: list = []  # Log entries of messages shown optimistically, until the server echoes them
+        self.slowmode: float = 0.0  # Server-enforced seconds between our messages, 0 when off
+        self.next_send_at: float = 0.0  # Monotonic time before which sending is refused
+        self.penalty_until: float = 0.0  # End of a rate-limit backoff the server asked for (monotonic)
+        self.cooldown_timer = None
+        self.undo_buffer = UndoBuffer()
+        self.clear_confirm_until: float = 0.0  # A second /clear before this confirms the first
+        self.clear_ignores_until: float = 0.0  # Same for /clear-ignores
+        self.roster: set = set()  # Usernames currently in the room
+        self.roster_updating: bool = False
+        self.statuses: dict = {}  # Username -> custom status text
+        self.custom_emoji: dict = {}  # Server-provided shortcodes, only valid for this connection
+        self.server_disconnected: bool = False  # Set when the server told us why it is closing
+        self.reconnect_timer = None
+        self.connect_worker = None  # The connect attempt in progress, so it can be called off
+        self.reconnecting: bool = False  # A reconnect is in progress; failures retry instead of leaving
+        self.waiting_for_network: bool = False  # A reconnect is held until the server's host answers
+        self.reconnect_attempts: int = 0
+        self.session_token: Optional[str] = None  # From the last join confirmation, never persisted
+        self.used_session_token: bool = False
+        self.connection_state: str = "connecting"
+        self.room_info_timer = None
+        self.announced_keys: dict = {}  # Username -> signing key they announced this session, not trusted until /trust
+        self.seen_signatures: dict = {}  # (username, signed_at) -> message id, see signature_state
+        self.signing_key: Optional[Ed25519PrivateKey] = None  # Loaded when signing is first needed
+        self.polls: dict = {}  # poll_id -> (Poll, its log entry)
+        self.thread_view: Optional[ThreadView] = None  # The thread dialog while it's open, so replies show up live
+        self.search_matches: list = []  # Entries found by the last /find, stepped through with /next and /prev
+        self.search_index = -1
+        self.sent_history: deque = deque(maxlen=SENT_HISTORY_SIZE)  # What we've sent this session, for /again
+        self.simulate_step: int = 0  # Next step of the --dev event generator
+        self.chatter_timer = None  # Running /simulate chatter
+        self.broadcasts = BroadcastStore()
+        self.broadcast_timer = None  # Fires when the next broadcast expires
+        self.presence = PresenceWindow()
+        self.presence_timer = None  # Pending presence summary while join/leave lines are held back
+        self.entries: list = []  # Everything shown in the log, see write_line
+        self.frame_buffer: str = ""  # Incomplete JSON carried over between frames
+        self.e2e_key: Optional[bytes] = (
+            derive_e2e_key(self.app.e2e_passphrase, chat_name) if self.app.e2e_passphrase else None
+        )
+
+        
+    def compose(self) -> ComposeResult:
+        yield Label(f"TERMCHAT - Connecting to '{self.chat_name}'...", id="header")
+        yield Static(id="broadcasts")
+        with Container(id="messages_container"):
+            yield RichLog(id="messages", highlight=True, markup=True)
+        mention_list = OptionList(id="mention_list")
+        mention_list.can_focus = False  # Typing stays in the message input while it's open
+        yield mention_list
+        yield Static(id="preview")
+        yield Static(id="outbox_bar")
+        with Container(id="input_container"):
+            yield self.make_message_input()
+            yield Button(glyphs.smiley, id="emoji_button")
+
+    def make_message_input(self):
+        # Single-line Input when Enter sends, otherwise a multi-line area sent with Ctrl+Enter
+        if self.app.settings["enter_sends"]:
+            self.remove_class("multiline")
+            return MessageInput(placeholder=input_placeholder(self.connection_state), id="message_input")
+        self.add_class("multiline")
+        return MultilineInput(id="message_input")
+
+    async def refresh_message_input(self):
+        # Swap the input widget after enter_sends changes, keeping any typed text
+        draft = self.get_draft()
+        await self.query_one("#message_input").remove()
+        await self.query_one("#input_container").mount(self.make_message_input(), before="#emoji_button")
+        self.set_draft(draft)
+        self.query_one("#message_input").focus()
+
+    def get_draft(self) -> str:
+        input_widget = self.query_one("#message_input")
+        return input_widget.text if isinstance(input_widget, MultilineInput) else input_widget.value
+
+    def set_draft(self, draft: str):
+        input_widget = self.query_one("#message_input")
+        if isinstance(input_widget, MultilineInput):
+            input_widget.load_text(draft)
+        else:
+            input_widget.value = draft.replace("\n", " ")
+
+    @property
+    def draft_key(self) -> tuple:
+        return (self.app.server_url, self.chat_name)
+
+    def save_draft(self, draft: str):
+        # Keep half-typed text so coming back to this room restores it
+        if draft:
+            self.app.drafts[self.draft_key] = draft
+        else:
+            self.app.drafts.pop(self.draft_key, None)
+
+    def on_input_changed(self, event: Input.Changed):
+        if event.input.id == "message_input":
+            self.save_draft(event.value)
+            self.update_mentions()
+            self.update_preview()
+            self.update_length_counter()
+
+    def on_text_area_changed(self, event: TextArea.Changed):
+        if event.text_area.id == "message_input":
+            self.save_draft(event.text_area.text)
+            self.update_mentions()
+            self.update_preview()
+            self.update_length_counter()
+
+    def update_length_counter(self):
+        # Characters typed against the limit, under the input; commands aren't messages so don't count
+        draft = self.get_draft()
+        container = self.query_one("#input_container")
+        if not draft or draft.lstrip().startswith("/"):
+            container.border_subtitle = None
+            return
+        # The server limits what it relays, which is the ciphertext when encryption is on
+        length = wire_length(self.e2e_key, draft)
+        style = LENGTH_LEVEL_STYLES[length_level(length, self.max_length)]
+        container.border_subtitle = f"[{style}]{length}/{self.max_length}[/{style}]"
+
+    def on_button_pressed(self, event: Button.Pressed):
+        if event.button.id == "emoji_button":
+            self.action_emoji_picker()
+
+    def action_emoji_picker(self):
+        self.app.push_screen(EmojiPicker(self.custom_emoji), self.insert_emoji)
+
+    def insert_emoji(self, emoji: Optional[str]):
+        # Insert at the cursor rather than appending, in either input mode
+        input_widget = self.query_one("#message_input")
+        if emoji:
+            if isinstance(input_widget, MultilineInput):
+                input_widget.insert(emoji)
+            else:
+                input_widget.insert_text_at_cursor(emoji)
+        input_widget.focus()
+
+    async def go_to_room(self, chat_name: str):
+        # Leave this room and join another with the same name and credentials; drafts are kept per room
+        if not chat_name:
+            self.write_line(f"[bold yellow]Usage: {escape('/go <room>')}[/bold yellow]")
+            return
+        if chat_name == self.chat_name:
+            self.write_line(f"[bold yellow]{sys_msg('already_in_room', chat=escape(chat_name))}[/bold yellow]")
+            return
+        if is_room_blocked(self.app.settings["blocked_rooms"], self.app.server_url, chat_name):
+            self.write_line(f"[bold red]{sys_msg('room_blocked', chat=escape(chat_name))}[/bold red]")
+            return
+        self.stash_room_state()
+        await self.leave_room()
+        self.app.switch_screen(ChatScreen(self.username, chat_name, self.password, self.auth_method))
+
+    def stash_room_state(self):
+        # Unsent messages belong to this room, so they wait here until we come back instead of being
+        # dropped or sent somewhere else; so does how far up the log we were reading
+        unsent = [{key: value for key, value in item.items() if key != "entry"} for item in self.outbox]
+        unsent += [{"content": user_message, "attempts": 0, "reply_to": reply_to} for user_message, reply_to in self.pending_sends]
+        messages_log = self.query_one("#messages", RichLog)
+        offset = None if self.log_pinned() else messages_log.max_scroll_y - messages_log.scroll_y
+        self.app.left_rooms[self.draft_key] = {"outbox": unsent, "scroll_offset": offset}
+        if unsent:
+            count = len(unsent)
+            self.app.notify(sys_msg("unsent_kept", count=count, s="" if count == 1 else "s", chat=escape(self.chat_name)), severity="warning")
+
+    def restore_room_state(self):
+        saved = self.app.left_rooms.pop(self.draft_key, None)
+        if saved is None:
+            return
+        self.outbox = saved["outbox"]
+        self.restore_scroll_offset = saved["scroll_offset"]
+        self.refresh_outbox()
+
+    def scroll_to_saved_offset(self):
+        offset, self.restore_scroll_offset = self.restore_scroll_offset, None
+        if offset is not None:
+            messages_log = self.query_one("#messages", RichLog)
+            messages_log.scroll_to(y=max(0, messages_log.max_scroll_y - offset), animate=False)
+
+    async def leave_room(self):
+        # Drop the connection for good, without the reconnect logic stepping in
+        self.cancel_reconnect()
+        self.reconnecting = False
+        # An attempt still in flight would otherwise hand its socket to the next room's screen
+        await self.cancel_connect()
+        transport, self.app.transport = self.app.transport, None
+        self.app.connected = False
+        if transport:
+            try:
+                await asyncio.wait_for(transport.close(), timeout=SHUTDOWN_TIMEOUT)
+            except Exception:
+                pass
+
+    async def block_current_room(self):
+        # Leave and refuse to come back, until /unblock-room
+        block_room(self.app.settings["blocked_rooms"], self.app.server_url, self.chat_name)
+        save_settings(self.app.settings)
+        await self.leave_room()
+        self.app.notify(sys_msg("room_now_blocked", chat=escape(self.chat_name)))
+        self.app.pop_screen()
+
+    def unblock_room_command(self, args: str):
+        # /unblock-room <chat> on this server, /unblock-room <server> <chat> elsewhere; no arguments lists them
+        blocked = self.app.settings["blocked_rooms"]
+        name = args.strip()
+        if not name:
+            if not blocked:
+                self.write_line(f"[bold #87CEEB]{sys_msg('no_blocked_rooms')}[/bold #87CEEB]")
+                return
+            self.write_line("[bold #87CEEB]Blocked rooms:[/bold #87CEEB]")
+            for entry in blocked:
+                self.write_line(f"  {escape(entry['chat'])} on {escape(entry['server'])}")
+            return
+        # Room names can have spaces, so only a leading server URL is split off
+        first, _, rest = name.partition(" ")
+        if first.startswith(("wss://", "ws://")):
+            server, chat = first, rest.strip()
+        else:
+            server, chat = self.app.server_url, name
+        if not chat:
+            self.write_line(f"[bold yellow]Usage: {escape('/unblock-room [server] <chat>')}[/bold yellow]")
+            return
+        if not unblock_room(blocked, server, chat):
+            self.write_line(f"[bold yellow]{sys_msg('room_not_blocked', chat=escape(chat))}[/bold yellow]")
+            return
+        save_settings(self.app.settings)
+        self.write_line(f"[bold #87CEEB]{sys_msg('room_unblocked', chat=escape(chat))}[/bold #87CEEB]")
+
+    def room_list(self) -> list:
+        return rooms_on_server(self.app.settings["recent_rooms"], self.app.server_url, self.app.settings["blocked_rooms"])
+
+    async def action_cycle_room(self, step: int):
+        target = room_cycle(self.room_list(), self.chat_name, step)
+        if target is None:
+            self.write_line(f"[bold yellow]{sys_msg('no_other_rooms')}[/bold yellow]")
+            return
+        await self.go_to_room(target)
+
+    async def action_room_number(self, number: int):
+        rooms = self.room_list()
+        if number > len(rooms):
+            self.write_line(f"[bold yellow]{sys_msg('no_room_number', number=number)}[/bold yellow]")
+            return
+        await self.go_to_room(rooms[number - 1])
+
+    async def action_retry_message(self):
+        await self.retry_message()
+
+    def action_toggle_preview(self):
+        # Only the preview pane changes; the draft in the input is left alone
+        self.preview_enabled = not self.preview_enabled
+        self.update_preview()
+
+    def refresh_broadcasts(self):
+        # Show the broadcasts that are still current and wake up again when the next one runs out
+        if self.broadcast_timer:
+            self.broadcast_timer.stop()
+            self.broadcast_timer = None
+        banner = self.query_one("#broadcasts", Static)
+        active = self.broadcasts.prune(time.time())
+        if not active:
+            banner.remove_class("open")
+            return
+        banner.update("\n".join(f"[bold]{glyphs.indicator}[/bold] {escape(expand_shortcodes(content, self.custom_emoji))}" for content in active))
+        banner.add_class("open")
+        self.broadcast_timer = self.set_timer(max(0.0, self.broadcasts.next_expiry() - time.time()), self.refresh_broadcasts)
+
+    async def simulate(self, kind: str):
+        # Feeds fake events through handle_message, the same path real server events take
+        kind = kind.lower()
+        if kind not in SIMULATE_KINDS:
+            self.write_line(f"[bold yellow]Usage: /simulate {escape('|'.join(SIMULATE_KINDS))}[/bold yellow]")
+            return
+        if kind == "chatter":
+            # Toggles a stream of fake messages, one every couple of seconds
+            if self.chatter_timer:
+                self.chatter_timer.stop()
+                self.chatter_timer = None
+            else:
+                self.chatter_timer = self.set_interval(SIMULATED_CHATTER_INTERVAL, partial(self.simulate, "message"))
+            return
+        data = simulated_event(kind, self.simulate_step)
+        self.simulate_step += 1
+        await self.handle_message(data)
+
+    def dismiss_broadcasts(self):
+        if not self.broadcasts.items:
+            self.write_line(f"[bold yellow]{sys_msg('no_broadcasts')}[/bold yellow]")
+            return
+        self.broadcasts.clear()
+        self.refresh_broadcasts()
+
+    def update_preview(self):
+        preview = self.query_one("#preview", Static)
+        draft = self.get_draft()
+        if not (self.preview_enabled and draft.strip()):
+            preview.remove_class("open")
+            return
+        preview.update(self.render_message_body(draft))
+        preview.add_class("open")
+
+    def text_before_cursor(self) -> str:
+        input_widget = self.query_one("#message_input")
+        if isinstance(input_widget, MultilineInput):
+            row, column = input_widget.cursor_location
+            return input_widget.document.get_line(row)[:column]
+        return input_widget.value[:input_widget.cursor_position]
+
+    def update_mentions(self):
+        # Open, narrow or close the @mention dropdown to match what's being typed
+        mention_list = self.query_one("#mention_list", OptionList)
+        query = mention_query(self.text_before_cursor())
+        candidates = mention_candidates(query, self.roster, self.username) if query is not None else []
+        mention_list.clear_options()
+        if not candidates:
+            mention_list.remove_class("open")
+            return
+        mention_list.add_options(candidates)
+        mention_list.highlighted = 0
+        mention_list.add_class("open")
+
+    def close_mentions(self):
+        mention_list = self.query_one("#mention_list", OptionList)
+        mention_list.clear_options()
+        mention_list.remove_class("open")
+
+    def mention_key(self, key: str) -> bool:
+        # Called by the message input before it handles a key; True means the dropdown used it
+        mention_list = self.query_one("#mention_list", OptionList)
+        if not mention_list.has_class("open"):
+            return False
+        if key == "up":
+            mention_list.action_cursor_up()
+        elif key == "down":
+            mention_list.action_cursor_down()
+        elif key in ("enter", "tab"):
+            if mention_list.highlighted is not None:
+                self.insert_mention(str(mention_list.get_option_at_index(mention_list.highlighted).prompt))
+        elif key == "escape":
+            self.close_mentions()
+        else:
+            return False
+        return True
+
+    def on_option_list_option_selected(self, event: OptionList.OptionSelected):
+        if event.option_list.id == "mention_list":
+            self.insert_mention(str(event.option.prompt))
+            self.query_one("#message_input").focus()
+
+    def insert_mention(self, username: str):
+        # Replace the partial @name before the cursor with the full name and a trailing space
+        query = mention_query(self.text_before_cursor())
+        if query is None:
+            return
+        replaced = len(query) + 1  # The @ and what was typed after it
+        mention = f"@{username} "
+        input_widget = self.query_one("#message_input")
+        if isinstance(input_widget, MultilineInput):
+            row, column = input_widget.cursor_location
+            result = input_widget.replace(mention, (row, column - replaced), (row, column))
+            input_widget.cursor_location = result.end_location
+        else:
+            cursor = input_widget.cursor_position
+            value = input_widget.value
+            input_widget.value = value[:cursor - replaced] + mention + value[cursor:]
+            input_widget.cursor_position = cursor - replaced + len(mention)
+        self.close_mentions()
+
+    async def on_mount(self):
+        # Initialize the chat screen
+        apply_glyph_borders(self)
+        # Apply a non-default starting theme before connecting
+        if self.app.theme_color != DEFAULT_THEME_COLOR:
+            await self.change_theme_color(self.app.theme_color)
+        if self.app.background_color != DEFAULT_BACKGROUND_COLOR:
+            await self.change_background_color(self.app.background_color)
+        self.set_interval(OS_SCHEME_POLL_INTERVAL, self.follow_os_scheme)
+        self.query_one("#message_input").can_focus = True
+        self.focus_input()
+        self.set_draft(self.app.drafts.get(self.draft_key, ""))
+        self.restore_room_state()
+        # Connect in the background so the screen stays responsive during the join handshake
+        self.connect_worker = self.run_worker(self.connect_to_server())
+
+    async def on_input_submitted(self, event: Input.Submitted):
+        # Handle user message input
+        if event.input.id != "message_input":
+            return
+        event.input.clear()
+        await self.submit_message(event.value)
+        self.focus_input()
+
+    async def on_multiline_input_submitted(self, event: MultilineInput.Submitted):
+        event.input.clear()
+        await self.submit_message(event.value)
+        self.focus_input()
+
+    def focus_input(self):
+        # Commands like /thread can open a dialog, and focus must stay with it
+        if self.app.settings["auto_focus"] and self.app.screen is self and not self.has_class("focus-mode"):
+            self.query_one("#message_input").focus()
+
+    def action_blur_input(self):
+        # In focus mode the input is hidden, so Escape is the obvious way back to it
+        if self.has_class("focus-mode"):
+            self.action_toggle_focus_mode()
+        else:
+            self.set_focus(None)
+
+    def action_toggle_focus_mode(self):
+        # Only the message log stays. The hidden widgets keep their own state (an open preview,
+        # a half-typed draft), so leaving focus mode puts the layout back exactly as it was.
+        entering = not self.has_class("focus-mode")
+        self.set_class(entering, "focus-mode")
+        container = self.query_one("#messages_container")
+        container.border_subtitle = "F11 or Escape to leave focus mode" if entering else None
+        if entering:
+            self.set_focus(self.query_one("#messages"))
+        else:
+            self.query_one("#message_input").focus()
+
+    def action_search_step(self, step: int):
+        self.search_step(step)
+
+    async def submit_message(self, value: str):
+        # Shared send path for both input modes
+        if self.app.settings["normalize_outgoing"]:
+            user_message = normalize_message(value) or ""
+            if user_message.lstrip().startswith("/"):
+                user_message = user_message.strip()  # Indentation only matters in messages, not commands
         else:
-            indicator_light.update("●")
-            indicator_light.styles.color = "#ff0000"
-            indicator_text.update("Server unavailable")
-            indicator_text.styles.color = "#ff0000"
-    
-    async def on_input_submitted(self, event: Input.Submitted):
-        # Handle Enter key in any input field - navigate to next or connect
-        if event.input.id == "username_input":
-            self.query_one("#chatname_input").focus()
-        elif event.input.id == "chatname_input":
-            self.query_one("#password_input").focus()
-        elif event.input.id == "password_input":
-            await self.action_connect()
+            user_message = value.strip()
 
-    async def action_connect(self):
-        if self.connecting:
-            return  # Already connecting
-            
-        username = self.query_one("#username_input").value.strip()
-        chat_name = self.query_one("#chatname_input").value.strip()
-        password = self.query_one("#password_input").value.strip()
+        if not user_message:
+            return
         
-        # Only check for forbidden username, no empty field warnings
-        if username.lower() == "server":
-            self.app.notify("Username 'server' is forbidden!", severity="error")
-            self.query_one("#username_input").focus()
+        spec = find_command(user_message)
+        if spec:
+            if spec.capability and not self.capabilities.supports(spec.capability):
+                self.write_line(f"[bold yellow]{sys_msg('unsupported', command=spec.name)}[/bold yellow]")
+                return
+            result = spec.run(self, user_message)
+            if inspect.isawaitable(result):
+                await result
+            return
+        envelope = server_command(user_message) if self.app.settings["forward_unknown_commands"] else None
+        if envelope:
+            if not (self.app.transport and self.app.connected):
+                self.write_line(f"[bold yellow]{sys_msg('not_connected', action='run server commands')}[/bold yellow]")
+                return
+            await self.send_payload(envelope)
             return
         
-        # Use defaults if fields are empty
-        if not username:
-            username = "guest"
-        if not chat_name:
-            chat_name = "general"
-        if not password:
-            password = "default"
+        # Send message to server
+        self.sent_history.append(user_message)
+        await self.send_message(user_message)
+
+    def action_quit(self):
+        self.app.action_quit()
+
+    async def run_palette_command(self, spec: CommandSpec):
+        # Commands that can't run without arguments are put in the input to be finished off
+        if not spec.needs_args:
+            await self.submit_message(f"/{spec.name}")
+            return
+        self.set_draft(f"/{spec.name} ")
+        input_widget = self.query_one("#message_input")
+        if isinstance(input_widget, MultilineInput):
+            input_widget.move_cursor(input_widget.document.end)
+        else:
+            input_widget.cursor_position = len(input_widget.value)
+        input_widget.focus()
+
+    def show_invite(self):
+        # Show a join link for this room and copy it to the clipboard
+        uri = build_join_uri(self.app.server_url, self.chat_name)
+        self.write_line(f"[bold #87CEEB]Invite link:[/bold #87CEEB] {escape(uri)}")
+        if hasattr(self.app, "copy_to_clipboard"):
+            self.app.copy_to_clipboard(uri)
+            self.write_line("[bold #87CEEB]Copied to clipboard. The password is not included.[/bold #87CEEB]")
+
+    def copy_message(self, args: str):
+        # /copy [text|markdown|link|username] [username] copies the latest message, or someone's latest
+        parts = args.split()
+        mode = parts.pop(0).lower() if parts and parts[0].lower() in ("text", "markdown", "link", "username") else "text"
+        if len(parts) > 1:
+            self.write_line(f"[bold yellow]Usage: {escape('/copy [text|markdown|link|username] [username]')}[/bold yellow]")
+            return
+        if parts:
+            entry = next((entry for entry in reversed(self.entries) if entry.get("username", "").lower() == parts[0].lower()), None)
+        else:
+            entry = next((entry for entry in reversed(self.entries) if "username" in entry), None)
+        if entry is None:
+            self.write_line(f"[bold yellow]{sys_msg('nothing_to_copy')}[/bold yellow]")
+            return
+        if mode == "link":
+            if entry["id"] is None or not self.capabilities.supports("message_links"):
+                self.write_line(f"[bold yellow]{sys_msg('no_message_links')}[/bold yellow]")
+                return
+            text = build_message_link(self.app.server_url, self.chat_name, entry["id"])
+        elif mode == "markdown":
+            text = format_message_markdown(entry["username"], entry["content"], entry["sent_at"])
+        elif mode == "username":
+            text = entry["username"]
+        else:
+            text = entry["content"]
+        if not hasattr(self.app, "copy_to_clipboard"):
+            self.write_line(f"[bold yellow]{sys_msg('no_clipboard')}[/bold yellow]")
+            return
+        self.app.copy_to_clipboard(text)
+        self.write_line(f"[dim]{sys_msg('copied', what=mode if mode != 'text' else 'message')}[/dim]")
+
+    def handle_server_command(self, command: str):
+        # /server list | add <name> <url> [chat] [username] | edit <name> <url> [chat] [username] | remove <name>
+        parts = command.split()
+        profiles = self.app.settings["profiles"]
+        action = parts[1].lower() if len(parts) > 1 else "list"
+
+        if action == "list":
+            if not profiles:
+                self.write_line(f"[bold #87CEEB]No saved servers. Add one with {escape('/server add <name> <url> [chat] [username]')}[/bold #87CEEB]")
+                return
+            self.write_line("[bold #87CEEB]Saved servers:[/bold #87CEEB]")
+            for profile in profiles:
+                details = escape(profile["url"])
+                if profile["default_chat"]:
+                    details += f" chat: {escape(profile['default_chat'])}"
+                if profile["default_username"]:
+                    details += f" as {escape(profile['default_username'])}"
+                self.write_line(f"  {escape(profile['name'])} - {details}")
+            return
+
+        if action in ("add", "edit") and 4 <= len(parts) <= 6:
+            try:
+                profile = make_profile(*parts[2:])
+                if action == "add":
+                    add_profile(profiles, profile)
+                else:
+                    edit_profile(profiles, profile)
+            except ValueError as e:
+                self.write_line(f"[bold red]Could not save server: {escape(str(e))}[/bold red]")
+                return
+            save_settings(self.app.settings)
+            self.write_line(f"[bold #87CEEB]Saved server '{escape(profile['name'])}'.[/bold #87CEEB]")
+            return
+
+        if action == "remove" and len(parts) == 3:
+            if remove_profile(profiles, parts[2]):
+                save_settings(self.app.settings)
+                self.write_line(f"[bold #87CEEB]Removed server '{escape(parts[2])}'.[/bold #87CEEB]")
+            else:
+                self.write_line(f"[bold red]No server named '{escape(parts[2])}'.[/bold red]")
+            return
+
+        usage = "/server list | add <name> <url> [chat] [username] | edit <name> <url> [chat] [username] | remove <name>"
+        self.write_line(f"[bold yellow]Usage: {escape(usage)}[/bold yellow]")
+
+    async def handle_set_command(self, command: str):
+        # /set lists settings, /set <key> shows one, /set <key> <value> changes it
+        parts = command.split(maxsplit=2)
+        settings = self.app.settings
+
+        if len(parts) == 1:
+            self.write_line("[bold #87CEEB]Settings:[/bold #87CEEB]")
+            for key in SETTING_PARSERS:
+                self.write_line(f"  {key} = {escape(format_setting(settings[key]))}")
+            return
+
+        key = parts[1].lower()
+        if key not in SETTING_PARSERS:
+            valid = ", ".join(SETTING_PARSERS)
+            self.write_line(f"[bold red]Unknown setting '{escape(key)}'. Valid settings: {valid}[/bold red]")
+            return
+
+        if len(parts) == 2:
+            self.write_line(f"[bold #87CEEB]{key} = {escape(format_setting(settings[key]))}[/bold #87CEEB]")
+            return
+
+        try:
+            value = SETTING_PARSERS[key](parts[2].strip())
+        except ValueError as e:
+            self.write_line(f"[bold red]Invalid value for {key}: {escape(str(e))}[/bold red]")
+            return
+
+        settings[key] = value
+        save_settings(settings)
+        await self.apply_setting(key)
+        self.write_line(f"[bold #87CEEB]{key} set to {escape(format_setting(value))}[/bold #87CEEB]")
+
+    async def handle_config_command(self, args: str):
+        # /config export <path> writes a bundle; /config import <path> merge|replace reads one back
+        parts = args.split()
+        if len(parts) < 2 or parts[0].lower() not in ("export", "import"):
+            self.write_line(f"[bold yellow]Usage: {escape('/config export <path>')} or {escape('/config import <path> [merge|replace]')}[/bold yellow]")
+            return
+        action, path = parts[0].lower(), os.path.expanduser(parts[1])
+        if action == "export":
+            try:
+                with open(path, "w", encoding="utf-8") as f:
+                    json.dump(export_config(self.app.settings), f, indent=2)
+            except OSError as e:
+                self.write_line(f"[bold red]{sys_msg('config_export_failed', error=escape(str(e)))}[/bold red]")
+                return
+            self.write_line(f"[bold #87CEEB]{sys_msg('config_exported', path=escape(os.path.abspath(path)))}[/bold #87CEEB]")
+            return
+        mode = parts[2].lower() if len(parts) > 2 else ""
+        if mode not in ("merge", "replace"):
+            # Ask rather than guess, since replacing throws away settings the file doesn't have
+            self.write_line(f"[bold yellow]{sys_msg('config_import_mode', path=escape(parts[1]))}[/bold yellow]")
+            return
+        try:
+            with open(path, "r", encoding="utf-8") as f:
+                bundle = json.load(f)
+            settings, skipped = import_config(bundle, self.app.settings, mode == "replace")
+        except (OSError, ValueError) as e:
+            self.write_line(f"[bold red]{sys_msg('config_import_failed', error=escape(str(e)))}[/bold red]")
+            return
+        changed = [key for key in settings if settings[key] != self.app.settings[key]]
+        self.app.settings.clear()
+        self.app.settings.update(settings)
+        save_settings(self.app.settings)
+        for key in changed:
+            await self.apply_setting(key)
+        self.write_line(f"[bold #87CEEB]{sys_msg('config_imported', count=len(changed))}[/bold #87CEEB]")
+        if skipped:
+            self.write_line(f"[bold yellow]{sys_msg('config_skipped', keys=escape(', '.join(skipped)))}[/bold yellow]")
+
+    def handle_dnd_command(self, args: str):
+        # /dnd shows the state, /dnd on|off toggles it, /dnd schedule HH:MM-HH:MM|off sets quiet hours
+        settings = self.app.settings
+        parts = args.split()
+        if not parts:
+            state = "on" if settings["dnd"] else "off"
+            schedule = settings["dnd_schedule"] or "none"
+            active = " (notifications are currently silenced)" if self.app.dnd_active() else ""
+            self.write_line(f"[bold #87CEEB]Do not disturb: {state}, quiet hours: {escape(schedule)}{active}[/bold #87CEEB]")
+            return
+        try:
+            if parts[0].lower() == "schedule" and len(parts) == 2:
+                settings["dnd_schedule"] = parse_schedule_setting(parts[1])
+                schedule = settings["dnd_schedule"] or "none"
+                message = f"Quiet hours set to {escape(schedule)}"
+            elif len(parts) == 1:
+                settings["dnd"] = parse_bool_setting(parts[0])
+                message = f"Do not disturb {'on' if settings['dnd'] else 'off'}"
+            else:
+                raise ValueError("usage: /dnd on|off or /dnd schedule HH:MM-HH:MM|off")
+        except ValueError as e:
+            self.write_line(f"[bold red]{escape(str(e))}[/bold red]")
+            return
+        save_settings(settings)
+        if self.app.connected:
+            self.update_connected_header()
+        self.write_line(f"[bold #87CEEB]{message}[/bold #87CEEB]")
+
+    async def apply_setting(self, key: str):
+        # Push a changed setting into the live UI where it has a visible effect
+        if key in ("dnd", "dnd_schedule") and self.app.connected:
+            self.update_connected_header()
+        elif key == "enter_sends":
+            await self.refresh_message_input()
+            if not self.app.settings["enter_sends"]:
+                self.write_line("[bold #87CEEB]Enter now inserts a newline. Press Ctrl+Enter to send.[/bold #87CEEB]")
+        elif key == "sign_messages":
+            await self.announce_public_key()
+        elif key == "timestamp_style":
+            by_id = index_entries(self.entries)
+            for entry in self.entries:
+                if "sent_at" in entry:
+                    entry["markup"] = self.chat_markup(entry, by_id)
+            self.rerender_log()
+        elif key == "theme":
+            if self.app.settings["theme"] == "auto":
+                self.app.os_scheme = await asyncio.get_running_loop().run_in_executor(None, detect_os_color_scheme)
+            await self.apply_theme(pick_theme(None, self.app.settings["theme"], self.app.os_scheme))
+
+    async def apply_theme(self, name: str):
+        theme_color, background_color = resolve_theme(name)
+        await self.change_theme_color(theme_color)
+        await self.change_background_color(background_color)
+
+    async def follow_os_scheme(self):
+        # Only while the theme is "auto" and neither --theme nor the server has picked colors
+        if self.app.requested_theme or self.app.server_themed or self.app.settings["theme"] != "auto":
+            return
+        scheme = await asyncio.get_running_loop().run_in_executor(None, detect_os_color_scheme)
+        if scheme is None or scheme == self.app.os_scheme:
+            return
+        self.app.os_scheme = scheme
+        await self.apply_theme(pick_theme(None, "auto", scheme))
+
+    async def connect_to_server(self):
+        # Establish WebSocket connection to the backend
         
-        # Start the chat directly - no separate test connection to avoid duplicate join/leave notifications
+        self.joining = True
+        self.frame_buffer = ""
+        self.set_connection_state("connecting")
+        self.server_disconnected = False
+        self.capabilities = ServerCapabilities()
+        # Echoes from the old connection will never come
+        for entry in self.awaiting_echo:
+            self.update_send_state(entry, "error")
+        self.awaiting_echo = []
+        self.reset_roster()
+        self.custom_emoji = {}
         try:
-            self.app.start_chat(username, chat_name, password)
+            self.app.transport = await self.open_transport(
+                ping_interval=30,
+                ping_timeout=10,
+                close_timeout=10,
+                max_size=MAX_FRAME_SIZE,
+                max_queue=32,    # Max queued messages
+                **bind_options(self.app.bind_address)
+            )
+            
+            # Send authentication message; reconnects present the session token instead of the password
+            self.used_session_token = bool(self.reconnecting and self.session_token)
+            auth_message = build_join_payload(
+                self.username,
+                self.chat_name,
+                self.auth_method,
+                self.password,
+                self.session_token if self.used_session_token else None,
+            )
+            
+            await self.send_payload(auth_message)
+            
+            # Wait for join confirmation before considering connection complete. The deadline covers
+            # the whole handshake, so a server that sends other frames but never answers still times out
+            loop = asyncio.get_running_loop()
+            join_deadline = loop.time() + self.app.settings["join_timeout_seconds"]
+            try:
+                joined = False
+                backlog = []  # Messages the server replays before confirming our join
+                leftover = []  # Anything that arrived in the same frame after our join echo
+                while not joined:
+                    response = await asyncio.wait_for(self.app.transport.recv(), timeout=max(0, join_deadline - loop.time()))
+                    self.app.received.add(frame_size(response))
+                    for data in self.parse_frame(response):
+                        if joined:
+                            leftover.append(data)
+                        elif data.get("type") == "join" and data.get("username") == self.username:
+                            # Join successful - set connected state
+                            self.joining = False
+                            # A reconnect leaves the reader where they were instead of jumping to the bottom
+                            resumed = self.reconnecting
+                            self.reconnecting = False
+                            self.reconnect_attempts = 0
+                            # Short-lived token for the next reconnect; kept in memory only
+                            self.session_token = data.get("session_token") or None
+                            self.capabilities = ServerCapabilities(data.get("capabilities"))
+                            self.max_length = effective_max_length(data.get("max_message_length"))
+                            self.update_length_counter()
+                            self.stats.reset()
+                            self.app.connected = True
+                            self.set_connection_state("connected")
+                            self.update_connected_header()
+                            self.app.record_recent_room(self.chat_name)
+                            if resumed:
+                                stamp = time.strftime('%H:%M', time.localtime())
+                                scroll_end = self.follow_scroll()
+                                self.write_line(f"[dim]{glyphs.dash} {sys_msg('reconnected', time=stamp)} {glyphs.dash}[/dim]", kind="divider", scroll_end=scroll_end)
+                                self.write_backlog(backlog, scroll_end=scroll_end)
+                            else:
+                                self.write_backlog(backlog)
+                                self.write_line(f"[bold #87CEEB]{sys_msg('joined', chat=escape(self.chat_name))}[/bold #87CEEB]")
+                                self.call_after_refresh(self.scroll_to_saved_offset)
+                            joined = True
+                        elif data.get("type") == "roster":
+                            self.apply_roster_snapshot(data.get("users", []))
+                        elif data.get("type") == "custom_emoji":
+                            self.custom_emoji = merge_custom_emoji(data.get("shortcodes", {}))
+                        elif data.get("type") == "message":
+                            # Hold server messages during connection and write them in one go once joined
+                            backlog.append(data)
+                        elif data.get("type") in ("error", "auth_failed"):
+                            if self.used_session_token:
+                                raise SessionTokenRejected()
+                            error_message = data.get("message", "Connection failed")
+                            raise JoinRejected(error_message)
+
+            except asyncio.TimeoutError:
+                # Don't leave the socket half-open behind us
+                await self.close_half_open()
+                raise Exception("Server did not confirm join")
+            except Exception:
+                # Nor when the join was refused, or failed for any other reason
+                await self.close_half_open()
+                raise
+            
+            for data in leftover:
+                await self.handle_message(data)
+
+            # Start listening for messages after successful join
+            asyncio.create_task(self.listen_for_messages())
+            asyncio.create_task(self.monitor_health(self.app.transport))
+            await self.announce_public_key()
+            await self.flush_outbox()
+            await self.flush_pending_sends()
+            
+        except SessionTokenRejected:
+            # The token expired or was revoked, so rejoin the normal way with the password
+            self.session_token = None
+            self.write_line(f"[bold yellow]{sys_msg('session_expired')}[/bold yellow]")
+            await self.connect_to_server()
+        except websockets.exceptions.InvalidStatusCode as e:
+            if e.status_code == 403:
+                error_msg = "Server is currently disabled or unavailable"
+            else:
+                error_msg = f"Server rejected connection: HTTP {e.status_code}"
+            self.connection_failed(error_msg, fatal=classify_connect_error(e) in FATAL_CONNECT_ERRORS)
+        except OSError as e:
+            if is_bind_error(e, self.app.bind_address):
+                error_msg = f"Cannot bind to local address {self.app.bind_address}: {e.strerror}"
+            else:
+                error_msg = CONNECT_ERROR_MESSAGES.get(classify_connect_error(e), f"Network error: {str(e)}")
+            self.connection_failed(error_msg)
         except Exception as e:
-            # Connection failed, show error and reset
-            self.connecting = False
-            status_label.update(f"[red]Connection failed: {str(e)}[/red]")
-            self.app.notify(f"Could not connect to server: {str(e)}", severity="error")
+            if "server rejected WebSocket connection" in str(e) and "403" in str(e):
+                error_msg = "Server is currently disabled or unavailable"
+            else:
+                error_msg = CONNECT_ERROR_MESSAGES.get(classify_connect_error(e), f"Failed to connect to server: {e}")
+            self.connection_failed(error_msg, fatal=classify_connect_error(e) in FATAL_CONNECT_ERRORS)
 
+    async def open_transport(self, **options) -> Transport:
+        # Retry a failed DNS lookup a couple of times before giving up; other errors go straight up
+        for attempt in range(DNS_RETRIES + 1):
+            try:
+                url = self.app.websocket_url()
+                return await self.app.connect_transport(url, **tls_options(url), **options)
+            except OSError as e:
+                if classify_connect_error(e) != "dns" or attempt == DNS_RETRIES:
+                    raise
+                self.write_line(f"[bold yellow]{sys_msg('dns_retry', seconds=f'{DNS_RETRY_DELAY:.0f}')}[/bold yellow]")
+                await asyncio.sleep(DNS_RETRY_DELAY)
 
-    def action_quit(self):
-        self.app.exit()
+    async def close_half_open(self):
+        transport, self.app.transport = self.app.transport, None
+        if transport:
+            try:
+                await asyncio.wait_for(transport.close(), timeout=SHUTDOWN_TIMEOUT)
+            except Exception:
+                pass  # Closing is best effort; the server may never answer the close either
 
+    def connection_failed(self, error_msg: str, fatal: bool = False):
+        # fatal failures (a rejected join, HTTP 401/403) stop reconnecting instead of retrying
+        self.joining = False
+        error_msg = escape(error_msg)  # Often carries the server's own words
+        self.write_line(f"[bold red]{error_msg}[/bold red]")
+        self.app.alert(error_msg, severity="error")
+        pending, self.pending_sends = self.pending_sends, []
+        if self.reconnecting:
+            # Messages typed while joining wait in the outbox for the next attempt
+            for user_message, reply_to in pending:
+                self.queue_outbox({"content": user_message, "attempts": 0, "reply_to": reply_to})
+            self.mark_disconnected()
+            if fatal:
+                # The same details would only be turned down again; stay in the room so it can be read
+                self.reconnecting = False
+                self.reconnect_attempts = 0
+                self.write_line(f"[bold yellow]{sys_msg('no_auto_reconnect')}[/bold yellow]")
+            else:
+                # Keep retrying with backoff rather than throwing the user out of the room
+                self.schedule_reconnect(reconnect_delay(self.reconnect_attempts))
+                self.reconnect_attempts += 1
+        else:
+            # This screen is about to close, so lost messages are reported where they'll still be seen
+            for user_message, _reply_to in pending:
+                self.app.alert(sys_msg("join_failed_unsent", message=escape(reply_snippet(user_message))), severity="warning")
+            self.app.pop_screen()
 
-class ChatScreen(Screen):
-    # Main chat screen
-    
-    CSS = """
-    ChatScreen {
-        layout: vertical;
-        background: black;
-        color: white;
-    }
-    
-    #header {
-        dock: top;
-        height: 3;
-        background: black;
-        color: #87CEEB;
-        content-align: center middle;
-        text-style: bold;
-        padding: 1 0;
-    }
-    
-    #messages_container {
-        height: 1fr;
-        border: solid #87CEEB;
-        margin: 0;
-    }
-    
-    #messages {
-        height: 1fr;
-        scrollbar-gutter: stable;
-        border: none;
-        background: black;
-        color: white;
-        padding: 1;
-    }
-    
-    #input_container {
-        dock: bottom;
-        height: 5;
-        border: solid #87CEEB;
-        margin: 0 0 1 0;
-        padding: 1;
-        content-align: center middle;
-    }
-    
-    #message_input {
-        width: 1fr;
-        height: 3;
-        border: none;
-        background: black;
-        color: white;
-        content-align: center middle;
-    }
-    
-    #message_input:focus {
-        border: none;
-    }
-    """
-    
-    BINDINGS = [
-        Binding("ctrl+c", "quit", "Quit"),
-        Binding("ctrl+q", "quit", "Quit"),
-    ]
+    async def listen_for_messages(self):
+        # Listen for incoming messages from the server
+        transport = self.app.transport
+        closed = False
+        try:
+            async for message in transport:
+                self.app.received.add(frame_size(message))
+                for data in self.parse_frame(message):
+                    try:
+                        await self.handle_message(data)
+                    except Exception as e:
+                        self.write_line(f"[bold red]{sys_msg('processing_error', error=escape(str(e)))}[/bold red]")
+            # The iterator ends quietly when the connection closes
+            closed = True
+        except TransportClosed:
+            closed = True
+        except Exception as e:
+            self.write_line(f"[bold red]{sys_msg('receiving_error', error=escape(str(e)))}[/bold red]")
+        finally:
+            # After /go this screen is gone and the app's transport belongs to the next room
+            if self.is_mounted or self.app.transport is transport:
+                self.mark_disconnected()
+        if closed and self.is_mounted:
+            self.report_close(transport.close_code, transport.close_reason)
 
-    def __init__(self, username: str, chat_name: str, password: str):
-        super().__init__()
-        self.username = username
-        self.chat_name = chat_name
-        self.password = password
+    async def monitor_health(self, transport: Transport):
+        # Ping on our own schedule so a link that has gone quiet shows up before the keepalive gives up
+        self.health = HealthMonitor()
+        try:
+            while self.app.transport is transport:
+                await asyncio.sleep(HEALTH_PING_INTERVAL)
+                if self.app.transport is not transport:
+                    break
+                try:
+                    state = self.health.pong(await transport.ping(HEALTH_PONG_TIMEOUT))
+                    self.app.rtt_history.add(self.health.rtt)
+                    self.app.events.publish("latency", rtt=self.health.rtt)
+                except asyncio.TimeoutError:
+                    state = self.health.miss()
+                    self.app.rtt_history.gap()
+                if self.is_mounted:
+                    self.update_connected_header()
+                if state == "lost":
+                    # Closing hands over to the listener, which reports the drop and reconnects
+                    self.write_line(f"[bold yellow]{sys_msg('health_lost')}[/bold yellow]")
+                    await transport.close(code=1011, reason="Health check failed")
+                    break
+        except TransportClosed:
+            pass  # The listener already deals with closed connections
+
+    def report_close(self, code: Optional[int], reason: Optional[str]):
+        # Tell the user why the socket closed and decide whether to reconnect on our own
+        if self.server_disconnected:
+            return  # Already explained by the server's disconnect message
+        self.write_line(f"[bold yellow]{sys_msg('disconnected', reason=escape(describe_close(code, reason)))}[/bold yellow]", scroll_end=self.follow_scroll())
+        if code != 1000:
+            self.app.alert(sys_msg("connection_lost"), severity="warning")
+        if should_auto_reconnect(code):
+            if self.reconnect_timer is None:
+                self.reconnecting = True
+                self.schedule_reconnect(reconnect_delay(self.reconnect_attempts))
+                self.reconnect_attempts += 1
+        elif code not in (None, 1000):
+            self.write_line(f"[bold yellow]{sys_msg('no_auto_reconnect')}[/bold yellow]")
+
+    async def request_room_info(self):
+        if not (self.app.transport and self.app.connected):
+            self.write_line(f"[bold yellow]{sys_msg('not_connected', action='request room info')}[/bold yellow]")
+            return
+        await self.send_payload({"type": "room_info"})
+        if self.room_info_timer:
+            self.room_info_timer.stop()
+        self.room_info_timer = self.set_timer(ROOM_INFO_TIMEOUT, self.room_info_timed_out)
+
+    def room_info_timed_out(self):
+        self.room_info_timer = None
+        self.write_line(f"[bold yellow]{sys_msg('room_info_unavailable')}[/bold yellow]")
+
+    def set_connection_state(self, state: str):
+        # Drive the input's placeholder from the connection state. The field stays editable
+        # while disconnected so /reconnect and other local commands can still be typed.
+        self.connection_state = state
+        self.app.events.publish(state, chat=self.chat_name)
+        input_widget = self.query_one("#message_input")
+        if isinstance(input_widget, Input):
+            input_widget.placeholder = input_placeholder(state)
+        if state == "connected":
+            self.focus_input()
+
+    def schedule_reconnect(self, delay: float):
+        # Only one reconnect is ever pending; a newer request replaces the old one
+        self.cancel_reconnect()
+        self.reconnect_timer = self.set_timer(delay, self.reconnect)
+        self.write_line(f"[bold #87CEEB]{sys_msg('reconnect_scheduled', seconds=f'{delay:.0f}')}[/bold #87CEEB]", scroll_end=self.follow_scroll())
+
+    def cancel_reconnect(self) -> bool:
+        if self.reconnect_timer is None:
+            return False
+        self.reconnect_timer.stop()
+        self.reconnect_timer = None
+        return True
+
+    def reconnect(self):
+        self.cancel_reconnect()
+        if self.app.connected or self.joining or self.waiting_for_network:
+            return
+        self.reconnecting = True
+        self.connect_worker = self.run_worker(self.reconnect_when_online())
+
+    async def network_reachable(self) -> bool:
+        return await host_reachable(self.app.websocket_url(), self.app.bind_address)
+
+    async def reconnect_when_online(self):
+        # After sleep or while offline, attempts would only fail and stack up backoff. Wait for the
+        # host to answer instead, then try straight away with the backoff reset.
+        if not await self.network_reachable():
+            self.waiting_for_network = True
+            self.write_line(f"[bold yellow]{sys_msg('waiting_for_network')}[/bold yellow]", scroll_end=self.follow_scroll())
+            try:
+                await wait_until_reachable(self.network_reachable)
+            finally:
+                self.waiting_for_network = False
+            self.reconnect_attempts = 0
+        self.write_line(f"[bold #87CEEB]{sys_msg('reconnecting')}[/bold #87CEEB]")
+        self.query_one("#header").update(f"TERMCHAT - Connecting to '{self.chat_name}'...")
+        await self.connect_to_server()
+
+    async def cancel_connect(self) -> bool:
+        # Stop a connect or join that hasn't finished yet and drop its half-open socket
+        worker, self.connect_worker = self.connect_worker, None
+        if worker is None or worker.is_finished or not (self.joining or self.waiting_for_network):
+            return False
+        worker.cancel()
+        self.joining = False
+        self.waiting_for_network = False
+        await self.close_half_open()
+        self.mark_disconnected()
+        return True
+
+    async def handle_reconnect_command(self, command: str):
+        # /reconnect reconnects now, /reconnect cancel drops a scheduled or in-progress reconnect
+        if command.lower().split()[1:] == ["cancel"]:
+            self.reconnecting = False
+            self.reconnect_attempts = 0
+            cancelled = self.cancel_reconnect()
+            if await self.cancel_connect() or cancelled:
+                self.write_line(f"[bold #87CEEB]{sys_msg('reconnect_cancelled')}[/bold #87CEEB]")
+            else:
+                self.write_line(f"[bold yellow]{sys_msg('no_reconnect_scheduled')}[/bold yellow]")
+        elif self.app.connected:
+            self.write_line(f"[bold yellow]{sys_msg('already_connected')}[/bold yellow]")
+        else:
+            self.reconnect()
+
+    def mark_disconnected(self):
+        # Drop the dead socket so nothing keeps sending into it; safe to call more than once
+        if self.connection_state == "connected" and self.is_mounted:
+            self.write_line(f"[bold #87CEEB]{self.stats.summary()}[/bold #87CEEB]", scroll_end=self.follow_scroll())
+        self.app.connected = False
+        self.app.transport = None
+        self.app.rtt_history.gap()
+        self.health = None
+        self.custom_emoji = {}
+        if self.is_mounted:
+            self.set_connection_state("disconnected")
+            self.query_one("#header").update("TERMCHAT - Disconnected")
+
+    def reset_roster(self):
+        # After a (re)connect the old roster may hold people who left during the outage,
+        # so start over and rebuild from a server snapshot or, failing that, from join events
+        self.roster = {self.username}
+        self.statuses = {}
+        self.roster_updating = True
+        self.set_timer(ROSTER_SETTLE_SECONDS, self.finish_roster_update)
+
+    def presence_line(self, kind: str, text: str):
+        # Each join/leave is its own line in a small room; in a big one they're counted and summarised
+        self.presence.record(kind, time.monotonic())
+        if not summarize_presence(len(self.roster), self.app.settings["presence_summary_threshold"]):
+            self.write_line(f"[bold #87CEEB]{text}[/bold #87CEEB]")
+        elif self.presence_timer is None:
+            self.presence_timer = self.set_timer(PRESENCE_SUMMARY_DELAY, self.write_presence_summary)
+
+    def write_presence_summary(self):
+        self.presence_timer = None
+        joins, leaves = self.presence.counts(time.monotonic())
+        self.write_line(f"[bold #87CEEB]{sys_msg('presence_summary', count=len(self.roster), joins=joins, leaves=leaves)}[/bold #87CEEB]")
+
+    def apply_roster_snapshot(self, users: list):
+        # A full snapshot replaces whatever was rebuilt incrementally so far
+        self.roster = {str(user) for user in users if user}
+        for user in self.roster:
+            self.stats.see(user)
+        self.roster.add(self.username)
+        self.statuses = {name: text for name, text in self.statuses.items() if name in self.roster}
+        self.finish_roster_update()
+
+    def finish_roster_update(self):
+        if not self.roster_updating:
+            return
+        self.roster_updating = False
+        if self.app.connected and self.is_mounted:
+            self.update_connected_header()
+
+    def update_connected_header(self):
+        header = f"TERMCHAT - Connected to server:'{self.chat_name}'"
+        if self.roster_updating:
+            header += f" (updating roster{glyphs.ellipsis})"
+        if self.app.dnd_active():
+            header += "  [DND]"
+        if self.health:
+            color = HEALTH_COLORS[self.health.state]
+            header += f"  [{color}]{glyphs.indicator} {self.health.state}[/{color}]"
+            if self.health.rtt is not None:
+                header += f" ({self.health.rtt * 1000:.0f} ms)"
+        self.query_one("#header").update(header)
+
+    def show_recent_rooms(self):
+        recent = self.app.settings["recent_rooms"]
+        if not recent:
+            self.write_line("[bold #87CEEB]No recent rooms yet.[/bold #87CEEB]")
+            return
+        self.write_line("[bold #87CEEB]Recent rooms:[/bold #87CEEB]")
+        for entry in recent:
+            self.write_line(f"  {escape(describe_recent_room(entry))}")
+
+    def find_messages(self, args: str):
+        # /find <text> matches literally, /find -r <regex> as a regular expression
+        use_regex = False
+        parts = args.split(maxsplit=1)
+        if parts and parts[0] in ("-r", "--regex"):
+            use_regex = True
+            args = parts[1] if len(parts) > 1 else ""
+        if not args:
+            self.write_line(f"[bold yellow]Usage: {escape('/find [-r] <text>')}[/bold yellow]")
+            return
+        try:
+            pattern = search_pattern(args, use_regex)
+        except re.error as error:
+            self.write_line(f"[bold red]{sys_msg('bad_regex', error=escape(str(error)))}[/bold red]")
+            return
+        self.search_matches = search_entries(self.entries, pattern)
+        if not self.search_matches:
+            self.search_index = -1
+            self.write_line(f"[bold #87CEEB]{sys_msg('no_matches', query=escape(args))}[/bold #87CEEB]")
+            return
+        # Start from the newest match, since that's usually the one being looked for
+        self.search_index = len(self.search_matches)
+        self.search_step(-1)
+
+    def search_step(self, step: int):
+        if not self.search_matches:
+            self.write_line(f"[bold yellow]{sys_msg('no_search')}[/bold yellow]")
+            return
+        self.search_index = (self.search_index + step) % len(self.search_matches)
+        entry = self.search_matches[self.search_index]
+        self.scroll_to_entry(entry)
+        self.notify(sys_msg("search_match", index=self.search_index + 1, count=len(self.search_matches)), timeout=2)
+
+    def set_user_color(self, args: str):
+        # /color <username> <color> picks someone's color, /color <username> goes back to the assigned one
+        parts = args.split()
+        if not 1 <= len(parts) <= 2:
+            self.write_line(f"[bold yellow]Usage: {escape('/color <username> [#RRGGBB|name]')}[/bold yellow]")
+            return
+        username = parts[0].lstrip("@")
+        if username.lower() == "server":
+            self.write_line(f"[bold yellow]{sys_msg('server_color')}[/bold yellow]")
+            return
+        overrides = self.app.settings["color_overrides"]
+        if len(parts) == 1:
+            if overrides.pop(username.lower(), None) is None:
+                self.write_line(f"[bold yellow]{sys_msg('no_color_override', username=escape(username))}[/bold yellow]")
+                return
+            message = sys_msg("color_cleared", username=escape(username))
+        else:
+            color = parse_user_color(parts[1])
+            if color is None:
+                self.write_line(f"[bold red]{sys_msg('bad_color', color=escape(parts[1]))}[/bold red]")
+                return
+            overrides[username.lower()] = color
+            message = sys_msg("color_set", username=f"[{color}]{escape(username)}[/{color}]")
+        save_settings(self.app.settings)
+        by_id = index_entries(self.entries)
+        for entry in self.entries:
+            if entry["kind"] == "message" and entry["username"].lower() == username.lower():
+                entry["markup"] = self.chat_markup(entry, by_id)
+        self.rerender_log()
+        self.write_line(f"[bold #87CEEB]{message}[/bold #87CEEB]")
+
+    def show_seen(self, username: str):
+        if not username:
+            self.write_line(f"[bold yellow]Usage: {escape('/seen <username>')}[/bold yellow]")
+            return
+        last_active = self.stats.history.last_active
+        username = next((name for name in last_active if name.lower() == username.lower()), username)
+        then = last_active.get(username)
+        if then is None:
+            self.write_line(f"[bold #87CEEB]{sys_msg('not_seen', username=escape(username))}[/bold #87CEEB]")
+        else:
+            self.write_line(f"[bold #87CEEB]{sys_msg('last_seen', username=escape(username), ago=format_relative(time.time(), then))}[/bold #87CEEB]")
+
+    def show_whois(self, username: str):
+        if not username:
+            self.write_line(f"[bold yellow]Usage: {escape('/whois <username>')}[/bold yellow]")
+            return
+        # Match names case-insensitively, preferring someone who is here now
+        known = list(self.roster) + list(self.stats.history.first_seen)
+        username = next((name for name in known if name.lower() == username.lower()), username)
+        lines = format_whois(
+            username,
+            username in self.roster,
+            self.app.get_user_color(username) if username in known else "",
+            self.statuses.get(username, ""),
+            self.stats.history.first_seen.get(username),
+            self.stats.history.message_counts.get(username, 0),
+            time.time(),
+        )
+        for line in lines:
+            self.write_line(line)
+
+    def show_roster(self):
+        suffix = " (still updating)" if self.roster_updating else ""
+        self.write_line(f"[bold #87CEEB]{len(self.roster)} in '{escape(self.chat_name)}'{suffix}:[/bold #87CEEB]")
+        for name in sorted(self.roster, key=str.lower):
+            user_color = self.app.get_user_color(name)
+            line = f"  [{user_color}]{escape(name)}[/{user_color}]"
+            if is_potentially_confusable(name):
+                line += f" [yellow]{glyphs.warning} possible impersonation[/yellow]"
+            if self.statuses.get(name):
+                line += f" {glyphs.dash} {escape(expand_shortcodes(self.statuses[name], self.custom_emoji))}"
+            self.write_line(line)
+
+    async def report_user(self, args: str):
+        # /report <username> <reason> - goes to moderators only, never to the room
+        parts = args.split(maxsplit=1)
+        if len(parts) < 2:
+            self.write_line(f"[bold yellow]Usage: {escape('/report <username> <reason>')}[/bold yellow]")
+            return
+        target, reason = parts
+        if target.lower() == self.username.lower():
+            self.write_line("[bold yellow]You can't report yourself.[/bold yellow]")
+            return
+        if not (self.app.transport and self.app.connected):
+            self.write_line(f"[bold yellow]{sys_msg('not_connected', action='send report')}[/bold yellow]")
+            return
+        await self.send_payload({
+            "type": "report",
+            "target_username": target,
+            "message_id": None,
+            "reason": reason
+        })
+
+    async def create_poll(self, args: str):
+        parsed = parse_poll_command(args)
+        if parsed is None:
+            self.write_line(f"[bold yellow]Usage: {escape(POLL_USAGE)} (2 to {MAX_POLL_OPTIONS} options)[/bold yellow]")
+            return
+        if not (self.app.transport and self.app.connected):
+            self.write_line(f"[bold yellow]{sys_msg('not_connected', action='start a poll')}[/bold yellow]")
+            return
+        question, options = parsed
+        await self.send_payload({"type": "poll", "question": question, "options": options})
 
-        
-    def compose(self) -> ComposeResult:
-        yield Label(f"TERMCHAT - Connecting to '{self.chat_name}'...", id="header")
-        with Container(id="messages_container"):
-            yield RichLog(id="messages", highlight=True, markup=True)
-        with Container(id="input_container"):
-            yield Input(placeholder="Type your message here...", id="message_input")
+    async def vote(self, poll_id, option_index: int):
+        # From clicking an option on a poll card, or /vote
+        if poll_id not in self.polls:
+            self.write_line(f"[bold yellow]{sys_msg('poll_gone')}[/bold yellow]")
+            return
+        poll, entry = self.polls[poll_id]
+        if not 0 <= option_index < len(poll.options):
+            self.write_line(f"[bold yellow]{sys_msg('no_such_option', count=len(poll.options))}[/bold yellow]")
+            return
+        if not (self.app.transport and self.app.connected):
+            self.write_line(f"[bold yellow]{sys_msg('not_connected', action='vote')}[/bold yellow]")
+            return
+        if not poll.vote(option_index):
+            return
+        await self.send_payload({"type": "vote", "poll_id": poll_id, "option_index": option_index})
+        self.refresh_poll(poll, entry)
 
-    async def on_mount(self):
-        # Initialize the chat screen
-        # Start connection to server
-        await self.connect_to_server()
-        input_widget = self.query_one("#message_input")
-        input_widget.can_focus = True
-        input_widget.focus()
+    async def vote_command(self, args: str):
+        # /vote <option> votes on the newest poll
+        if not self.polls:
+            self.write_line(f"[bold yellow]{sys_msg('no_polls')}[/bold yellow]")
+            return
+        try:
+            option = int(args)
+        except ValueError:
+            self.write_line(f"[bold yellow]Usage: {escape('/vote <option number>')}[/bold yellow]")
+            return
+        await self.vote(next(reversed(self.polls)), option - 1)
 
-    async def on_input_submitted(self, event: Input.Submitted):
-        # Handle user message input
-        if event.input.id != "message_input":
+    def show_poll(self, data: dict):
+        poll_id, question, options = data.get("poll_id"), data.get("question"), data.get("options")
+        if poll_id is None or not isinstance(question, str) or not isinstance(options, list) or not options:
             return
-            
-        user_message = event.value.strip()
-        event.input.clear()
+        poll = Poll(poll_id, question, [str(option) for option in options[:MAX_POLL_OPTIONS]], data.get("votes"))
+        entry = self.write_line(poll.markup(), kind="poll", scroll_end=self.follow_scroll())
+        self.polls.pop(poll_id, None)
+        self.polls[poll_id] = (poll, entry)
 
-        if not user_message:
+    def update_poll(self, data: dict):
+        if data.get("poll_id") not in self.polls:
+            return  # A poll from before we joined, or one that has scrolled out of the log
+        poll, entry = self.polls[data["poll_id"]]
+        poll.update(data.get("votes"), data.get("your_vote"))
+        self.refresh_poll(poll, entry)
+
+    def refresh_poll(self, poll: Poll, entry: dict):
+        entry["markup"] = poll.markup()
+        if any(shown is entry for shown in self.entries) and self.is_mounted:
+            self.refresh_entry(entry)
+
+    async def set_status(self, text: str):
+        # An empty status clears it
+        if len(text) > MAX_STATUS_LENGTH:
+            self.write_line(f"[bold yellow]Status is too long (max {MAX_STATUS_LENGTH} characters).[/bold yellow]")
             return
-        
-        # Handle clear command
-        if user_message.lower() in ['/clear','/c']:
-            messages_log = self.query_one("#messages", RichLog)
-            messages_log.clear()
+        if not (self.app.transport and self.app.connected):
+            self.write_line(f"[bold yellow]{sys_msg('not_connected', action='set status')}[/bold yellow]")
             return
-            
-        # Handle quit commands
-        if user_message.lower() in ['/quit', '/exit', '/q']:
-            await self.app.action_quit()
+        await self.send_payload({"type": "status", "text": text})
+        if text:
+            self.write_line(f"[bold #87CEEB]Status set to: {escape(text)}[/bold #87CEEB]")
+        else:
+            self.write_line("[bold #87CEEB]Status cleared.[/bold #87CEEB]")
+
+    def write_backlog(self, backlog: list, scroll_end: Optional[bool] = None):
+        # A big backlog is written inside one batch update and scrolled once at the end,
+        # instead of re-laying out and auto-scrolling for every message
+        backlog = [data for data in backlog if not is_ignored(self.app.settings["ignored"], data.get("username"))]
+        if len(backlog) < BACKLOG_BATCH_THRESHOLD:
+            for data in backlog:
+                self.write_chat_data(data, scroll_end=scroll_end, live=False)
             return
-        
-        # Send message to server
-        await self.send_message(user_message)
+        with self.app.batch_update():
+            for data in backlog:
+                self.write_chat_data(data, scroll_end=False, live=False)
+        if scroll_end is not False:
+            self.query_one("#messages", RichLog).scroll_end(animate=False)
 
-    def action_quit(self):
-        self.app.action_quit()
+    def write_chat_data(self, data: dict, scroll_end: Optional[bool] = None, live: bool = True) -> dict:
+        # A chat message frame from the server, with its id and reply parent if it has them
+        return self.write_chat_message(
+            data.get("username", "Unknown"),
+            data.get("content", ""),
+            scroll_end=scroll_end,
+            message_id=data.get("id"),
+            reply_to=data.get("reply_to"),
+            signature=signature_state(data, self.trusted_keys(), self.seen_signatures, time.time() if live else None),
+        )
 
+    def write_chat_message(self, username: str, message: str, scroll_end: Optional[bool] = None, state: Optional[str] = None,
+                           message_id=None, reply_to=None, signature: Optional[str] = None) -> dict:
+        # Write a chat line, preceded by a divider when the sender changes or time has passed
+        now = time.time()
+        divider = divider_for(self.last_message, (username, now), self.app.settings["group_gap_minutes"])
+        group_start = starts_group(self.last_message, divider)
+        self.last_message = (username, now)
 
-    async def connect_to_server(self):
-        # Establish WebSocket connection to the backend
-        messages_log = self.query_one("#messages", RichLog)
-        
-        try:
-            # Create SSL context with proper settings
-            import ssl
-            import certifi
-            ssl_context = ssl.create_default_context(cafile=certifi.where())
-            
-            self.app.websocket = await websockets.connect(
-                self.app.server_url,
-                ssl=ssl_context,
-                ping_interval=30,
-                ping_timeout=10,
-                close_timeout=10,
-                max_size=2**20,  # 1MB max message size
-                max_queue=32     # Max queued messages
-            )
-            
-            # Send authentication message
-            auth_message = {
-                "type": "join",
-                "username": self.username,
-                "chatname": self.chat_name,
-                "password": self.password
-            }
-            
-            await self.app.websocket.send(json.dumps(auth_message))
-            
-            # Wait for join confirmation before considering connection complete
-            try:
-                while True:
-                    response = await asyncio.wait_for(self.app.websocket.recv(), timeout=10.0)
-                    data = json.loads(response)
-                    
-                    if data.get("type") == "join" and data.get("username") == self.username:
-                        # Join successful - set connected state
-                        self.app.connected = True
-                        self.query_one("#header").update(f"TERMCHAT - Connected to server:'{self.chat_name}'")
-                        messages_log.write(f"[bold #87CEEB]Successfully joined chat '{self.chat_name}'[/bold #87CEEB]")
-                        # Focus the input field after successful connection
-                        self.query_one("#message_input").focus()
-                        break
-                    elif data.get("type") == "message":
-                        # Handle server messages during connection
-                        username = data.get("username", "Unknown")
-                        message = data.get("content", "")
-                        
-                        if username == "Server":
-                            messages_log.write(f"[bold #87CEEB]Server:[/bold #87CEEB] {escape(message)}")
-                        else:
-                            user_color = self.app.get_user_color(username)
-                            messages_log.write(f"[{user_color}]\\[{escape(username)}]:[/{user_color}] {escape(message)}")
-                    elif data.get("type") == "error":
-                        error_message = data.get("message", "Connection failed")
-                        raise Exception(error_message)
-                        
-            except asyncio.TimeoutError:
-                raise Exception("Server response timeout - no join confirmation received")
-            
-            # Start listening for messages after successful join
-            asyncio.create_task(self.listen_for_messages())
-            
-        except websockets.exceptions.InvalidStatusCode as e:
-            if e.status_code == 403:
-                error_msg = "Server is currently disabled or unavailable"
-            else:
-                error_msg = f"Server rejected connection: HTTP {e.status_code}"
-            messages_log.write(f"[bold red]{error_msg}[/bold red]")
-            self.app.notify(error_msg, severity="error")
-            self.app.pop_screen()
-        except OSError as e:
-            if "Name or service not known" in str(e):
-                error_msg = "Cannot resolve server address"
-            elif "Connection refused" in str(e):
-                error_msg = "Server is not responding"
-            else:
-                error_msg = f"Network error: {str(e)}"
-            messages_log.write(f"[bold red]{error_msg}[/bold red]")
-            self.app.notify(error_msg, severity="error")
-            self.app.pop_screen()
-        except Exception as e:
-            if "server rejected WebSocket connection" in str(e) and "403" in str(e):
-                error_msg = "Server is currently disabled or unavailable"
+        if divider == "time":
+            self.write_line(f"[dim]{glyphs.dash} {time.strftime('%H:%M', time.localtime(now))} {glyphs.dash}[/dim]", kind="divider", scroll_end=scroll_end)
+        elif divider == "sender" and self.app.settings["sender_separators"]:
+            self.write_line("", kind="divider", scroll_end=scroll_end)
+
+        message = display_content(self.e2e_key, message)
+        # Kept so replies, threads, edits and /retry can find this message again
+        details = {"username": username, "content": message, "id": message_id, "reply_to": reply_to,
+                   "sent_at": now, "group_start": group_start, "edited_at": None, "signature": signature}
+        entry = self.write_line(self.chat_markup(details), kind="message", scroll_end=scroll_end, state=state)
+        entry.update(details)
+        if self.thread_view is not None and self.thread_view.belongs(entry):
+            self.thread_view.add_entry(entry)
+        return entry
+
+    def chat_markup(self, entry: dict, by_id: Optional[dict] = None) -> str:
+        # Built from the details write_chat_message keeps on the entry, so edits and setting changes can rebuild it.
+        # Passes over the whole log give an index_entries map so replies don't each rescan it
+        username, message = entry["username"], entry["content"]
+        if username == "Server":
+            markup = f"[bold #87CEEB]Server:[/bold #87CEEB] {escape(expand_shortcodes(message, self.custom_emoji))}"
+        else:
+            user_color = self.app.get_user_color(username)
+            # The name itself is left alone, so matching, /reply and /ignore still use the real one
+            warning = f"[yellow]{glyphs.warning}[/yellow]" if is_potentially_confusable(username) else ""
+            markup = f"[{user_color}]\\[{escape(username)}]{warning}:[/{user_color}] {self.render_message_body(message)}"
+        if entry["signature"] == "verified":
+            markup += f" [green]{glyphs.check}[/green]"
+        elif entry["signature"] == "unverified":
+            markup += f" [yellow]{glyphs.warning}[/yellow]"
+        if entry["edited_at"] is not None:
+            markup += f" [dim](edited {time.strftime('%H:%M', time.localtime(entry['edited_at']))})[/dim]"
+        markup = add_timestamp(markup, self.app.settings["timestamp_style"], entry["sent_at"], entry["group_start"])
+        if entry["reply_to"] is not None:
+            parent = by_id.get(entry["reply_to"]) if by_id is not None else find_entry(self.entries, entry["reply_to"])
+            markup = f"{format_reply_header(parent)}\n{markup}"
+        return markup
+
+    def apply_edit(self, data: dict):
+        # Edits of messages no longer (or never) in the log are ignored
+        edited_ms = data.get("edited_ms")
+        edited_at = edited_ms / 1000 if isinstance(edited_ms, (int, float)) and not isinstance(edited_ms, bool) else time.time()
+        content = data.get("new_content")
+        entry = edit_entry(self.entries, data.get("message_id"), display_content(self.e2e_key, content) if isinstance(content, str) else None, edited_at)
+        if entry is None:
+            return
+        # The old signature covered the old text, so it's checked again against the new one
+        signed = {"username": entry["username"], "content": content, "signature": data.get("signature"), "signed_at": data.get("signed_at")}
+        state = signature_state(signed, self.trusted_keys(), now=time.time())
+        if state is None and entry["signature"] is not None:
+            state = "unverified"  # Signed before, so an unsigned edit stands out
+        entry["signature"] = state
+        entry["markup"] = self.chat_markup(entry)
+        self.rerender_log()
+
+    def render_message_body(self, message: str) -> str:
+        # Shared by the chat log and the compose preview so the preview matches what others will see
+        rendered = []
+        for kind, content, _language in split_code_blocks(message):
+            if kind == "code":
+                rendered.append("\n" + render_code_block(content) + "\n")
             else:
-                error_msg = f"Failed to connect to server: {e}"
-            messages_log.write(f"[bold red]{error_msg}[/bold red]")
-            self.app.notify(error_msg, severity="error")
-            self.app.pop_screen()
+                rendered.append(parse_inline_markdown(expand_shortcodes(content, self.custom_emoji)))
+        return "".join(rendered).rstrip("\n")  # A leading block still starts on its own line
 
-    async def listen_for_messages(self):
-        # Listen for incoming messages from the server
+    def parse_frame(self, frame) -> list:
+        # Reassemble JSON that may be split across frames or packed several to a frame
+        if isinstance(frame, bytes):
+            frame = frame.decode("utf-8", errors="replace")
+        objects, invalid, self.frame_buffer = extract_json_objects(self.frame_buffer + frame)
+        if len(self.frame_buffer) > MAX_FRAME_SIZE:
+            # A JSON object that never ends would otherwise grow the buffer one frame at a time
+            self.write_line(f"[bold red]{sys_msg('incomplete_json_dropped', size=format_bytes(len(self.frame_buffer)))}[/bold red]")
+            self.frame_buffer = ""
+        for chunk in invalid:
+            self.write_line(f"[bold red]{sys_msg('invalid_json', data=escape(chunk[:100]))}[/bold red]")
+        for data in objects:
+            self.app.events.publish("server", data=mask_secrets(data))  # The join echo carries a session token
+        return objects
+
+    def write_line(self, markup: str, kind: str = "notice", scroll_end: Optional[bool] = None, state: Optional[str] = None) -> dict:
+        # Every line goes through self.entries so the log can be re-rendered. A notice identical
+        # to the one just before it bumps that line's counter instead of repeating it.
+        now = time.time()
+        last = self.entries[-1] if self.entries else None
+        if should_collapse(last, markup, kind, now, NOTICE_DEDUP_WINDOW):
+            last["count"] += 1
+            last["time"] = now
+            self.refresh_entry(last)
+            return last
+        entry = {"markup": markup, "kind": kind, "count": 1, "time": now}
+        if state:
+            entry["state"] = state  # Delivery state of our own optimistically shown message
+        self.entries.append(entry)
+        if len(self.entries) > MAX_LOG_ENTRIES + LOG_TRIM_BATCH:
+            # Re-rendered without the dropped entries, so the log never shows lines it no longer keeps
+            removed = self.entries[-MAX_LOG_ENTRIES]["line"]
+            del self.entries[:-MAX_LOG_ENTRIES]
+            self.rerender_log(removed_lines=removed)
+            return entry
         messages_log = self.query_one("#messages", RichLog)
-        
-        try:
-            async for message in self.app.websocket:
-                try:
-                    data = json.loads(message)
-                    await self.handle_message(data)
-                except json.JSONDecodeError:
-                    messages_log.write(f"[bold red]Received invalid JSON: {escape(message[:100])}...[/bold red]")
-                except Exception as e:
-                    messages_log.write(f"[bold red]Error processing message: {e}[/bold red]")
-        except websockets.exceptions.ConnectionClosed:
-            messages_log.write("[bold yellow]Connection to server lost.[/bold yellow]")
-            self.app.connected = False
-            self.query_one("#header").update("TERMCHAT - Disconnected")
-            self.app.notify("Connection lost", severity="warning")
-        except websockets.exceptions.ConnectionClosedError as e:
-            messages_log.write(f"[bold yellow]Connection closed: {e}[/bold yellow]")
-            self.app.connected = False
-            self.query_one("#header").update("TERMCHAT - Connection Closed")
-        except Exception as e:
-            messages_log.write(f"[bold red]Error receiving messages: {e}[/bold red]")
-            self.app.connected = False
+        entry["line"] = len(messages_log.lines)  # Where to scroll to show this entry
+        messages_log.write(render_entry(entry), scroll_end=scroll_end)
+        return entry
+
+    def follow_scroll(self) -> Optional[bool]:
+        # Keep following new lines at the bottom, but don't pull someone back down who scrolled up
+        return None if self.log_pinned() else False
+
+    def log_pinned(self) -> bool:
+        messages_log = self.query_one("#messages", RichLog)
+        return is_pinned(
+            messages_log.scroll_y,
+            messages_log.virtual_size.height,
+            messages_log.scrollable_content_region.height,
+            self.app.settings["scroll_tolerance_lines"],
+        )
+
+    def scroll_to_entry(self, entry: Optional[dict]):
+        if entry is None:
+            return
+        if not any(shown is entry for shown in self.entries):
+            self.write_line(f"[bold yellow]{sys_msg('message_gone')}[/bold yellow]")
+            return
+        self.query_one("#messages", RichLog).scroll_to(y=entry["line"], animate=False)
+
+    def rerender_log(self, removed_lines: int = 0):
+        # Rewriting the log must not move someone who has scrolled up to read history;
+        # removed_lines is how many lines went from the top, so the same text stays in view
+        messages_log = self.query_one("#messages", RichLog)
+        at_end = self.log_pinned()
+        scroll_y = max(0, messages_log.scroll_y - removed_lines)
+        with self.app.batch_update():
+            messages_log.clear()
+            for entry in self.entries:
+                entry["line"] = len(messages_log.lines)
+                messages_log.write(render_entry(entry), scroll_end=False)
+        if at_end:
+            messages_log.scroll_end(animate=False)
+        else:
+            messages_log.scroll_to(y=scroll_y, animate=False)
+
+    def refresh_entry(self, entry: dict):
+        # RichLog has no public way to replace lines, so the whole log is rewritten, in one batch
+        if any(shown is entry for shown in self.entries):
+            self.rerender_log()
+
+    def clear_log(self, undoable: bool = False):
+        if undoable and self.entries:
+            self.undo_buffer.store(self.entries, self.last_message, time.monotonic())
+        self.entries = []
+        self.last_message = None
+        self.query_one("#messages", RichLog).clear()
+
+    def handle_clear_command(self, command: str):
+        # /clear asks for a repeat to confirm, /clear! clears straight away
+        now = time.monotonic()
+        if not command.split()[0].endswith("!") and now > self.clear_confirm_until:
+            self.clear_confirm_until = now + CLEAR_CONFIRM_WINDOW
+            self.write_line(f"[bold yellow]{sys_msg('clear_confirm', seconds=f'{CLEAR_CONFIRM_WINDOW:.0f}')}[/bold yellow]")
+            return
+        self.clear_confirm_until = 0.0
+        self.clear_log(undoable=True)
+        self.write_line(f"[dim]{sys_msg('cleared', seconds=f'{UNDO_WINDOW:.0f}')}[/dim]")
+
+    def ignore_users(self, args: str):
+        usernames = [name.lstrip("@") for name in args.split() if name.lstrip("@")]
+        if not usernames:
+            self.write_line(f"[bold yellow]Usage: {escape('/ignore <username> [username...]')}[/bold yellow]")
+            return
+        if any(name.lower() == self.username.lower() for name in usernames):
+            self.write_line(f"[bold yellow]{sys_msg('ignore_self')}[/bold yellow]")
+            usernames = [name for name in usernames if name.lower() != self.username.lower()]
+        added = add_ignores(self.app.settings["ignored"], usernames)
+        if added:
+            save_settings(self.app.settings)
+            self.write_line(f"[bold #87CEEB]{sys_msg('ignored', usernames=escape(', '.join(added)))}[/bold #87CEEB]")
+        elif usernames:
+            self.write_line(f"[bold #87CEEB]{sys_msg('already_ignored')}[/bold #87CEEB]")
+
+    def unignore_users(self, args: str):
+        usernames = [name.lstrip("@") for name in args.split() if name.lstrip("@")]
+        if not usernames:
+            self.write_line(f"[bold yellow]Usage: {escape('/unignore <username> [username...]')}[/bold yellow]")
+            return
+        removed = remove_ignores(self.app.settings["ignored"], usernames)
+        if not removed:
+            self.write_line(f"[bold yellow]{sys_msg('not_ignored')}[/bold yellow]")
+            return
+        save_settings(self.app.settings)
+        self.write_line(f"[bold #87CEEB]{sys_msg('unignored', usernames=escape(', '.join(removed)))}[/bold #87CEEB]")
+
+    def show_ignores(self):
+        ignored = self.app.settings["ignored"]
+        if not ignored:
+            self.write_line(f"[bold #87CEEB]{sys_msg('no_ignores')}[/bold #87CEEB]")
+            return
+        self.write_line(f"[bold #87CEEB]Ignored ({len(ignored)}):[/bold #87CEEB]")
+        for name in sorted(ignored, key=str.lower):
+            self.write_line(f"  {escape(name)}")
+
+    def clear_ignores(self):
+        # Asks for a repeat to confirm, like /clear
+        if not self.app.settings["ignored"]:
+            self.write_line(f"[bold #87CEEB]{sys_msg('no_ignores')}[/bold #87CEEB]")
+            return
+        now = time.monotonic()
+        if now > self.clear_ignores_until:
+            self.clear_ignores_until = now + CLEAR_CONFIRM_WINDOW
+            self.write_line(f"[bold yellow]{sys_msg('clear_ignores_confirm', count=len(self.app.settings['ignored']), seconds=f'{CLEAR_CONFIRM_WINDOW:.0f}')}[/bold yellow]")
+            return
+        self.clear_ignores_until = 0.0
+        self.app.settings["ignored"] = []
+        save_settings(self.app.settings)
+        self.write_line(f"[bold #87CEEB]{sys_msg('ignores_cleared')}[/bold #87CEEB]")
+
+    def undo_clear(self):
+        saved = self.undo_buffer.take(time.monotonic())
+        if saved is None:
+            self.write_line(f"[bold yellow]{sys_msg('nothing_to_undo')}[/bold yellow]")
+            return
+        # Anything written since the clear stays, after the restored history
+        entries, self.last_message = saved
+        self.entries = (entries + self.entries)[-MAX_LOG_ENTRIES:]
+        self.rerender_log()
 
     async def handle_message(self, data):
         # Handle different types of messages from the server
-        messages_log = self.query_one("#messages", RichLog)
         message_type = data.get("type", "")
         
         if message_type == "message":
-            username = data.get("username", "Unknown")
-            message = data.get("content", "")
-            
+            if is_ignored(self.app.settings["ignored"], data.get("username")):
+                return
+            echoed = self.take_echo(data) if data.get("username") == self.username else None
+            if echoed:
+                echoed["id"] = data.get("id")
+                self.update_send_state(echoed, "echo")
+                return  # Already shown when it was sent
             # Display messages with proper formatting - show ALL messages including own
-            if username == "Server":
-                messages_log.write(f"[bold #87CEEB]Server:[/bold #87CEEB] {escape(message)}")
-            else:
-                user_color = self.app.get_user_color(username)
-                messages_log.write(f"[{user_color}]\\[{escape(username)}]:[/{user_color}] {escape(message)}")
+            self.write_chat_data(data)
+            if data.get("username") != self.username:
+                self.stats.count_message(data.get("username", "Unknown"))
         
         elif message_type == "join":
             username = data.get("username", "Unknown")
+            self.stats.activity(username)
             # Handle other users joining - server doesn't send join notifications back to joining user
             if username and username != self.username:
-                messages_log.write(f"[bold #87CEEB]A wild {escape(username)} has appeared.[/bold #87CEEB]")
+                self.roster.add(username)
+                self.presence_line("join", sys_msg('user_joined', username=escape(username)))
         
         elif message_type == "leave":
             username = data.get("username", "Unknown") 
+            self.stats.activity(username)
             # Show leave notifications for all users
             if username and username != self.username:
-                messages_log.write(f"[bold #87CEEB]{escape(username)} has left the chat.[/bold #87CEEB]")
+                self.roster.discard(username)
+                self.statuses.pop(username, None)
+                self.presence_line("leave", sys_msg('user_left', username=escape(username)))
+
+        elif message_type == "roster":
+            self.apply_roster_snapshot(data.get("users", []))
+
+        elif message_type == "ack" and data.get("for") == "report":
+            if data.get("ok", True):
+                self.write_line(f"[bold #87CEEB]{sys_msg('report_submitted')}[/bold #87CEEB]")
+            else:
+                reason = data.get("message", "The server rejected the report")
+                self.write_line(f"[bold red]{sys_msg('report_failed', reason=escape(str(reason)))}[/bold red]")
+
+        elif message_type == "status":
+            username = data.get("username")
+            text = str(data.get("text", ""))[:MAX_STATUS_LENGTH]
+            if username:
+                self.stats.activity(username)
+                if text:
+                    self.statuses[username] = text
+                else:
+                    self.statuses.pop(username, None)
+
+        elif message_type == "custom_emoji":
+            self.custom_emoji = merge_custom_emoji(data.get("shortcodes", {}))
         
         elif message_type == "colourshift":
             # Handle theme color change
             new_color = data.get("color", "#87CEEB")
+            self.app.server_themed = True
             await self.change_theme_color(new_color)
             # messages_log.write(f"[bold {new_color}]Theme color changed to {new_color}[/bold {new_color}]")
         
         elif message_type == "bgshift":
-            messages_log = self.query_one("#messages", RichLog)
-            messages_log.clear()
+            self.clear_log()
             bg_color = data.get("color", "#000000")
+            self.app.server_themed = True
             await self.change_background_color(bg_color)
 
         elif message_type == "chatclear":
-            messages_log = self.query_one("#messages", RichLog)
-            messages_log.clear()
+            # The server's clear is authoritative, but can still be undone locally
+            self.clear_log(undoable=True)
+
+        elif message_type == "slowmode":
+            seconds = data.get("seconds", 0)
+            self.slowmode = float(seconds) if isinstance(seconds, (int, float)) and seconds > 0 else 0.0
+            if self.slowmode:
+                self.write_line(f"[bold #87CEEB]{sys_msg('slowmode_on', seconds=f'{self.slowmode:g}')}[/bold #87CEEB]")
+            else:
+                self.next_send_at = self.penalty_until  # A rate-limit backoff still applies
+                self.update_cooldown()
+                self.write_line(f"[bold #87CEEB]{sys_msg('slowmode_off')}[/bold #87CEEB]")
+
+        elif message_type == "broadcast":
+            content = data.get("content")
+            now = time.time()
+            expires_at = broadcast_expiry(data, now)
+            if isinstance(content, str) and content and expires_at > now:
+                self.broadcasts.add(content, expires_at)
+                self.refresh_broadcasts()
+
+        elif message_type == "edit":
+            self.apply_edit(data)
+
+        elif message_type == "poll":
+            self.show_poll(data)
+
+        elif message_type == "poll_update":
+            self.update_poll(data)
+
+        elif message_type == "public_key":
+            username, key = data.get("username"), data.get("key")
+            if isinstance(username, str) and isinstance(key, str) and username != self.username:
+                # Announcing a key never makes it trusted; the server could announce anything in anyone's name
+                trusted = self.app.settings["trusted_keys"].get(username)
+                if trusted is not None and trusted != key:
+                    self.write_line(f"[bold yellow]{sys_msg('key_changed', username=escape(username), check=glyphs.check)}[/bold yellow]")
+                elif trusted is None and self.announced_keys.get(username) != key:
+                    self.write_line(f"[dim]{sys_msg('key_announced', username=escape(username))}[/dim]")
+                self.announced_keys[username] = key
+
+        elif message_type == "rate_limited":
+            # A one-off penalty on top of any slow mode: hold sends until it runs out
+            wait = parse_retry_after(data)
+            self.penalty_until = time.monotonic() + wait
+            self.next_send_at = max(self.next_send_at, self.penalty_until)
+            self.update_cooldown()
+            self.write_line(f"[bold yellow]{sys_msg('rate_limited', seconds=math.ceil(wait))}[/bold yellow]")
+
+        elif message_type == "room_info":
+            if self.room_info_timer:
+                self.room_info_timer.stop()
+                self.room_info_timer = None
+            self.write_line(f"[bold #87CEEB]Room info for '{escape(self.chat_name)}':[/bold #87CEEB]")
+            for line in format_room_info(data):
+                self.write_line(f"  {escape(line)}")
+
+        elif message_type == "disconnect":
+            # Planned disconnect (e.g. maintenance), optionally with a server-requested reconnect delay
+            reason = data.get("reason", "Disconnected by server")
+            self.write_line(f"[bold yellow]{sys_msg('disconnected', reason=escape(str(reason)))}[/bold yellow]")
+            self.server_disconnected = True
+            reconnect_after_ms = data.get("reconnect_after_ms")
+            if isinstance(reconnect_after_ms, (int, float)) and reconnect_after_ms >= 0:
+                self.schedule_reconnect(reconnect_after_ms / 1000)
+            if self.app.transport:
+                await self.app.transport.close()
 
         elif message_type == "kicked":
             kicked_message = data.get("message", "You have been kicked :)")
-            messages_log = self.query_one("#messages", RichLog)
-            messages_log.clear()
-            messages_log.write(f"[bold #FF0000]{kicked_message}[/bold #FF0000]")
+            self.clear_log()
+            self.write_line(f"[bold #FF0000]{kicked_message}[/bold #FF0000]")
             await asyncio.sleep(5)
             await self.app.action_quit()
             return
         
+        elif message_type == "command_result":
+            name, message = data.get("name"), data.get("message")
+            style = "bold green" if data.get("success") is True else "bold red"
+            text = f"/{name}: {message}" if isinstance(name, str) and name else str(message or "")
+            if text:
+                self.write_line(f"[{style}]{sys_msg('command_result', text=escape(text))}[/{style}]")
+
         elif message_type == "error":
             error_message = data.get("message", "Unknown error")
-            messages_log.write(f"[bold red]Error: {escape(error_message)}[/bold red]")
+            self.write_line(f"[bold red]{sys_msg('server_error', message=escape(error_message))}[/bold red]")
             # If connection failed, go back to connection screen
             if not self.app.connected:
-                self.app.notify(f"Connection failed: {error_message}", severity="error")
+                self.app.alert(sys_msg("connection_failed", message=escape(error_message)), severity="error")
                 self.app.pop_screen()  # Return to connection screen
         
         elif message_type == "auth_failed":
             error_message = data.get("message", "Authentication failed")
-            messages_log.write(f"[bold red]Authentication failed: {escape(error_message)}[/bold red]")
-            self.app.notify(f"Authentication failed: {error_message}", severity="error")
+            self.write_line(f"[bold red]{sys_msg('auth_failed', message=escape(error_message))}[/bold red]")
+            self.app.alert(sys_msg("auth_failed", message=escape(error_message)), severity="error")
             # Go back to connection screen
             self.app.pop_screen()
 
-    async def send_message(self, user_message: str):
+    async def send_payload(self, payload: dict):
+        # Single place where frames go out, so traffic stats see everything we send
+        frame = json.dumps(payload)
+        await self.app.transport.send(frame)
+        self.app.events.publish("sent", data=mask_secrets(payload))  # Subscribers never see the password or token
+        self.app.sent.add(frame_size(frame))
+
+    def diagnostics(self) -> list:
+        app = self.app
+        return [
+            f"Server: {app.server_url}",
+            f"Connected: {'yes' if app.connected else 'no'} ({self.connection_state})",
+            f"Sent: {format_bytes(app.sent.total)} ({format_bytes(app.sent.rate())}/s over {app.sent.window:.0f}s)",
+            f"Received: {format_bytes(app.received.total)} ({format_bytes(app.received.rate())}/s over {app.received.window:.0f}s)",
+            f"Messages this session: {self.stats.sent} sent, {self.stats.received} received, {len(self.stats.users)} users seen",
+        ] + self.latency_lines()
+
+    def latency_lines(self) -> list:
+        samples = self.app.rtt_history.samples
+        values = [rtt for _, rtt in samples if rtt is not None]
+        if not values:
+            return []
+        return [
+            f"Latency (last {len(samples)} pings, gaps are drops): {format_sparkline(sparkline_points(samples))}",
+            f"  fastest {min(values) * 1000:.0f} ms, slowest {max(values) * 1000:.0f} ms, latest {values[-1] * 1000:.0f} ms",
+        ]
+
+    def show_diagnostics(self):
+        self.write_line("[bold #87CEEB]Diagnostics:[/bold #87CEEB]")
+        for line in self.diagnostics():
+            self.write_line(f"  {escape(line)}")
+
+    def handle_diag_command(self, args: str):
+        # /diag shows the summary, /diag export [path] writes the full report to a file
+        parts = args.split(maxsplit=1)
+        if not parts:
+            self.show_diagnostics()
+            return
+        if parts[0].lower() != "export":
+            self.write_line(f"[bold yellow]Usage: {escape('/diag [export [path]]')}[/bold yellow]")
+            return
+        path = os.path.expanduser(parts[1]) if len(parts) > 1 else f"termchat-diag-{time.strftime('%Y%m%d-%H%M%S')}.txt"
+        report = format_diagnostic_report(self.diagnostics(), self.app.settings, list(self.app.events.recent))
+        try:
+            with open(path, "w", encoding="utf-8") as f:
+                f.write(report)
+        except OSError as e:
+            self.write_line(f"[bold red]{sys_msg('diag_export_failed', error=escape(str(e)))}[/bold red]")
+            return
+        self.write_line(f"[bold #87CEEB]{sys_msg('diag_exported', path=escape(os.path.abspath(path)))}[/bold #87CEEB]")
+
+    async def flush_pending_sends(self):
+        # Send anything typed while the join handshake was still in progress, in order
+        pending, self.pending_sends = self.pending_sends, []
+        for user_message, reply_to in pending:
+            await self.send_message(user_message, reply_to)
+
+    async def flush_outbox(self):
+        # Retry each queued message once after reconnecting. One that fails again leaves the outbox
+        # and stays in the chat as a failed line, which /retry can send again
+        for item in list(self.outbox):
+            item["attempts"] += 1
+            try:
+                await self.transmit(item["content"], entry=item.get("entry"), reply_to=item.get("reply_to"))
+            except Exception:
+                entry = item.get("entry")
+                if entry is None:
+                    # Lost before it was ever shown, so it needs a line of its own to be retried from
+                    entry = self.write_chat_message(self.username, item["content"], state="pending", reply_to=item.get("reply_to"))
+                self.update_send_state(entry, "error")
+                self.write_line(f"[bold red]{sys_msg('send_still_failed', message=escape(item['content'][:50]))}[/bold red]")
+            self.outbox.remove(item)
+        self.refresh_outbox()
+
+    def queue_outbox(self, item: dict):
+        self.outbox.append(item)
+        self.refresh_outbox()
+
+    def refresh_outbox(self):
+        # The bar above the input appears whenever something is waiting to be sent
+        self.app.events.publish("outbox", size=len(self.outbox))
+        if not self.is_mounted:
+            return
+        bar = self.query_one("#outbox_bar", Static)
+        if not self.outbox:
+            bar.remove_class("open")
+            return
+        count = len(self.outbox)
+        bar.update(
+            f"{sys_msg('outbox_pending', count=count, s='' if count == 1 else 's')}  "
+            f"[@click=screen.retry_outbox][b]Retry all[/b][/]  [@click=screen.discard_outbox][b]Discard all[/b][/]"
+        )
+        bar.add_class("open")
+
+    async def action_retry_outbox(self):
+        await self.retry_outbox()
+
+    def action_discard_outbox(self):
+        self.discard_outbox()
+
+    async def retry_outbox(self):
+        # Everything queued, in order and however often it has failed; stops at the first failure
+        # since the rest would only hit the same dead connection
+        if not self.outbox:
+            self.write_line(f"[bold yellow]{sys_msg('outbox_empty')}[/bold yellow]")
+            return
+        if self.joining or not (self.app.transport and self.app.connected):
+            self.write_line(f"[bold yellow]{sys_msg('not_connected', action='retry queued messages')}[/bold yellow]")
+            return
+        for item in list(self.outbox):
+            item["attempts"] += 1
+            try:
+                await self.transmit(item["content"], entry=item.get("entry"), reply_to=item.get("reply_to"))
+            except Exception as e:
+                if item.get("entry"):
+                    self.update_send_state(item["entry"], "error")
+                self.write_line(f"[bold red]{sys_msg('send_error', error=escape(str(e)))}[/bold red]")
+                if isinstance(e, TransportClosed):
+                    self.mark_disconnected()
+                break
+            self.outbox.remove(item)
+        self.refresh_outbox()
+
+    def discard_outbox(self):
+        if not self.outbox:
+            self.write_line(f"[bold yellow]{sys_msg('outbox_empty')}[/bold yellow]")
+            return
+        count = len(self.outbox)
+        for item in self.outbox:
+            if item.get("entry"):
+                self.update_send_state(item["entry"], "error")  # Its line stays, marked as not sent
+        self.outbox = []
+        self.refresh_outbox()
+        self.write_line(f"[bold #87CEEB]{sys_msg('outbox_discarded', count=count, s='' if count == 1 else 's')}[/bold #87CEEB]")
+
+    async def handle_outbox_command(self, args: str):
+        # /outbox lists what's queued, /outbox retry sends it all now, /outbox discard drops it
+        action = args.lower()
+        if action == "retry":
+            await self.retry_outbox()
+        elif action == "discard":
+            self.discard_outbox()
+        elif action:
+            self.write_line(f"[bold yellow]Usage: {escape('/outbox [retry|discard]')}[/bold yellow]")
+        elif not self.outbox:
+            self.write_line(f"[bold #87CEEB]{sys_msg('outbox_empty')}[/bold #87CEEB]")
+        else:
+            self.write_line(f"[bold #87CEEB]Queued messages ({len(self.outbox)}):[/bold #87CEEB]")
+            for item in self.outbox:
+                self.write_line(f"  {escape(reply_snippet(item['content']))}")
+
+    async def transmit(self, user_message: str, entry: Optional[dict] = None, reply_to=None):
+        # Put a single chat message on the wire; exceptions are left to the caller.
+        # A retried message passes its existing log entry so the same line goes back to pending.
+        content = encrypt_content(self.e2e_key, user_message) if self.e2e_key else user_message
+        message_data = {
+            "type": "message",
+            "content": content
+        }
+        if reply_to is not None:
+            message_data["reply_to"] = reply_to
+        if self.app.settings["sign_messages"]:
+            signed_at = int(time.time() * 1000)
+            message_data["signed_at"] = signed_at
+            message_data["signature"] = sign_message(self.get_signing_key(), self.username, content, signed_at)
+        await self.send_payload(message_data)
+        self.stats.sent += 1
+        cooldown = effective_cooldown(self.slowmode, self.penalty_until - time.monotonic())
+        if cooldown:
+            self.next_send_at = time.monotonic() + cooldown
+            self.update_cooldown()
+        if entry is None and self.app.settings["optimistic_send"]:
+            # Show it now as pending and drop the server's echo when it arrives
+            entry = self.write_chat_message(self.username, content, state="pending", reply_to=reply_to,
+                                            signature=signature_state({**message_data, "username": self.username}, self.trusted_keys()))
+        if entry is not None:
+            self.update_send_state(entry, "retry")
+            entry["content"] = user_message  # Kept for /retry
+            entry["wire"] = content
+            if not any(waiting is entry for waiting in self.awaiting_echo):
+                self.awaiting_echo.append(entry)
+            if entry.get("timer"):
+                entry["timer"].stop()
+            entry["timer"] = self.set_timer(CONFIRM_TIMEOUT, lambda: self.update_send_state(entry, "timeout"))
+
+    def latest_message_from(self, username: str) -> Optional[dict]:
+        # Newest message from someone (case-insensitively) that the server gave an id
+        return next(
+            (entry for entry in reversed(self.entries)
+             if entry.get("id") is not None and entry["username"].lower() == username.lower()),
+            None,
+        )
+
+    async def reply_to_user(self, args: str):
+        # /reply <username> <message> answers their most recent message
+        parts = args.split(maxsplit=1)
+        if len(parts) < 2:
+            self.write_line(f"[bold yellow]Usage: {escape('/reply <username> <message>')}[/bold yellow]")
+            return
+        parent = self.latest_message_from(parts[0])
+        if parent is None:
+            self.write_line(f"[bold yellow]{sys_msg('no_message_to_reply', username=escape(parts[0]))}[/bold yellow]")
+            return
+        await self.send_message(parts[1], reply_to=parent["id"])
+
+    def open_thread(self, username: str):
+        # /thread shows the thread of the latest reply, /thread <username> the one around their last message
+        if username:
+            start = self.latest_message_from(username)
+        else:
+            start = next((entry for entry in reversed(self.entries) if entry.get("reply_to") is not None), None)
+        if start is None:
+            self.write_line(f"[bold yellow]{sys_msg('no_thread')}[/bold yellow]")
+            return
+        root = thread_root(self.entries, start)
+        if root.get("id") is None:
+            thread = [root]
+        else:
+            thread = thread_entries(self.entries, root["id"])
+        self.thread_view = ThreadView(thread, partial(self.send_in_thread, root.get("id")))
+        self.app.push_screen(self.thread_view, self.close_thread)
+
+    def close_thread(self, entry: Optional[dict]):
+        self.thread_view = None
+        self.scroll_to_entry(entry)
+        self.focus_input()
+
+    async def send_in_thread(self, root_id, value: str):
+        # The thread composer's messages all answer the root, however deep the thread goes
+        if self.app.settings["normalize_outgoing"]:
+            user_message = normalize_message(value) or ""
+        else:
+            user_message = value.strip()
+        if user_message:
+            self.sent_history.append(user_message)
+            await self.send_message(user_message, reply_to=root_id)
+
+    def get_signing_key(self) -> Ed25519PrivateKey:
+        if self.signing_key is None:
+            self.signing_key = load_or_create_signing_key(signing_key_path())
+        return self.signing_key
+
+    def trusted_keys(self) -> dict:
+        # Keys /trust saved, plus our own so our echoes verify too
+        keys = dict(self.app.settings["trusted_keys"])
+        if self.signing_key is not None:
+            keys[self.username] = public_key_text(self.signing_key)
+        return keys
+
+    async def announce_public_key(self):
+        # Lets everyone in the room verify what we sign from now on
+        if self.app.settings["sign_messages"] and self.app.transport and self.app.connected:
+            await self.send_payload({"type": "public_key", "key": public_key_text(self.get_signing_key())})
+
+    def show_public_key(self):
+        key = public_key_text(self.get_signing_key())
+        self.write_line(f"[bold #87CEEB]Your signing key:[/bold #87CEEB] {key}")
+        if not self.app.settings["sign_messages"]:
+            self.write_line(f"[dim]{sys_msg('signing_off')}[/dim]")
+
+    def trust_key(self, args: str):
+        # /trust <username> trusts the key they announced, /trust <username> <key> one they gave you directly
+        parts = args.split()
+        if len(parts) not in (1, 2):
+            self.write_line(f"[bold yellow]Usage: {escape('/trust <username> [key]')}[/bold yellow]")
+            return
+        username = parts[0]
+        key = parts[1] if len(parts) == 2 else self.announced_keys.get(username)
+        if key is None:
+            self.write_line(f"[bold yellow]{sys_msg('no_announced_key', username=escape(username))}[/bold yellow]")
+            return
+        try:
+            Ed25519PublicKey.from_public_bytes(base64.b64decode(key, validate=True))
+        except ValueError:
+            self.write_line(f"[bold red]{sys_msg('bad_public_key')}[/bold red]")
+            return
+        self.app.settings["trusted_keys"][username] = key
+        save_settings(self.app.settings)
+        self.write_line(f"[bold #87CEEB]{sys_msg('key_trusted', username=escape(username))}[/bold #87CEEB]")
+
+    async def send_again(self, args: str):
+        # A brand new message with the old text; unlike /retry it doesn't reuse a failed line
+        message = history_lookup(self.sent_history, args)
+        if message is None:
+            self.write_line(f"[bold yellow]{sys_msg('nothing_to_resend')}[/bold yellow]")
+            return
+        self.sent_history.append(message)
+        await self.send_message(message)
+
+    async def retry_message(self):
+        # Re-send the newest failed line as that same line, or queue it if we're offline
+        entry = next((entry for entry in reversed(self.entries) if entry.get("state") == "failed" and "content" in entry), None)
+        if entry is None:
+            self.write_line(f"[bold yellow]{sys_msg('nothing_to_retry')}[/bold yellow]")
+            return
+        if time.monotonic() < self.next_send_at:
+            self.write_line(f"[bold yellow]{self.cooldown_message()}[/bold yellow]")
+            return
+        self.update_send_state(entry, "retry")
+        if self.joining or not (self.app.transport and self.app.connected):
+            self.queue_outbox({"content": entry["content"], "attempts": 1, "entry": entry, "reply_to": entry["reply_to"]})
+            self.write_line(f"[bold yellow]{sys_msg('retry_queued')}[/bold yellow]")
+            return
+        try:
+            await self.transmit(entry["content"], entry=entry, reply_to=entry["reply_to"])
+        except TransportClosed:
+            self.mark_disconnected()
+            self.queue_outbox({"content": entry["content"], "attempts": 1, "entry": entry, "reply_to": entry["reply_to"]})
+            self.write_line(f"[bold yellow]{sys_msg('retry_queued')}[/bold yellow]")
+        except Exception as e:
+            self.update_send_state(entry, "error")
+            self.write_line(f"[bold red]{sys_msg('send_error', error=escape(str(e)))}[/bold red]")
+
+    def take_echo(self, data: dict) -> Optional[dict]:
+        # The oldest optimistic entry this echo confirms. Failed entries stay here so a late echo isn't shown twice.
+        for entry in self.awaiting_echo:
+            if entry["wire"] == data.get("content"):
+                self.awaiting_echo.remove(entry)
+                return entry
+        return None
+
+    def update_send_state(self, entry: dict, event: str):
+        state = next_send_state(entry["state"], event)
+        if state == entry["state"]:
+            return
+        entry["state"] = state
+        if self.is_mounted:
+            self.refresh_entry(entry)  # Does nothing once the entry has left the log
+
+    async def send_message(self, user_message: str, reply_to=None):
         # Send message to server
-        if self.app.websocket and self.app.connected:
+        if wire_length(self.e2e_key, user_message) > self.max_length:
+            # The server would only reject it; hand it back to be shortened
+            key = "too_long_encrypted" if self.e2e_key else "too_long"
+            self.write_line(f"[bold yellow]{sys_msg(key, length=wire_length(self.e2e_key, user_message), limit=self.max_length)}[/bold yellow]")
+            if not self.get_draft():
+                self.set_draft(user_message)
+        elif self.joining:
+            # Hold messages until the server confirms the join so they aren't rejected or lost
+            self.pending_sends.append((user_message, reply_to))
+            self.write_line(f"[dim]{sys_msg('still_joining')}[/dim]")
+        elif self.connection_state == "disconnected":
+            self.write_line(f"[bold yellow]{sys_msg('disconnected_send')}[/bold yellow]")
+        elif time.monotonic() < self.next_send_at:
+            # Hand the text back so it can be sent once the cooldown is over
+            self.write_line(f"[bold yellow]{self.cooldown_message()}[/bold yellow]")
+            if not self.get_draft():
+                self.set_draft(user_message)
+        elif self.app.transport and self.app.connected:
             try:
-                message_data = {
-                    "type": "message",
-                    "content": user_message
-                }
-                await self.app.websocket.send(json.dumps(message_data))
-            except websockets.exceptions.ConnectionClosed:
-                messages_log = self.query_one("#messages", RichLog)
-                messages_log.write("[bold red]Cannot send message: Connection closed[/bold red]")
-                self.app.connected = False
-                self.query_one("#header").update("TERMCHAT - Disconnected")
+                await self.transmit(user_message, reply_to=reply_to)
+            except TransportClosed:
+                # The drop happened before the listener noticed
+                self.send_lost(user_message, reply_to)
             except Exception as e:
-                messages_log = self.query_one("#messages", RichLog)
-                messages_log.write(f"[bold red]Error sending message: {e}[/bold red]")
+                self.write_line(f"[bold red]{sys_msg('send_error', error=escape(str(e)))}[/bold red]")
         else:
-            messages_log = self.query_one("#messages", RichLog)
-            messages_log.write("[bold yellow]Not connected to server. Cannot send message.[/bold yellow]")
+            # The UI still says connected but the socket is already gone
+            self.send_lost(user_message, reply_to)
+
+    def cooldown_message(self) -> str:
+        # Says which limit is holding up sending: a rate-limit backoff or plain slow mode
+        now = time.monotonic()
+        key = "rate_limited_wait" if self.penalty_until >= self.next_send_at and self.penalty_until > now else "slowmode_wait"
+        return sys_msg(key, seconds=math.ceil(self.next_send_at - now))
+
+    def update_cooldown(self):
+        # Count down in the input's border while sending is blocked
+        if self.cooldown_timer:
+            self.cooldown_timer.stop()
+            self.cooldown_timer = None
+        container = self.query_one("#input_container")
+        remaining = self.next_send_at - time.monotonic()
+        if remaining <= 0:
+            container.border_title = None
+            return
+        container.border_title = self.cooldown_message()
+        self.cooldown_timer = self.set_timer(min(1.0, remaining), self.update_cooldown)
+
+    def send_lost(self, user_message: str, reply_to=None):
+        # Bring the UI in line with the dead connection (mark_disconnected is safe to repeat)
+        # and keep the message for one retry after reconnecting
+        self.mark_disconnected()
+        if not self.app.settings["retry_failed_sends"]:
+            self.write_line(f"[bold red]{sys_msg('send_closed')}[/bold red]")
+            return
+        self.queue_outbox({"content": user_message, "attempts": 1, "reply_to": reply_to})
+        self.write_line(f"[bold yellow]{sys_msg('send_queued')}[/bold yellow]")
 
     async def change_theme_color(self, new_color: str):
         # Change the theme color of the interface with smooth transition
@@ -777,8 +5305,8 @@ class ChatScreen(Screen):
                 
                 # Update all at once
                 header.styles.color = current_color
-                messages_container.styles.border = ("solid", current_color)
-                input_container.styles.border = ("solid", current_color)
+                messages_container.styles.border = (glyphs.border, current_color)
+                input_container.styles.border = (glyphs.border, current_color)
                 messages.styles.scrollbar_background = current_color
                 messages.styles.scrollbar_color = scrollbar_color
                 
@@ -788,8 +5316,8 @@ class ChatScreen(Screen):
             
             # Final values
             header.styles.color = new_color
-            messages_container.styles.border = ("solid", new_color)
-            input_container.styles.border = ("solid", new_color)
+            messages_container.styles.border = (glyphs.border, new_color)
+            input_container.styles.border = (glyphs.border, new_color)
             messages.styles.scrollbar_background = new_color
             messages.styles.scrollbar_color = rgb_to_hex(*(x * 0.5 for x in end_rgb))
             
@@ -803,8 +5331,8 @@ class ChatScreen(Screen):
             messages = self.query_one("#messages")
             
             header.styles.color = new_color
-            messages_container.styles.border = ("solid", new_color)
-            input_container.styles.border = ("solid", new_color)
+            messages_container.styles.border = (glyphs.border, new_color)
+            input_container.styles.border = (glyphs.border, new_color)
             messages.styles.scrollbar_background = new_color
             messages.styles.scrollbar_color = rgb_to_hex(*(x * 0.5 for x in hex_to_rgb(new_color)))
             
@@ -817,7 +5345,7 @@ class ChatScreen(Screen):
         frame_time = duration / steps
         
         # Get current background color
-        current_bg = getattr(self.app, 'background_color', '#000000')
+        current_bg = self.app.background_color
         
         def hex_to_rgb(hex_color):
             hex_color = hex_color.lstrip('#')
@@ -870,6 +5398,7 @@ class ChatScreen(Screen):
             messages.styles.background = bg_color
             input_container.styles.background = bg_color
             message_input.styles.background = dark_final
+            messages.styles.color = message_input.styles.color = text_color_for(bg_color)
             
             self.app.background_color = bg_color
             
@@ -888,72 +5417,358 @@ class ChatScreen(Screen):
             input_container.styles.background = bg_color
             message_input = self.query_one("#message_input")
             message_input.styles.background = dark_bg
+            messages.styles.color = message_input.styles.color = text_color_for(bg_color)
             
             self.app.background_color = bg_color
 
+class ChatCommands(Provider):
+    # Command palette entries for the chat screen: every slash command plus a few screen actions
+
+    async def search(self, query: str):
+        screen = self.screen
+        if not isinstance(screen, ChatScreen):
+            return
+        matcher = self.matcher(query)
+        for spec in COMMANDS:
+            if spec.capability and not screen.capabilities.supports(spec.capability):
+                continue  # Hidden rather than offered and refused
+            score = matcher.match(f"{spec.usage} {spec.description}")
+            if score > 0:
+                yield Hit(score, matcher.highlight(spec.usage), partial(screen.run_palette_command, spec), help=spec.description)
+        for label, shortcut, action in PALETTE_ACTIONS:
+            score = matcher.match(label)
+            if score > 0:
+                yield Hit(score, matcher.highlight(label), partial(screen.run_action, action), help=shortcut)
+
+
 class TermchatApp(App):
     # Main Termchat application using proper screen management
 
+    COMMAND_PALETTE_BINDING = "f1"  # Ctrl+P is the message preview, and most terminals send Ctrl+Shift+P as Ctrl+P
+    COMMANDS = App.COMMANDS | {ChatCommands}
+
     SCREENS = {
         "splash": SplashScreen,
         "connection": ConnectionScreen,
     }
     
-    def __init__(self):
+    def __init__(self, theme: Optional[str] = None, join_uri: Optional[str] = None, e2e_passphrase: Optional[str] = None, bind_address: Optional[str] = None, server_url: Optional[str] = None):
         super().__init__()
-        self.websocket: Optional[websockets.WebSocketClientProtocol] = None
+        self.e2e_passphrase = e2e_passphrase  # Only kept in memory, never saved
+        self.bind_address = bind_address  # Local source address for every connection, None lets the OS pick
+        self.transport: Optional[Transport] = None
+        self.connect_transport = WebSocketTransport.connect  # Opens the chat connection; the tests swap in a mock
         self.user_colors: dict = {}  # Maps usernames to colors
         self.color_index: int = 0    # For cycling through colors
+        self.readable_colors: dict = {}  # (palette color, background) -> contrast-adjusted color
         self.connected: bool = False
-        self.theme_color: str = "#87CEEB"  # Current theme color
+        self.settings: dict = load_settings()
+
+        # Starting theme from --theme, else the saved setting or desktop; the server can still override it
+        self.startup_warning: Optional[str] = None
+        self.requested_theme = theme
+        self.os_scheme: Optional[str] = detect_os_color_scheme() if not theme and self.settings["theme"] == "auto" else None
+        self.server_themed = False  # Once the server sets colors, desktop changes no longer do
+        requested_theme = pick_theme(theme, self.settings["theme"], self.os_scheme)
+        resolved = resolve_theme(requested_theme)
+        if resolved is None:
+            self.startup_warning = f"Unknown theme '{requested_theme}', using default"
+            resolved = THEMES["default"]
+        self.theme_color: str = resolved[0]  # Current theme color
+        self.background_color: str = resolved[1]
+        self.http_session: Optional[aiohttp.ClientSession] = None
+        self.general_count_task: Optional[asyncio.Task] = None
+        self.is_shut_down: bool = False
+        self.drafts: dict = {}  # (server_url, chat_name) -> unsent input text
+        self.left_rooms: dict = {}  # (server_url, chat_name) -> queued messages and scroll offset, see stash_room_state
+        self.events = EventStream()  # Connection state changes and server events, see subscribe()
+        self.sent = ThroughputWindow()  # WebSocket frame traffic, for /diag
+        self.received = ThroughputWindow()
+        self.rtt_history = RttHistory()  # Survives reconnects, for the /diag latency graph
         
-        # Backend server URL (HTTPS WebSocket on port 443)
-        self.server_url = "wss://termchat-f9cgabe4ajd9djb9.australiaeast-01.azurewebsites.net"
+        # Backend server URL (HTTPS WebSocket on port 443), unless --server gave another
+        self.server_url = server_url or DEFAULT_SERVER_URL
+        self.initial_chat_name: Optional[str] = None
+
+        # A termchat://join link overrides the server and pre-fills the chat name
+        if join_uri:
+            join_params = parse_join_uri(join_uri)
+            if join_params:
+                self.server_url = join_params["server"]
+                self.initial_chat_name = join_params["chat"]
+            else:
+                self.startup_warning = "Ignoring malformed join link"
 
     def on_mount(self):
         # Start with the splash screen
         self.push_screen("splash")
+        if self.startup_warning:
+            self.notify(self.startup_warning, severity="warning")
 
-    def start_chat(self, username: str, chat_name: str, password: str):
+    def start_chat(self, username: str, chat_name: str, password: str, auth_method: str = "password"):
         # Start the chat with the given credentials
-        chat_screen = ChatScreen(username, chat_name, password)
+        chat_screen = ChatScreen(username, chat_name, password, auth_method)
         self.push_screen(chat_screen)
 
+    def dnd_active(self) -> bool:
+        return self.settings["dnd"] or in_quiet_hours(self.settings["dnd_schedule"], time.localtime())
+
+    def alert(self, message: str, severity: str = "information"):
+        # Every notification raised while chatting goes through here so do not disturb can hold it back
+        if not self.dnd_active():
+            self.notify(message, severity=severity)
+
+    def subscribe(self) -> asyncio.Queue:
+        # Observe connection state changes and server events without polling
+        return self.events.subscribe()
+
+    def record_recent_room(self, chat_name: str):
+        self.settings["recent_rooms"] = touch_recent_room(
+            self.settings["recent_rooms"], self.server_url, chat_name, time.time()
+        )
+        save_settings(self.settings)
+
+    def websocket_url(self) -> str:
+        return resolve_server_url(self.server_url, self.settings["websocket_path"])
+
+    def get_http_session(self) -> aiohttp.ClientSession:
+        # One certifi-backed session for all HTTP requests so connections are reused
+        if self.http_session is None or self.http_session.closed:
+            ssl_context = ssl.create_default_context(cafile=certifi.where())
+            connector = aiohttp.TCPConnector(ssl=ssl_context, **bind_options(self.bind_address))
+            self.http_session = aiohttp.ClientSession(connector=connector)
+        return self.http_session
+
+    async def fetch_general_count(self) -> int:
+        # Concurrent callers share the in-flight request instead of racing each other
+        if self.general_count_task is None or self.general_count_task.done():
+            self.general_count_task = asyncio.create_task(
+                get_general_count(self.get_http_session(), self.server_url, self.settings["general_count_path"])
+            )
+        return await asyncio.shield(self.general_count_task)
+
     def get_user_color(self, username: str) -> str:
         # Get or assign a color for a username
         if username.lower() == "server":
             return "bold #87CEEB"
+        # A color picked with /color beats the assigned one, and is used as picked
+        override = self.settings["color_overrides"].get(username.lower())
+        if override:
+            return override
         
         if username not in self.user_colors:
             self.user_colors[username] = USER_COLORS[self.color_index % len(USER_COLORS)]
             self.color_index += 1
-        
-        return self.user_colors[username]
 
-    async def action_quit(self):
-        # Quit the application
-        if self.websocket:
+        # Keep the palette color when it's readable on the current background, else adjust it
+        color_name = self.user_colors[username]
+        if not HEX_COLOR_RE.match(self.background_color):
+            return color_name
+        cache_key = (color_name, self.background_color)
+        if cache_key not in self.readable_colors:
+            fg = USER_COLOR_RGB[color_name]
+            adjusted = readable_color(fg, hex_to_rgb(self.background_color))
+            self.readable_colors[cache_key] = color_name if adjusted == fg else "#{:02x}{:02x}{:02x}".format(*adjusted)
+        return self.readable_colors[cache_key]
+
+    async def shutdown(self):
+        # Close the socket and HTTP session; safe to call more than once
+        if self.is_shut_down:
+            return
+        self.is_shut_down = True
+        if self.general_count_task and not self.general_count_task.done():
+            self.general_count_task.cancel()
+        if self.transport:
             try:
-                await self.websocket.close()
+                # Bounded so an unresponsive server can't hang exit
+                await asyncio.wait_for(self.transport.close(), timeout=SHUTDOWN_TIMEOUT)
             except:
                 pass
+            self.transport = None
+        self.connected = False
+        if self.http_session and not self.http_session.closed:
+            await self.http_session.close()
+
+    async def action_quit(self):
+        # Quit the application
+        await self.shutdown()
         self.exit()
 
 
-async def main():
+def json_event(event: str, fields: dict) -> str:
+    # One JSON object per line for --json mode; server-provided fields win over our timestamp
+    output = {"event": event, "ts": time.time()}
+    output.update((key, value) for key, value in fields.items() if key != "event")
+    return json.dumps(output)
+
+async def run_json_mode(args) -> int:
+    # Headless scripting mode: server events go to stdout and commands come from stdin,
+    # both as newline-delimited JSON, e.g. {"type": "message", "content": "hi"}
+    username = args.username or "guest"
+    chat_name = args.chat or "general"
+    password = args.password or "default"
+    e2e_key = derive_e2e_key(args.e2e_key, chat_name) if args.e2e_key else None
+
+    def emit(event: str, **fields):
+        print(json_event(event, fields), flush=True)
+
+    url = resolve_server_url(args.server or DEFAULT_SERVER_URL, load_settings()["websocket_path"])
+    try:
+        websocket = await websockets.connect(
+            url,
+            **tls_options(url),
+            ping_interval=30,
+            ping_timeout=10,
+            close_timeout=10,
+            max_size=MAX_FRAME_SIZE,
+            max_queue=32,
+            **bind_options(args.bind)
+        )
+    except OSError as e:
+        if is_bind_error(e, args.bind):
+            emit("error", message=f"Cannot bind to local address {args.bind}: {e.strerror}")
+        else:
+            emit("error", message=f"Failed to connect to server: {e}")
+        return 1
+    except Exception as e:
+        emit("error", message=f"Failed to connect to server: {e}")
+        return 1
+
+    # stdin is read on a daemon thread so a blocked readline never holds up exit
+    loop = asyncio.get_running_loop()
+    stdin_lines: asyncio.Queue = asyncio.Queue()
+
+    def read_stdin():
+        for line in sys.stdin:
+            loop.call_soon_threadsafe(stdin_lines.put_nowait, line)
+        loop.call_soon_threadsafe(stdin_lines.put_nowait, None)
+
+    threading.Thread(target=read_stdin, daemon=True).start()
+
+    async def handle_commands():
+        while True:
+            line = await stdin_lines.get()
+            if line is None:
+                break
+            line = line.strip()
+            if not line:
+                continue
+            try:
+                command = json.loads(line)
+            except json.JSONDecodeError:
+                emit("error", message="Invalid JSON command")
+                continue
+            if not isinstance(command, dict):
+                emit("error", message="Commands must be JSON objects")
+                continue
+            command_type = command.get("type")
+            if command_type == "message":
+                content = str(command.get("content", ""))
+                if e2e_key:
+                    content = encrypt_content(e2e_key, content)
+                await websocket.send(json.dumps({"type": "message", "content": content}))
+            elif command_type == "quit":
+                break
+            else:
+                emit("error", message=f"Unknown command type: {command_type}")
+        await websocket.close()
+
+    auth_method = "token" if args.token else "password"
+    await websocket.send(json.dumps(build_join_payload(username, chat_name, auth_method, args.token or password)))
+    commands_task = asyncio.create_task(handle_commands())
+    joined = False
+    buffer = ""  # Partial JSON carried between frames
+    try:
+        async for raw in websocket:
+            if isinstance(raw, bytes):
+                raw = raw.decode("utf-8", errors="replace")
+            objects, invalid, buffer = extract_json_objects(buffer + raw)
+            for _ in invalid:
+                emit("error", message="Received invalid JSON")
+            for data in objects:
+                message_type = data.pop("type", "unknown")
+                if message_type == "message" and isinstance(data.get("content"), str):
+                    data["content"] = display_content(e2e_key, data["content"])
+                if message_type == "join" and data.get("username") == username and not joined:
+                    joined = True
+                    emit("connected", username=username, chat=chat_name)
+                else:
+                    print(json_event(message_type, data), flush=True)
+    except websockets.exceptions.ConnectionClosed:
+        pass
+    finally:
+        commands_task.cancel()
+    emit("disconnected")
+    return 0
+
+def parse_args():
+    parser = argparse.ArgumentParser(description="Termchat - terminal chat client")
+    parser.add_argument("join_uri", nargs="?", help="termchat://join link to open")
+    parser.add_argument(
+        "--theme",
+        metavar="NAME|#HEX",
+        help=f"starting theme: #RRGGBB or one of {', '.join(THEMES)}",
+    )
+    parser.add_argument(
+        "--json",
+        action="store_true",
+        help="headless mode: print events as JSON lines and read JSON commands from stdin",
+    )
+    parser.add_argument(
+        "--e2e-key",
+        metavar="PASSPHRASE",
+        help="encrypt messages end-to-end with a passphrase shared with the rest of the room",
+    )
+    parser.add_argument(
+        "--server",
+        metavar="URL",
+        type=parse_server_url,
+        help="connect to this server instead of the default, e.g. wss://chat.example.com/ws",
+    )
+    parser.add_argument(
+        "--bind",
+        metavar="IP",
+        type=parse_bind_address,
+        help="send all traffic from this local address (default: let the OS choose)",
+    )
+    parser.add_argument(
+        "--ascii",
+        action="store_true",
+        help="draw the interface with plain ASCII and leave emoji shortcodes as text",
+    )
+    parser.add_argument("--dev", action="store_true", help=argparse.SUPPRESS)
+    parser.add_argument("--username", help="username for --json mode (default: guest)")
+    parser.add_argument("--chat", help="chat name for --json mode (default: general)")
+    parser.add_argument("--password", help="chat password for --json mode")
+    parser.add_argument("--token", help="join with an auth token instead of a password in --json mode")
+    return parser.parse_args(restore_secret_args(sys.argv[1:]))
+
+async def main(args):
     # Entry point for the application
-    app = TermchatApp()
-    await app.run_async()
+    app = TermchatApp(theme=args.theme, join_uri=args.join_uri, e2e_passphrase=args.e2e_key, bind_address=args.bind, server_url=args.server)
+    try:
+        await app.run_async()
+    finally:
+        # Covers exits that didn't go through action_quit
+        await app.shutdown()
 
 if __name__ == "__main__":
+    args = parse_args()
+    if args.ascii:
+        use_glyphs(Glyphs.ascii())
+    if args.dev:
+        enable_dev_commands()
     try:
         # Ensure asyncio compatibility across platforms
         if sys.platform == "win32":
             asyncio.set_event_loop_policy(asyncio.WindowsProactorEventLoopPolicy())
+        # Headless JSON mode is meant for pipes, so it never relaunches into a terminal
+        if args.json:
+            sys.exit(asyncio.run(run_json_mode(args)))
         # Relaunch in terminal if not already in one
         if not is_in_terminal():
             launch_new_terminal()
-        asyncio.run(main())
+        asyncio.run(main(args))
     except KeyboardInterrupt:
         print("\nExiting...")
     except Exception as e:
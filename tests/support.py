@@ -0,0 +1,151 @@
+# Shared by the tests: an in-memory server behind the Transport interface, and an app that
+# starts straight in a chat room instead of on the splash screen.
+import asyncio
+import json
+import tempfile
+from unittest import mock
+
+import termchat
+
+
+class MockTransport(termchat.Transport):
+    # One client connection to a MockServer. Frames the client sends are kept, decoded, in `sent`;
+    # frames the server pushes wait in a queue for recv().
+
+    def __init__(self, server: "MockServer"):
+        self.server = server
+        self.sent: list = []
+        self.incoming: asyncio.Queue = asyncio.Queue()
+        self.is_closed = False
+
+    async def send(self, frame: str):
+        if self.is_closed:
+            raise termchat.TransportClosed(self.close_code, self.close_reason)
+        data = json.loads(frame)
+        self.sent.append(data)
+        for reply in self.server.respond(self, data):
+            self.push(reply)
+
+    def push(self, data):
+        # A frame from the server: a dict is sent as JSON, a str as it is
+        self.incoming.put_nowait(data if isinstance(data, str) else json.dumps(data))
+
+    def drop(self, code: int = 1006, reason: str = ""):
+        # The server closes the connection; any frames already pushed are still read first
+        self.incoming.put_nowait((code, reason))
+
+    async def recv(self):
+        if self.is_closed:
+            raise termchat.TransportClosed(self.close_code, self.close_reason)
+        item = await self.incoming.get()
+        if isinstance(item, tuple):
+            self.is_closed = True
+            self.close_code, self.close_reason = item
+            raise termchat.TransportClosed(*item)
+        return item
+
+    async def ping(self, timeout: float) -> float:
+        return 0.001
+
+    async def close(self, code: int = 1000, reason: str = ""):
+        # Closed from our side: nothing more can be sent, and a pending recv() wakes up to the close
+        if not self.is_closed:
+            self.is_closed = True
+            self.close_code, self.close_reason = code, reason
+            self.drop(code, reason)
+
+    def sent_of_type(self, message_type: str) -> list:
+        return [data for data in self.sent if data.get("type") == message_type]
+
+
+class MockServer:
+    # Confirms every join and echoes every message to its sender, like the real server does.
+    # Tests change the behaviour by overriding respond, or by pushing frames themselves.
+
+    def __init__(self):
+        self.transports: list = []  # One per connect, newest last
+        self.urls: list = []  # Every URL connected to, including refused attempts
+        self.next_id = 1
+        self.refuse: list = []  # Exceptions raised by the next connects, one per attempt
+        self.confirm_joins = True  # Off to hold the handshake open until the test pushes the join echo
+        self.reachable = True  # What the reachability check sees, see fast_reconnects
+
+    @property
+    def transport(self) -> MockTransport:
+        return self.transports[-1]
+
+    async def connect(self, url: str, **options) -> MockTransport:
+        self.urls.append(url)
+        if url.startswith("ws://") and options.get("ssl") is not None:
+            raise ValueError("connect() received a ssl argument for a ws:// URI")  # As websockets does
+        if self.refuse:
+            raise self.refuse.pop(0)
+        transport = MockTransport(self)
+        self.transports.append(transport)
+        return transport
+
+    def respond(self, transport: MockTransport, data: dict) -> list:
+        if data.get("type") == "join":
+            transport.username = data["username"]
+            return [join_echo(data)] if self.confirm_joins else []
+        if data.get("type") == "message":
+            message = {"type": "message", "username": transport.username, "content": data["content"], "id": self.next_id}
+            self.next_id += 1
+            return [message]
+        return []
+
+
+def join_echo(join: dict, **fields) -> dict:
+    # The server's confirmation of a join frame
+    return {"type": "join", "username": join["username"], "chatname": join.get("chatname"), **fields}
+
+
+class RoomApp(termchat.TermchatApp):
+    # Joins `chat` as `username` over the mock server as soon as it starts
+
+    def __init__(self, server: MockServer, username: str = "alice", chat: str = "general", **options):
+        super().__init__(**options)
+        self.connect_transport = server.connect
+        self.start_as = (username, chat)
+
+    def push_screen(self, screen, *args, **kwargs):
+        if screen == "splash":
+            screen = termchat.ChatScreen(self.start_as[0], self.start_as[1], "secret")
+        return super().push_screen(screen, *args, **kwargs)
+
+
+def isolate_config(test):
+    # Point settings and the signing key at a temporary directory for the rest of the test
+    directory = tempfile.TemporaryDirectory()
+    test.addCleanup(directory.cleanup)
+    for target, value in (("SETTINGS_PATH", f"{directory.name}/settings.json"), ("get_config_dir", lambda: directory.name)):
+        patcher = mock.patch.object(termchat, target, value)
+        patcher.start()
+        test.addCleanup(patcher.stop)
+    return directory.name
+
+
+def fast_reconnects(test, server: MockServer):
+    # Reconnect after a hundredth of a second instead of seconds, with reachability decided by the mock server
+    async def reachable(*args, **kwargs) -> bool:
+        return server.reachable
+    for target, value in (("RECONNECT_BASE_DELAY", 0.01), ("host_reachable", reachable)):
+        patcher = mock.patch.object(termchat, target, value)
+        patcher.start()
+        test.addCleanup(patcher.stop)
+
+
+async def wait_until(pilot, condition, timeout: float = 3.0):
+    # Let the app run until condition() holds; fails the test instead of hanging
+    loop = asyncio.get_running_loop()
+    deadline = loop.time() + timeout
+    while not condition():
+        if loop.time() > deadline:
+            raise AssertionError("timed out waiting for the app")
+        await pilot.pause(0.01)
+
+
+async def joined(pilot) -> "termchat.ChatScreen":
+    # The chat screen, once the mock server has confirmed the join
+    await wait_until(pilot, lambda: pilot.app.connected)
+    return pilot.app.screen
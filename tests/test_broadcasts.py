@@ -0,0 +1,35 @@
+import unittest
+
+import termchat
+
+
+class BroadcastStoreTest(unittest.TestCase):
+    def test_expired_notices_drop_out(self):
+        store = termchat.BroadcastStore()
+        store.add("Maintenance at noon", expires_at=100.0)
+        store.add("Welcome!", expires_at=200.0)
+        self.assertEqual(store.prune(now=50.0), ["Maintenance at noon", "Welcome!"])
+        self.assertEqual(store.next_expiry(), 100.0)
+        self.assertEqual(store.prune(now=100.0), ["Welcome!"])
+        self.assertEqual(store.next_expiry(), 200.0)
+
+    def test_dismissed(self):
+        store = termchat.BroadcastStore()
+        store.add("Welcome!", expires_at=200.0)
+        store.clear()
+        self.assertEqual(store.prune(now=0.0), [])
+        self.assertIsNone(store.next_expiry())
+
+
+class BroadcastExpiryTest(unittest.TestCase):
+    def test_server_time_in_milliseconds(self):
+        self.assertEqual(termchat.broadcast_expiry({"expires_ms": 1700000000000}, now=0.0), 1700000000.0)
+
+    def test_default_lifetime(self):
+        for data in ({}, {"expires_ms": "soon"}, {"expires_ms": True}):
+            with self.subTest(data=data):
+                self.assertEqual(termchat.broadcast_expiry(data, now=1000.0), 1000.0 + termchat.DEFAULT_BROADCAST_TTL)
+
+
+if __name__ == "__main__":
+    unittest.main()
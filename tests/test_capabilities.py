@@ -0,0 +1,48 @@
+import unittest
+
+import termchat
+from support import MockServer, RoomApp, isolate_config, join_echo, joined
+
+
+class ServerCapabilitiesTest(unittest.TestCase):
+    def test_advertised_features(self):
+        capabilities = termchat.ServerCapabilities(["status", "polls", "teleport", 7])
+        self.assertTrue(capabilities.supports("status"))
+        self.assertFalse(capabilities.supports("report"))
+        self.assertEqual(capabilities.advertised, {"status", "polls"})
+
+    def test_no_list_means_everything(self):
+        for advertised in (None, "status", {"status": True}):
+            with self.subTest(advertised=advertised):
+                self.assertTrue(termchat.ServerCapabilities(advertised).supports("report"))
+
+    def test_empty_list_means_nothing(self):
+        self.assertFalse(termchat.ServerCapabilities([]).supports("status"))
+
+
+class GatingTest(unittest.IsolatedAsyncioTestCase):
+    def setUp(self):
+        isolate_config(self)
+        self.server = MockServer()
+        server_respond = self.server.respond
+
+        def respond(transport, data):
+            if data.get("type") == "join":
+                transport.username = data["username"]
+                return [join_echo(data, capabilities=["status"])]
+            return server_respond(transport, data)
+        self.server.respond = respond
+        self.app = RoomApp(self.server)
+
+    async def test_unsupported_commands_are_refused_locally(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            await screen.submit_message("/report bob spam")
+            self.assertEqual(self.server.transport.sent_of_type("report"), [])
+            self.assertIn(termchat.sys_msg("unsupported", command="report"), screen.entries[-1]["markup"])
+            await screen.submit_message("/status away")
+            self.assertEqual(self.server.transport.sent_of_type("status"), [{"type": "status", "text": "away"}])
+
+
+if __name__ == "__main__":
+    unittest.main()
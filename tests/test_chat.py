@@ -0,0 +1,345 @@
+import unittest
+from unittest import mock
+
+import termchat
+from support import MockServer, RoomApp, isolate_config, joined, wait_until
+
+
+def withhold_echoes(server):
+    # Joins are confirmed but messages aren't echoed, so the test decides when the echo arrives
+    server_respond = server.respond
+    server.respond = lambda transport, data: server_respond(transport, data) if data.get("type") == "join" else []
+
+
+class ChatScreenTest(unittest.IsolatedAsyncioTestCase):
+    # Log behaviour that needs a mounted chat screen
+    def setUp(self):
+        isolate_config(self)
+        self.server = MockServer()
+        self.app = RoomApp(self.server)
+
+
+class CollapsedNoticeTest(ChatScreenTest):
+    async def test_repeats_update_the_line_in_place(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            messages_log = screen.query_one("#messages")
+            screen.write_line("Something happened")
+            lines = len(messages_log.lines)
+            entries = len(screen.entries)
+            screen.write_line("Something happened")
+            screen.write_line("Something happened")
+            self.assertEqual(len(screen.entries), entries)
+            self.assertEqual(screen.entries[-1]["count"], 3)
+            self.assertEqual(len(messages_log.lines), lines)
+            self.assertIn("3", messages_log.lines[-1].text)
+
+    async def test_lines_after_a_taller_entry_move_down(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            messages_log = screen.query_one("#messages")
+            first = screen.write_line("one line")
+            after = screen.write_line("after it")
+            first["markup"] = "now\ntwo lines"
+            screen.refresh_entry(first)
+            self.assertEqual(after["line"], first["line"] + 2)
+            self.assertEqual(messages_log.lines[first["line"] + 1].text.strip(), "two lines")
+            self.assertIn("after it", messages_log.lines[after["line"]].text)
+
+
+class LogTrimTest(ChatScreenTest):
+    async def test_dropped_entries_leave_the_screen_too(self):
+        for target, value in (("MAX_LOG_ENTRIES", 10), ("LOG_TRIM_BATCH", 3)):
+            patcher = mock.patch.object(termchat, target, value)
+            patcher.start()
+            self.addCleanup(patcher.stop)
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            messages_log = screen.query_one("#messages")
+            for number in range(20):
+                screen.write_line(f"line {number}")
+                self.assertLessEqual(len(screen.entries), 13)
+                self.assertEqual(screen.entries[0]["line"], 0)
+                self.assertEqual(len(messages_log.lines), screen.entries[-1]["line"] + 1)
+            self.assertIn(screen.entries[0]["markup"], messages_log.lines[0].text)
+            self.assertIn("line 19", messages_log.lines[-1].text)
+
+
+class StatusTest(ChatScreenTest):
+    async def test_set_and_limit(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            await screen.set_status("brb")
+            await screen.set_status("x" * (termchat.MAX_STATUS_LENGTH + 1))
+            self.assertEqual(self.server.transport.sent_of_type("status"), [{"type": "status", "text": "brb"}])
+
+    async def test_shown_in_the_roster_and_cleared(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            await screen.handle_message({"type": "join", "username": "bob"})
+            await screen.handle_message({"type": "status", "username": "bob", "text": "on holiday" + "!" * 100})
+            self.assertEqual(screen.statuses["bob"], ("on holiday" + "!" * 100)[:termchat.MAX_STATUS_LENGTH])
+            screen.show_roster()
+            self.assertIn("on holiday", screen.entries[-1]["markup"])
+            await screen.handle_message({"type": "status", "username": "bob", "text": ""})
+            self.assertNotIn("bob", screen.statuses)
+
+
+class DraftTest(ChatScreenTest):
+    async def in_room(self, pilot, chat_name: str):
+        await wait_until(pilot, lambda: getattr(pilot.app.screen, "chat_name", None) == chat_name and pilot.app.connected)
+        return pilot.app.screen
+
+    async def test_kept_per_room(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            await pilot.press("h", "a", "l", "f")
+            await screen.go_to_room("random")
+            screen = await self.in_room(pilot, "random")
+            self.assertEqual(screen.get_draft(), "")
+            await screen.go_to_room("general")
+            screen = await self.in_room(pilot, "general")
+            self.assertEqual(screen.get_draft(), "half")
+
+    async def test_cleared_once_sent(self):
+        async with self.app.run_test() as pilot:
+            await joined(pilot)
+            await pilot.press("h", "i", "enter")
+            await wait_until(pilot, lambda: self.server.transport.sent_of_type("message"))
+            await wait_until(pilot, lambda: self.app.screen.draft_key not in self.app.drafts)
+
+
+class ReportTest(ChatScreenTest):
+    async def test_sent_to_the_server_and_acknowledged(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            await screen.report_user("bob spamming links")
+            report = self.server.transport.sent_of_type("report")
+            self.assertEqual(report, [{"type": "report", "target_username": "bob", "message_id": None, "reason": "spamming links"}])
+            await screen.handle_message({"type": "ack", "for": "report", "ok": True})
+            self.assertIn(termchat.sys_msg("report_submitted"), screen.entries[-1]["markup"])
+
+    async def test_rejection_shows_the_reason(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            await screen.handle_message({"type": "ack", "for": "report", "ok": False, "message": "Too many reports"})
+            self.assertIn("Too many reports", screen.entries[-1]["markup"])
+
+    async def test_nothing_sent_for_bad_reports(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            await screen.report_user("bob")
+            await screen.report_user("ALICE being rude")
+            self.assertEqual(self.server.transport.sent_of_type("report"), [])
+
+
+class OptimisticEchoTest(ChatScreenTest):
+    def setUp(self):
+        super().setUp()
+        withhold_echoes(self.server)
+
+    def own_lines(self, screen) -> list:
+        return [entry for entry in screen.entries if entry.get("username") == "alice" and entry.get("content") == "hello"]
+
+    async def test_shown_pending_then_confirmed_once(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            await screen.send_message("hello")
+            [entry] = self.own_lines(screen)
+            self.assertEqual(entry["state"], "pending")
+            self.server.transport.push({"type": "message", "username": "alice", "content": "hello", "id": 7})
+            await wait_until(pilot, lambda: entry["state"] == "sent")
+            self.assertEqual(entry["id"], 7)
+            self.assertEqual(len(self.own_lines(screen)), 1)
+
+    async def test_unconfirmed_send_fails(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            with mock.patch.object(termchat, "CONFIRM_TIMEOUT", 0.05):
+                await screen.send_message("hello")
+            [entry] = self.own_lines(screen)
+            await wait_until(pilot, lambda: entry["state"] == "failed")
+
+    def test_on_by_default(self):
+        self.assertTrue(termchat.DEFAULT_SETTINGS["optimistic_send"])
+
+    async def test_off_waits_for_the_echo(self):
+        self.app.settings["optimistic_send"] = False
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            await screen.send_message("hello")
+            self.assertEqual(self.own_lines(screen), [])
+            self.server.transport.push({"type": "message", "username": "alice", "content": "hello", "id": 7})
+            await wait_until(pilot, lambda: len(self.own_lines(screen)) == 1)
+            self.assertNotEqual(self.own_lines(screen)[0].get("state"), "pending")
+
+
+class SlowModeTest(ChatScreenTest):
+    async def test_sends_wait_out_the_cooldown(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            await screen.handle_message({"type": "slowmode", "seconds": 30})
+            await screen.send_message("one")
+            await screen.send_message("two")
+            self.assertEqual([data["content"] for data in self.server.transport.sent_of_type("message")], ["one"])
+            self.assertEqual(screen.get_draft(), "two")
+            self.assertIn("Slow mode", screen.query_one("#input_container").border_title)
+
+    async def test_turning_it_off_lifts_the_wait(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            await screen.handle_message({"type": "slowmode", "seconds": 30})
+            await screen.send_message("one")
+            await screen.handle_message({"type": "slowmode", "seconds": 0})
+            await screen.send_message("two")
+            self.assertEqual(len(self.server.transport.sent_of_type("message")), 2)
+
+
+class ClearUndoTest(ChatScreenTest):
+    def contents(self, screen) -> list:
+        return [entry["content"] for entry in screen.entries if entry.get("username") == "bob"]
+
+    async def test_clear_asks_first_and_undo_restores(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            await screen.handle_message({"type": "message", "username": "bob", "content": "keep me", "id": 1})
+            screen.handle_clear_command("/clear")
+            self.assertEqual(self.contents(screen), ["keep me"])
+            screen.handle_clear_command("/clear")
+            self.assertEqual(self.contents(screen), [])
+            screen.undo_clear()
+            self.assertEqual(self.contents(screen), ["keep me"])
+
+    async def test_undo_expires(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            await screen.handle_message({"type": "message", "username": "bob", "content": "gone", "id": 1})
+            screen.handle_clear_command("/clear!")
+            screen.undo_buffer.saved_at -= termchat.UNDO_WINDOW + 1
+            screen.undo_clear()
+            self.assertEqual(self.contents(screen), [])
+            self.assertIn(termchat.sys_msg("nothing_to_undo"), screen.entries[-1]["markup"])
+
+
+class RetryTest(ChatScreenTest):
+    def setUp(self):
+        super().setUp()
+        withhold_echoes(self.server)
+
+    async def test_failed_line_is_sent_again_as_the_same_line(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            with mock.patch.object(termchat, "CONFIRM_TIMEOUT", 0.05):
+                await screen.send_message("hello")
+                entry = screen.entries[-1]
+                await wait_until(pilot, lambda: entry["state"] == "failed")
+            await pilot.press("ctrl+r")
+            await wait_until(pilot, lambda: len(self.server.transport.sent_of_type("message")) == 2)
+            self.assertEqual(entry["state"], "pending")
+            self.assertEqual([data["content"] for data in self.server.transport.sent_of_type("message")], ["hello", "hello"])
+            self.server.transport.push({"type": "message", "username": "alice", "content": "hello", "id": 9})
+            await wait_until(pilot, lambda: entry["state"] == "sent")
+            self.assertEqual(sum(candidate.get("content") == "hello" for candidate in screen.entries), 1)
+
+    async def test_nothing_to_retry(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            await screen.retry_message()
+            self.assertIn(termchat.sys_msg("nothing_to_retry"), screen.entries[-1]["markup"])
+
+
+class RateLimitTest(ChatScreenTest):
+    async def test_sends_held_until_the_penalty_ends(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            await screen.handle_message({"type": "rate_limited", "retry_after_ms": 20000})
+            await screen.send_message("too soon")
+            self.assertEqual(self.server.transport.sent_of_type("message"), [])
+            self.assertIn("Rate limited", screen.query_one("#input_container").border_title)
+
+
+class EditTest(ChatScreenTest):
+    async def test_shown_in_place_and_marked_edited(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            await screen.handle_message({"type": "message", "username": "bob", "content": "helo", "id": 5})
+            await screen.handle_message({"type": "message", "username": "bob", "content": "next", "id": 6})
+            await screen.handle_message({"type": "edit", "message_id": 5, "new_content": "hello", "edited_ms": 1700000000000})
+            entry = termchat.find_entry(screen.entries, 5)
+            self.assertEqual(entry["content"], "hello")
+            self.assertEqual([candidate.get("id") for candidate in screen.entries if candidate.get("username") == "bob"], [5, 6])
+            self.assertIn("edited", entry["markup"])
+
+    async def test_unknown_message_is_ignored(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            entries = list(screen.entries)
+            await screen.handle_message({"type": "edit", "message_id": 404, "new_content": "hello"})
+            self.assertEqual(screen.entries, entries)
+
+
+class FocusModeTest(ChatScreenTest):
+    async def test_f11_hides_the_chrome_and_escape_leaves(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            message_input = screen.query_one("#message_input")
+            message_input.value = "half a thought"
+            await pilot.press("f11")
+            self.assertTrue(screen.has_class("focus-mode"))
+            self.assertFalse(screen.query_one("#input_container").display)
+            self.assertIs(self.app.focused, screen.query_one("#messages"))
+            await pilot.press("escape")
+            self.assertFalse(screen.has_class("focus-mode"))
+            self.assertIs(self.app.focused, message_input)
+            self.assertEqual(message_input.value, "half a thought")
+
+    async def test_sends_keep_focus_on_the_log(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            await screen.submit_message("/focus")
+            screen.focus_input()
+            self.assertIs(self.app.focused, screen.query_one("#messages"))
+
+
+class ThreadComposerTest(ChatScreenTest):
+    async def test_replies_answer_the_root(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            await screen.handle_message({"type": "message", "username": "bob", "content": "root", "id": 100})
+            await screen.handle_message({"type": "message", "username": "carol", "content": "reply", "id": 101, "reply_to": 100})
+            await screen.handle_message({"type": "message", "username": "bob", "content": "deeper", "id": 102, "reply_to": 101})
+            screen.open_thread("")
+            await wait_until(pilot, lambda: self.app.screen is screen.thread_view and self.app.screen.query("#thread_input"))
+            thread_input = self.app.screen.query_one("#thread_input")
+            self.assertIs(self.app.focused, thread_input)
+            thread_input.value = "me too"
+            await pilot.press("enter")
+            await wait_until(pilot, lambda: self.server.transport.sent_of_type("message"))
+            sent = self.server.transport.sent_of_type("message")[-1]
+            self.assertEqual((sent["content"], sent["reply_to"]), ("me too", 100))
+            self.assertEqual(screen.thread_view.thread[-1]["content"], "me too")
+            self.assertIs(self.app.screen, screen.thread_view)
+
+    async def test_no_composer_without_a_root_id(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            # A reply to something no longer in the log, with no id of its own to answer
+            await screen.handle_message({"type": "message", "username": "bob", "content": "no id", "reply_to": 42})
+            screen.open_thread("")
+            await wait_until(pilot, lambda: self.app.screen is screen.thread_view)
+            self.assertFalse(self.app.screen.query("#thread_input"))
+
+
+class MarkupInRoomNameTest(unittest.IsolatedAsyncioTestCase):
+    async def test_join_is_not_turned_into_a_failure(self):
+        isolate_config(self)
+        server = MockServer()
+        app = RoomApp(server, chat="[/b]club")
+        async with app.run_test() as pilot:
+            screen = await joined(pilot)
+            self.assertFalse(server.transport.is_closed)
+            self.assertTrue(any("[/b]club" in line.text for line in screen.query_one("#messages").lines))
+
+
+if __name__ == "__main__":
+    unittest.main()
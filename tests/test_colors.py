@@ -0,0 +1,51 @@
+import unittest
+from types import SimpleNamespace
+
+import termchat
+
+
+class ParseUserColorTest(unittest.TestCase):
+    def test_normalized(self):
+        self.assertEqual(termchat.parse_user_color("#ff8800"), "#FF8800")
+        self.assertEqual(termchat.parse_user_color("Bright_Cyan"), "bright_cyan")
+
+    def test_not_colors(self):
+        for value in ("#ff88", "blurple", ""):
+            with self.subTest(value=value):
+                self.assertIsNone(termchat.parse_user_color(value))
+
+
+class UserColorTest(unittest.TestCase):
+    def setUp(self):
+        # Just the state get_user_color reads
+        self.app = SimpleNamespace(settings={"color_overrides": {}}, user_colors={}, color_index=0,
+                                   background_color="#000000", readable_colors={})
+
+    def color(self, username: str) -> str:
+        return termchat.TermchatApp.get_user_color(self.app, username)
+
+    def test_assigned_in_palette_order_and_kept(self):
+        first = self.color("bob")
+        self.color("carol")
+        self.assertEqual(self.color("bob"), first)
+        self.assertEqual(self.app.user_colors, {"bob": termchat.USER_COLORS[0], "carol": termchat.USER_COLORS[1]})
+
+    def test_override_beats_the_assigned_color(self):
+        assigned = self.color("bob")
+        self.app.settings["color_overrides"]["bob"] = "#FF8800"
+        self.assertEqual(self.color("Bob"), "#FF8800")
+        self.app.settings["color_overrides"].clear()
+        self.assertEqual(self.color("bob"), assigned)
+
+    def test_server_is_never_overridden(self):
+        self.app.settings["color_overrides"]["server"] = "#FF8800"
+        self.assertEqual(self.color("Server"), "bold #87CEEB")
+
+    def test_override_used_as_picked_on_any_background(self):
+        self.app.background_color = "#FFFFFF"
+        self.app.settings["color_overrides"]["bob"] = "yellow"
+        self.assertEqual(self.color("bob"), "yellow")
+
+
+if __name__ == "__main__":
+    unittest.main()
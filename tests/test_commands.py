@@ -0,0 +1,81 @@
+import os
+import re
+import unittest
+
+from textual.command import CommandPalette
+
+import termchat
+from support import MockServer, RoomApp, isolate_config, joined
+
+README = os.path.join(os.path.dirname(os.path.dirname(os.path.abspath(__file__))), "README.md")
+
+
+class FindCommandTest(unittest.TestCase):
+    def test_names_and_aliases(self):
+        self.assertEqual(termchat.find_command("/clear").name, "clear")
+        self.assertEqual(termchat.find_command("/C").name, "clear")
+        self.assertEqual(termchat.find_command("/colour bob red").name, "color")
+
+    def test_chat_messages(self):
+        for text in ("hello", " /clear", "/nosuchcommand", "/clear the air"):
+            with self.subTest(text=text):
+                self.assertIsNone(termchat.find_command(text))
+
+
+class ServerCommandTest(unittest.TestCase):
+    def test_unknown_commands_go_to_the_server(self):
+        self.assertEqual(termchat.server_command("/Kick bob  spam"), {"type": "command", "name": "kick", "args": ["bob", "spam"]})
+        self.assertEqual(termchat.server_command("/motd"), {"type": "command", "name": "motd", "args": []})
+        self.assertEqual(termchat.server_command("/set-topic a\nb")["args"], ["a", "b"])
+
+    def test_ours_and_non_commands_are_not(self):
+        for text in ("/clear", "/Q", "/colour bob", "hello", "/", "/ kick", "/1up"):
+            with self.subTest(text=text):
+                self.assertIsNone(termchat.server_command(text))
+
+
+class RegistryTest(unittest.TestCase):
+    def test_names_and_aliases_are_unique(self):
+        words = [word for spec in termchat.COMMANDS for word in (spec.name, *spec.aliases)]
+        self.assertEqual(len(words), len(set(words)))
+
+    def test_commands_needing_arguments_say_which(self):
+        for spec in termchat.COMMANDS:
+            if spec.needs_args:
+                with self.subTest(command=spec.name):
+                    self.assertTrue(spec.arg_hint)
+
+    def test_every_command_is_documented(self):
+        with open(README, encoding="utf-8") as f:
+            documented = set(re.findall(r"`/([a-z!-]+)", f.read()))
+        for spec in termchat.COMMANDS:
+            with self.subTest(command=spec.name):
+                self.assertIn(spec.name, documented)
+
+    def test_palette_actions_exist(self):
+        for _label, _shortcut, action in termchat.PALETTE_ACTIONS:
+            with self.subTest(action=action):
+                self.assertTrue(hasattr(termchat.ChatScreen, f"action_{action}") or hasattr(termchat.TermchatApp, f"action_{action}"))
+
+
+class PaletteTest(unittest.IsolatedAsyncioTestCase):
+    def setUp(self):
+        isolate_config(self)
+        self.app = RoomApp(MockServer())
+
+    async def test_opens_with_f1(self):
+        async with self.app.run_test() as pilot:
+            await joined(pilot)
+            await pilot.press("f1")
+            await pilot.pause()
+            self.assertIsInstance(self.app.screen, CommandPalette)
+
+    async def test_commands_with_arguments_are_left_to_finish(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            await screen.run_palette_command(termchat.find_command("/go general"))
+            self.assertEqual(screen.get_draft(), "/go ")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,73 @@
+import copy
+import unittest
+
+import termchat
+
+
+def settings_with(**changes) -> dict:
+    settings = copy.deepcopy(termchat.DEFAULT_SETTINGS)
+    settings.update(changes)
+    return settings
+
+
+class ConfigBundleTest(unittest.TestCase):
+    def test_round_trip(self):
+        settings = settings_with(
+            enter_sends=False,
+            profiles=[termchat.make_profile("work", "wss://chat.example.com", "team", "alice")],
+            recent_rooms=[{"server": "wss://chat.example.com", "chat": "team", "last_joined": 1700000000.0}],
+            ignored=["spammer"],
+            color_overrides={"bob": "#FF8800"},
+        )
+        imported, skipped = termchat.import_config(termchat.export_config(settings), termchat.DEFAULT_SETTINGS, replace=True)
+        self.assertEqual(imported, settings)
+        self.assertEqual(skipped, [])
+
+    def test_users_named_like_secrets_survive(self):
+        settings = settings_with(color_overrides={"token": "#FF8800", "password": "red"}, trusted_keys={"token": "a2V5"})
+        imported, skipped = termchat.import_config(termchat.export_config(settings), termchat.DEFAULT_SETTINGS, replace=True)
+        self.assertEqual(imported, settings)
+        self.assertEqual(skipped, [])
+
+    def test_secrets_never_leave(self):
+        settings = settings_with(profiles=[{**termchat.make_profile("work", "wss://chat.example.com"), "password": "hunter2"}])
+        self.assertNotIn("hunter2", repr(termchat.export_config(settings)))
+
+    def test_merge_keeps_what_the_bundle_leaves_out(self):
+        current = settings_with(enter_sends=False, ignored=["spammer"])
+        bundle = {"termchat_config": 1, "settings": {"ignored": ["troll"]}}
+        merged, _ = termchat.import_config(bundle, current, replace=False)
+        self.assertEqual((merged["enter_sends"], merged["ignored"]), (False, ["troll"]))
+        replaced, _ = termchat.import_config(bundle, current, replace=True)
+        self.assertEqual(replaced["enter_sends"], termchat.DEFAULT_SETTINGS["enter_sends"])
+
+    def test_broken_entries_are_skipped_one_by_one(self):
+        bundle = {"termchat_config": 1, "settings": {
+            "profiles": [
+                termchat.make_profile("work", "wss://chat.example.com"),
+                {"name": "no url"},
+                {"name": "http", "url": "http://example.com"},
+                {"name": 5, "url": "wss://example.com"},
+            ],
+            "recent_rooms": [{"server": "wss://a.example", "chat": "x", "last_joined": "yesterday"}],
+            "ignored": ["ok", "", 7],
+            "color_overrides": {"bob": "red", "eve": 3},
+            "made_up": True,
+            "enter_sends": "yes",
+        }}
+        settings, skipped = termchat.import_config(bundle, termchat.DEFAULT_SETTINGS, replace=True)
+        self.assertEqual([profile["name"] for profile in settings["profiles"]], ["work"])
+        self.assertEqual(settings["recent_rooms"], [])
+        self.assertEqual(settings["ignored"], ["ok"])
+        self.assertEqual(settings["color_overrides"], {"bob": "red"})
+        self.assertCountEqual(skipped, ["profiles[1]", "profiles[2]", "profiles[3]", "recent_rooms[0]",
+                                        "ignored[1]", "ignored[2]", "color_overrides[eve]", "made_up", "enter_sends"])
+
+    def test_not_a_bundle(self):
+        for bundle in ([], {"settings": {}}, {"termchat_config": True, "settings": {}}, {"termchat_config": 99, "settings": {}}):
+            with self.subTest(bundle=bundle), self.assertRaises(ValueError):
+                termchat.import_config(bundle, termchat.DEFAULT_SETTINGS, replace=False)
+
+
+if __name__ == "__main__":
+    unittest.main()
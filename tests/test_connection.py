@@ -0,0 +1,404 @@
+import asyncio
+import unittest
+from unittest import mock
+
+import termchat
+from support import MockServer, RoomApp, fast_reconnects, isolate_config, join_echo, joined, wait_until
+
+
+class ConnectionTest(unittest.IsolatedAsyncioTestCase):
+    def setUp(self):
+        isolate_config(self)
+        self.server = MockServer()
+        fast_reconnects(self, self.server)
+        self.app = RoomApp(self.server)
+
+
+class ReadSideClosedTest(ConnectionTest):
+    async def test_state_resets_and_nothing_more_is_sent(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            transport = self.server.transport
+            transport.drop(1000, "bye")
+            await wait_until(pilot, lambda: screen.connection_state == "disconnected")
+            self.assertFalse(self.app.connected)
+            self.assertIsNone(self.app.transport)
+            sent = len(transport.sent)
+            await screen.send_message("anyone there?")
+            self.assertEqual(len(transport.sent), sent)
+
+
+class HeldUntilJoinedTest(ConnectionTest):
+    def setUp(self):
+        super().setUp()
+        self.server.confirm_joins = False
+
+    async def joining(self, pilot):
+        await wait_until(pilot, lambda: self.server.transports and self.server.transport.sent)
+        return self.app.screen
+
+    async def test_sent_once_the_join_is_confirmed(self):
+        async with self.app.run_test() as pilot:
+            screen = await self.joining(pilot)
+            await screen.send_message("early")
+            self.assertEqual(self.server.transport.sent_of_type("message"), [])
+            self.server.transport.push(join_echo(self.server.transport.sent[0]))
+            await wait_until(pilot, lambda: self.server.transport.sent_of_type("message"))
+            self.assertEqual(self.server.transport.sent_of_type("message")[0]["content"], "early")
+
+    async def test_never_sent_when_the_join_fails(self):
+        async with self.app.run_test() as pilot:
+            screen = await self.joining(pilot)
+            await screen.send_message("early")
+            transport = self.server.transport
+            transport.push({"type": "error", "message": "Wrong password"})
+            await wait_until(pilot, lambda: not screen.joining)
+            self.assertEqual(screen.pending_sends, [])
+            self.assertEqual(transport.sent_of_type("message"), [])
+
+
+class RosterTest(ConnectionTest):
+    async def test_snapshot_replaces_the_roster(self):
+        respond = self.server.respond
+        def with_snapshot(transport, data):
+            replies = respond(transport, data)
+            if data.get("type") == "join":
+                replies.insert(0, {"type": "roster", "users": ["bob", "carol"]})
+            return replies
+        self.server.respond = with_snapshot
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            self.assertEqual(screen.roster, {"alice", "bob", "carol"})
+            self.assertFalse(screen.roster_updating)
+
+    async def test_incremental_rebuild_after_reconnect(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            self.assertTrue(screen.roster_updating)  # No snapshot, so joins fill it in
+            self.server.transport.push({"type": "join", "username": "bob"})
+            await wait_until(pilot, lambda: "bob" in screen.roster)
+            self.server.transport.drop(1006)
+            await wait_until(pilot, lambda: len(self.server.transports) == 2 and self.app.connected)
+            self.assertEqual(screen.roster, {"alice"})  # bob may have left during the outage
+            self.server.transport.push({"type": "join", "username": "carol"})
+            await wait_until(pilot, lambda: "carol" in screen.roster)
+            self.assertEqual(screen.roster, {"alice", "carol"})
+
+
+class ServerDisconnectTest(ConnectionTest):
+    async def disconnected(self, pilot, **fields):
+        screen = await joined(pilot)
+        self.server.transport.push({"type": "disconnect", "reason": "maintenance", **fields})
+        await wait_until(pilot, lambda: screen.connection_state == "disconnected")
+        return screen
+
+    async def test_scheduled_reconnect_happens_once(self):
+        async with self.app.run_test() as pilot:
+            await self.disconnected(pilot, reconnect_after_ms=50)
+            await wait_until(pilot, lambda: self.app.connected)
+            await pilot.pause(0.2)
+            self.assertEqual(len(self.server.transports), 2)  # The close that followed didn't add another
+
+    async def test_cancelled_by_the_user(self):
+        async with self.app.run_test() as pilot:
+            screen = await self.disconnected(pilot, reconnect_after_ms=300)
+            self.assertIsNotNone(screen.reconnect_timer)
+            await screen.submit_message("/reconnect cancel")
+            self.assertIsNone(screen.reconnect_timer)
+            await pilot.pause(0.5)
+            self.assertEqual(len(self.server.transports), 1)
+
+    async def test_no_reconnect_unless_asked(self):
+        async with self.app.run_test() as pilot:
+            screen = await self.disconnected(pilot)
+            await pilot.pause(0.2)
+            self.assertIsNone(screen.reconnect_timer)
+            self.assertEqual(len(self.server.transports), 1)
+
+
+class RoomInfoTimeoutTest(ConnectionTest):
+    async def test_unavailable_without_a_reply(self):
+        with mock.patch.object(termchat, "ROOM_INFO_TIMEOUT", 0.05):
+            async with self.app.run_test() as pilot:
+                screen = await joined(pilot)
+                await screen.request_room_info()
+                self.assertEqual(self.server.transport.sent_of_type("room_info"), [{"type": "room_info"}])
+                unavailable = termchat.sys_msg("room_info_unavailable")
+                await wait_until(pilot, lambda: any(unavailable in entry["markup"] for entry in screen.entries))
+
+
+class ShutdownTest(ConnectionTest):
+    async def test_safe_to_call_twice(self):
+        async with self.app.run_test() as pilot:
+            await joined(pilot)
+            transport = self.server.transport
+            with mock.patch.object(transport, "close", wraps=transport.close) as close:
+                await self.app.shutdown()
+                await self.app.shutdown()
+            close.assert_called_once()
+            self.assertIsNone(self.app.transport)
+            self.assertFalse(self.app.connected)
+
+
+class AutoReconnectTest(ConnectionTest):
+    async def test_dropped_connection_reconnects(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            self.server.transport.drop(1006)
+            await wait_until(pilot, lambda: len(self.server.transports) == 2 and self.app.connected)
+            self.assertEqual(screen.connection_state, "connected")
+
+    async def test_policy_close_stays_disconnected(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            self.server.transport.drop(1008, "banned")
+            await wait_until(pilot, lambda: screen.connection_state == "disconnected")
+            await pilot.pause(0.1)
+            self.assertEqual(len(self.server.transports), 1)
+            self.assertIsNone(screen.reconnect_timer)
+            self.assertTrue(any(termchat.sys_msg("no_auto_reconnect") in entry["markup"] for entry in screen.entries))
+
+    async def test_rejected_rejoin_stops_retrying(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            self.server.respond = lambda transport, data: [{"type": "error", "message": "Wrong password"}] if data.get("type") == "join" else []
+            self.server.transport.drop(1006)
+            await wait_until(pilot, lambda: len(self.server.transports) == 2 and not screen.reconnecting)
+            await pilot.pause(0.1)
+            self.assertEqual(len(self.server.transports), 2)
+            self.assertIsNone(screen.reconnect_timer)
+            self.assertTrue(self.server.transport.is_closed)
+
+    async def test_refused_join_closes_the_socket(self):
+        self.server.respond = lambda transport, data: [{"type": "auth_failed", "message": "Wrong password"}] if data.get("type") == "join" else []
+        async with self.app.run_test() as pilot:
+            await wait_until(pilot, lambda: self.server.transports and self.server.transport.sent)
+            await wait_until(pilot, lambda: self.server.transport.is_closed)
+            self.assertIsNone(self.app.transport)
+            self.assertFalse(self.app.connected)
+
+
+class BacklogTest(ConnectionTest):
+    def setUp(self):
+        super().setUp()
+        self.server.confirm_joins = False
+
+    async def test_written_in_order_in_one_batch(self):
+        async with self.app.run_test() as pilot:
+            await wait_until(pilot, lambda: self.server.transports and self.server.transport.sent)
+            screen = self.app.screen
+            transport = self.server.transport
+            count = termchat.BACKLOG_BATCH_THRESHOLD + 5
+            for number in range(count):
+                transport.push({"type": "message", "username": "bob", "content": f"old {number}", "id": number})
+            with mock.patch.object(self.app, "batch_update", wraps=self.app.batch_update) as batch_update:
+                transport.push(join_echo(transport.sent[0]))
+                await joined(pilot)
+            self.assertTrue(batch_update.called)
+            contents = [entry["content"] for entry in screen.entries if entry.get("username") == "bob"]
+            self.assertEqual(contents, [f"old {number}" for number in range(count)])
+            await pilot.pause()
+            self.assertTrue(screen.log_pinned())
+
+
+class SessionTokenTest(ConnectionTest):
+    def setUp(self):
+        super().setUp()
+        self.accept_token = True
+        server_respond = self.server.respond
+
+        def respond(transport, data):
+            if data.get("type") != "join":
+                return server_respond(transport, data)
+            transport.username = data["username"]
+            if "session_token" in data and not self.accept_token:
+                return [{"type": "error", "message": "Session expired"}]
+            return [join_echo(data, session_token="tok-1")]
+        self.server.respond = respond
+
+    def joins(self) -> list:
+        return [transport.sent_of_type("join")[0] for transport in self.server.transports if transport.sent]
+
+    async def test_reconnect_presents_the_token(self):
+        async with self.app.run_test() as pilot:
+            await joined(pilot)
+            self.server.transport.drop(1006)
+            await wait_until(pilot, lambda: len(self.server.transports) == 2 and self.app.connected)
+            first, second = self.joins()
+            self.assertEqual(first["password"], "secret")
+            self.assertEqual(second["session_token"], "tok-1")
+            self.assertNotIn("password", second)
+
+    async def test_rejected_token_falls_back_to_the_password(self):
+        self.accept_token = False
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            self.server.transport.drop(1006)
+            await wait_until(pilot, lambda: len(self.server.transports) == 3 and self.app.connected)
+            self.assertEqual(self.joins()[2]["password"], "secret")
+            self.assertEqual(screen.session_token, "tok-1")
+
+
+class OutboxTest(ConnectionTest):
+    async def lose_send(self, pilot, screen, content: str):
+        # The socket dies between the listener's reads, so the send is the first to find out
+        transport = self.server.transport
+        transport.is_closed, transport.close_code, transport.close_reason = True, 1006, ""
+        await screen.send_message(content)
+        transport.drop(1006)
+
+    async def test_sent_once_reconnected(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            await self.lose_send(pilot, screen, "lost")
+            self.assertEqual([item["content"] for item in screen.outbox], ["lost"])
+            await wait_until(pilot, lambda: len(self.server.transports) == 2 and not screen.outbox)
+            self.assertEqual(self.server.transport.sent_of_type("message")[0]["content"], "lost")
+
+    async def test_failing_again_leaves_a_failed_line(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            server_respond = self.server.respond
+
+            def respond(transport, data):
+                if data.get("type") == "message":
+                    raise ConnectionResetError("still down")
+                return server_respond(transport, data)
+            self.server.respond = respond
+            await self.lose_send(pilot, screen, "lost")
+            await wait_until(pilot, lambda: len(self.server.transports) == 2 and not screen.outbox)
+            entry = next(entry for entry in screen.entries if entry.get("content") == "lost")
+            self.assertEqual(entry["state"], "failed")
+
+
+class OutboxBarTest(ConnectionTest):
+    async def queue(self, screen, *contents) -> list:
+        # Messages left over from a failed flush, each with its pending line in the log
+        entries = []
+        for content in contents:
+            entry = screen.write_chat_message(screen.username, content, state="pending")
+            screen.queue_outbox({"content": content, "attempts": 1, "entry": entry, "reply_to": None})
+            entries.append(entry)
+        return entries
+
+    def sizes(self, events) -> list:
+        return [item["size"] for item in (events.get_nowait() for _ in range(events.qsize())) if item["event"] == "outbox"]
+
+    async def test_size_reported_on_every_change(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            events = self.app.events.subscribe()
+            await self.queue(screen, "one", "two")
+            bar = screen.query_one("#outbox_bar")
+            self.assertTrue(bar.has_class("open"))
+            await screen.submit_message("/outbox discard")
+            self.assertEqual(self.sizes(events), [1, 2, 0])
+            self.assertFalse(bar.has_class("open"))
+
+    async def test_retry_all_sends_in_order(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            await self.queue(screen, "one", "two", "three")
+            await screen.submit_message("/outbox retry")
+            self.assertEqual([data["content"] for data in self.server.transport.sent_of_type("message")], ["one", "two", "three"])
+            self.assertEqual(screen.outbox, [])
+
+    async def test_discard_all_keeps_the_lines_as_failed(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            entries = await self.queue(screen, "one", "two")
+            await screen.submit_message("/outbox discard")
+            self.assertEqual(screen.outbox, [])
+            self.assertEqual([entry["state"] for entry in entries], ["failed", "failed"])
+            self.assertEqual(self.server.transport.sent_of_type("message"), [])
+            self.assertTrue(all(entry in screen.entries for entry in entries))
+
+
+class JoinTimeoutTest(ConnectionTest):
+    def setUp(self):
+        super().setUp()
+        self.server.confirm_joins = False
+        self.app.settings["join_timeout_seconds"] = 1
+
+    async def test_other_frames_do_not_extend_the_deadline(self):
+        async with self.app.run_test() as pilot:
+            await wait_until(pilot, lambda: self.server.transports and self.server.transport.sent)
+            screen = self.app.screen
+            transport = self.server.transport
+            for _ in range(3):
+                transport.push({"type": "roster", "users": ["bob"]})
+                await pilot.pause(0.4)
+            await wait_until(pilot, lambda: not screen.joining)
+            self.assertTrue(transport.is_closed)
+            self.assertFalse(self.app.connected)
+            self.assertTrue(any("Server did not confirm join" in entry["markup"] for entry in screen.entries))
+
+
+class ScrollKeptTest(ConnectionTest):
+    async def test_reading_position_survives_a_reconnect(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            for number in range(60):
+                self.server.transport.push({"type": "message", "username": "bob", "content": f"line {number}", "id": number})
+            await wait_until(pilot, lambda: sum(entry.get("username") == "bob" for entry in screen.entries) == 60)
+            messages_log = screen.query_one("#messages")
+            await pilot.pause()
+            messages_log.scroll_to(y=0, animate=False)
+            await pilot.pause()
+            self.assertFalse(screen.log_pinned())
+            self.server.transport.drop(1006)
+            await wait_until(pilot, lambda: len(self.server.transports) == 2 and self.app.connected)
+            await pilot.pause()
+            self.assertEqual(messages_log.scroll_y, 0)
+            self.assertTrue(any(entry["kind"] == "divider" and "reconnected" in entry["markup"] for entry in screen.entries))
+
+
+class SendRaceTest(ConnectionTest):
+    async def test_send_after_the_server_closed_but_before_the_listener_noticed(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            transport = self.server.transport
+            transport.is_closed, transport.close_code, transport.close_reason = True, 1001, "going away"
+            await screen.send_message("first")
+            self.assertEqual(screen.connection_state, "disconnected")
+            self.assertIsNone(self.app.transport)
+            await screen.send_message("second")
+            self.assertEqual([item["content"] for item in screen.outbox], ["first"])
+            summaries = [entry for entry in screen.entries if "Session ended" in entry["markup"]]
+            self.assertEqual(len(summaries), 1)
+            transport.drop(1001, "going away")
+            await wait_until(pilot, lambda: len(self.server.transports) == 2 and not screen.outbox)
+            self.assertEqual(self.server.transport.sent_of_type("message")[0]["content"], "first")
+
+
+class BackgroundReconnectTest(ConnectionTest):
+    async def test_cancel_while_a_reconnect_is_connecting(self):
+        attempts = []
+
+        async def hang(url, **options):
+            attempts.append(url)
+            await asyncio.Event().wait()
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            self.app.connect_transport = hang
+            self.server.transport.drop(1006)
+            await wait_until(pilot, lambda: attempts and screen.joining)
+            await screen.submit_message("/reconnect cancel")
+            self.assertFalse(screen.joining)
+            self.assertFalse(screen.reconnecting)
+            self.assertEqual(screen.connection_state, "disconnected")
+            self.assertIn(termchat.sys_msg("reconnect_cancelled"), screen.entries[-1]["markup"])
+
+    async def test_switch_rooms_while_waiting_for_the_network(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            self.server.reachable = False
+            self.server.transport.drop(1006)
+            await wait_until(pilot, lambda: screen.waiting_for_network)
+            await screen.go_to_room("random")
+            await wait_until(pilot, lambda: getattr(self.app.screen, "chat_name", None) == "random" and self.app.connected)
+            self.assertEqual(self.server.transport.sent_of_type("join")[0]["chatname"], "random")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,32 @@
+import unittest
+
+import termchat
+
+
+class EffectiveCooldownTest(unittest.TestCase):
+    def test_strictest_limit_wins(self):
+        self.assertEqual(termchat.effective_cooldown(5.0, 2.0), 5.0)
+        self.assertEqual(termchat.effective_cooldown(0.0, 12.5), 12.5)
+
+    def test_nothing_or_expired_limits(self):
+        self.assertEqual(termchat.effective_cooldown(), 0.0)
+        self.assertEqual(termchat.effective_cooldown(0.0, -3.0), 0.0)
+
+
+class RetryAfterTest(unittest.TestCase):
+    def test_milliseconds_to_seconds(self):
+        self.assertEqual(termchat.parse_retry_after({"type": "rate_limited", "retry_after_ms": 2500}), 2.5)
+        self.assertEqual(termchat.parse_retry_after({"retry_after_ms": 0}), 0.0)
+
+    def test_capped(self):
+        self.assertEqual(termchat.parse_retry_after({"retry_after_ms": 10**9}), termchat.MAX_RATE_LIMIT_WAIT)
+
+    def test_missing_or_bad_values_use_the_default(self):
+        for value in (None, "2500", -1, True, [1]):
+            with self.subTest(value=value):
+                self.assertEqual(termchat.parse_retry_after({"retry_after_ms": value}), termchat.RATE_LIMIT_DEFAULT)
+        self.assertEqual(termchat.parse_retry_after({}), termchat.RATE_LIMIT_DEFAULT)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,104 @@
+import unittest
+
+import termchat
+
+
+class ThroughputTest(unittest.TestCase):
+    def test_rate_over_the_window(self):
+        window = termchat.ThroughputWindow(window=10.0)
+        window.add(500, now=100.0)
+        window.add(1500, now=105.0)
+        self.assertEqual(window.rate(now=106.0), 200.0)
+        self.assertEqual(window.total, 2000)
+
+    def test_old_samples_drop_out_but_stay_in_the_total(self):
+        window = termchat.ThroughputWindow(window=10.0)
+        window.add(1000, now=100.0)
+        window.add(100, now=111.0)
+        self.assertEqual(window.rate(now=111.0), 10.0)
+        self.assertEqual(window.rate(now=200.0), 0.0)
+        self.assertEqual(window.total, 1100)
+
+    def test_frame_size_counts_utf8_bytes(self):
+        self.assertEqual(termchat.frame_size("hé"), 3)
+        self.assertEqual(termchat.frame_size(b"\x00\x01"), 2)
+
+    def test_format_bytes(self):
+        self.assertEqual(termchat.format_bytes(12), "12 B")
+        self.assertEqual(termchat.format_bytes(1536), "1.5 KB")
+        self.assertEqual(termchat.format_bytes(3 * 1024 * 1024), "3.0 MB")
+
+
+class HealthMonitorTest(unittest.TestCase):
+    def test_fast_pongs_are_good(self):
+        health = termchat.HealthMonitor()
+        self.assertEqual(health.pong(0.05), "good")
+        self.assertEqual(health.rtt, 0.05)
+
+    def test_a_slow_pong_or_one_miss_is_degraded(self):
+        health = termchat.HealthMonitor()
+        self.assertEqual(health.pong(termchat.DEGRADED_RTT + 0.1), "degraded")
+        self.assertEqual(termchat.HealthMonitor().miss(), "degraded")
+
+    def test_two_misses_in_a_row_are_lost(self):
+        health = termchat.HealthMonitor()
+        health.miss()
+        self.assertEqual(health.pong(0.05), "good")
+        health.miss()
+        self.assertEqual(health.miss(), "lost")
+
+
+class RedactionTest(unittest.TestCase):
+    def test_mask_secrets_at_any_depth(self):
+        data = {"type": "join", "password": "pw", "nested": [{"token": "tk", "content": "hi"}]}
+        self.assertEqual(termchat.mask_secrets(data), {"type": "join", "password": "[redacted]", "nested": [{"token": "[redacted]", "content": "hi"}]})
+        self.assertEqual(data["password"], "pw")
+
+    def test_redact_also_hides_message_text(self):
+        data = {"event": "server", "data": {"type": "message", "content": "private words", "session_token": "abc"}}
+        self.assertEqual(termchat.redact(data)["data"], {"type": "message", "content": "[13 chars]", "session_token": "[redacted]"})
+
+    def test_report_holds_no_secrets_or_text(self):
+        events = termchat.EventStream()
+        events.publish("sent", data={"type": "join", "username": "alice", "password": "hunter2"})
+        events.publish("server", data={"type": "message", "username": "bob", "content": "my address is 1 Main St"})
+        settings = {**termchat.DEFAULT_SETTINGS, "e2e_passphrase": "swordfish"}
+        report = termchat.format_diagnostic_report(["Status: connected"], settings, list(events.recent))
+        for secret in ("hunter2", "swordfish", "Main St"):
+            self.assertNotIn(secret, report)
+        self.assertIn("Status: connected", report)
+        self.assertIn("alice", report)
+        self.assertEqual(len(events.recent), 2)
+
+
+class RttSparklineTest(unittest.TestCase):
+    def test_history_is_bounded(self):
+        history = termchat.RttHistory(size=3)
+        for number in range(5):
+            history.add(number / 100, now=float(number))
+        self.assertEqual([rtt for _, rtt in history.samples], [0.02, 0.03, 0.04])
+
+    def test_one_gap_per_outage(self):
+        history = termchat.RttHistory()
+        history.gap(now=1.0)
+        history.add(0.05, now=2.0)
+        history.gap(now=3.0)
+        history.gap(now=4.0)
+        self.assertEqual(list(history.samples), [(2.0, 0.05), (3.0, None)])
+
+    def test_points_scaled_between_fastest_and_slowest(self):
+        samples = [(0, 0.25), (1, None), (2, 0.75), (3, 0.5)]
+        self.assertEqual(termchat.sparkline_points(samples), [0.0, None, 1.0, 0.5])
+        self.assertEqual(termchat.sparkline_points([(0, 0.03), (1, 0.03)]), [0.5, 0.5])
+        self.assertEqual(termchat.sparkline_points([(0, None)]), [None])
+
+    def test_format(self):
+        termchat.use_glyphs(termchat.Glyphs.unicode())
+        self.assertEqual(termchat.format_sparkline([0.0, None, 0.5, 1.0]), "▁ ▅█")
+        termchat.use_glyphs(termchat.Glyphs.ascii())
+        self.addCleanup(termchat.use_glyphs, termchat.Glyphs.unicode())
+        self.assertEqual(termchat.format_sparkline([0.0, 1.0]), "_#")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,37 @@
+import unittest
+
+import termchat
+
+
+class EncryptionTest(unittest.TestCase):
+    key = termchat.derive_e2e_key("hunter2", "general")
+
+    def test_round_trip(self):
+        sealed = termchat.encrypt_content(self.key, "meet at noon ☕")
+        self.assertTrue(sealed.startswith(termchat.E2E_PREFIX))
+        self.assertNotIn("noon", sealed)
+        self.assertEqual(termchat.display_content(self.key, sealed), "meet at noon ☕")
+
+    def test_keys_differ_by_passphrase_and_room(self):
+        self.assertNotEqual(self.key, termchat.derive_e2e_key("hunter3", "general"))
+        self.assertNotEqual(self.key, termchat.derive_e2e_key("hunter2", "random"))
+
+    def test_wrong_or_missing_key(self):
+        sealed = termchat.encrypt_content(self.key, "secret")
+        other = termchat.derive_e2e_key("hunter2", "random")
+        self.assertIsNone(termchat.decrypt_content(other, sealed))
+        self.assertIn("wrong key", termchat.display_content(other, sealed))
+        self.assertIn("no key", termchat.display_content(None, sealed))
+        self.assertIn("wrong key", termchat.display_content(self.key, termchat.E2E_PREFIX + "not base64!"))
+
+    def test_plain_content_is_left_alone(self):
+        self.assertEqual(termchat.display_content(self.key, "hello"), "hello")
+
+    def test_wire_length_matches_the_ciphertext(self):
+        for content in ("", "hi", "x" * 100, "héllo ☕"):
+            self.assertEqual(termchat.wire_length(self.key, content), len(termchat.encrypt_content(self.key, content)))
+        self.assertEqual(termchat.wire_length(None, "héllo"), 5)
+
+
+if __name__ == "__main__":
+    unittest.main()
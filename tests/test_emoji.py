@@ -0,0 +1,47 @@
+import unittest
+
+import termchat
+
+
+class ShortcodeTest(unittest.TestCase):
+    def test_built_ins(self):
+        self.assertEqual(termchat.expand_shortcodes("gg :tada: :+1:"), "gg 🎉 👍")
+        self.assertEqual(termchat.expand_shortcodes(":not_an_emoji:"), ":not_an_emoji:")
+
+    def test_server_codes_expand_alongside_built_ins(self):
+        custom = termchat.merge_custom_emoji({":partyparrot:": "🦜", "tada": "🥳", "logo": "https://example.com/logo.png"})
+        self.assertEqual(custom, {"partyparrot": "🦜", "logo": "https://example.com/logo.png"})  # Built-ins can't be replaced
+        self.assertEqual(termchat.expand_shortcodes(":partyparrot: :tada:", custom), "🦜 🎉")
+
+    def test_image_codes_stay_as_text(self):
+        custom = termchat.merge_custom_emoji({"logo": "https://example.com/logo.png"})
+        self.assertEqual(termchat.expand_shortcodes(":logo:", custom), ":logo:")
+
+    def test_merge_skips_bad_entries(self):
+        self.assertEqual(termchat.merge_custom_emoji({"::": "x", "num": 5, "Shout": "📣"}), {"shout": "📣"})
+
+
+class SearchEmojiTest(unittest.TestCase):
+    def test_aliases_give_one_result(self):
+        self.assertEqual(termchat.search_emoji("thumbsup"), [("👍", "thumbsup")])
+        self.assertEqual(termchat.search_emoji(":+1:"), [("👍", "thumbsup")])
+
+    def test_substring_and_case(self):
+        names = [name for _, name in termchat.search_emoji("HEART")]
+        self.assertIn("heart", names)
+        self.assertIn("heart_eyes", names)
+
+    def test_category(self):
+        results = termchat.search_emoji("", category="food")
+        self.assertEqual([name for _, name in results], termchat.EMOJI_CATEGORIES["food"])
+        self.assertEqual(termchat.search_emoji("pizza", category="smileys"), [])
+
+    def test_custom_codes_in_all_only(self):
+        custom = {"partyparrot": "🦜", "logo": "https://example.com/logo.png"}
+        self.assertEqual(termchat.search_emoji("party", custom=custom), [("🦜", "partyparrot")])
+        self.assertEqual(termchat.search_emoji("logo", custom=custom), [])
+        self.assertEqual(termchat.search_emoji("party", category="symbols", custom=custom), [])
+
+
+if __name__ == "__main__":
+    unittest.main()
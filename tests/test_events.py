@@ -0,0 +1,67 @@
+import unittest
+
+import termchat
+
+
+class EventStreamTest(unittest.TestCase):
+    def drain(self, queue) -> list:
+        return [queue.get_nowait()["event"] for _ in range(queue.qsize())]
+
+    def test_every_subscriber_gets_every_event(self):
+        events = termchat.EventStream()
+        first, second = events.subscribe(), events.subscribe()
+        events.publish("connected", chat="general")
+        events.publish("message", content="hi")
+        self.assertEqual(self.drain(first), ["connected", "message"])
+        self.assertEqual(self.drain(second), ["connected", "message"])
+
+    def test_unsubscribed_queues_stop_receiving(self):
+        events = termchat.EventStream()
+        first, second = events.subscribe(), events.subscribe()
+        events.unsubscribe(first)
+        events.unsubscribe(first)
+        events.publish("message", content="hi")
+        self.assertEqual(self.drain(first), [])
+        self.assertEqual(self.drain(second), ["message"])
+
+    def test_fields_and_history(self):
+        events = termchat.EventStream(history=2)
+        queue = events.subscribe()
+        for number in range(3):
+            events.publish("message", number=number)
+        self.assertEqual(queue.get_nowait()["number"], 0)
+        self.assertEqual([item["number"] for item in events.recent], [1, 2])
+
+
+class ShedEventsTest(unittest.TestCase):
+    def test_messages_are_never_dropped(self):
+        events = termchat.EventStream(maxsize=3)
+        queue = events.subscribe()
+        for number in range(5):
+            events.publish("message", number=number)
+        self.assertEqual([queue.get_nowait()["number"] for _ in range(queue.qsize())], [0, 1, 2, 3, 4])
+
+    def test_low_priority_makes_room(self):
+        events = termchat.EventStream(maxsize=3)
+        queue = events.subscribe()
+        events.publish("message", number=0)
+        events.publish("latency", ms=10)
+        events.publish("message", number=1)
+        events.publish("message", number=2)
+        drained = [queue.get_nowait() for _ in range(queue.qsize())]
+        self.assertEqual([item["event"] for item in drained], ["message", "message", "message"])
+
+    def test_updates_coalesce_with_the_ones_they_supersede(self):
+        pending = [{"event": "latency", "ms": 10}, {"event": "message"}, {"event": "server", "data": {"type": "roster"}}]
+        kept, accept = termchat.shed_events(pending, {"event": "latency", "ms": 20})
+        self.assertTrue(accept)
+        self.assertEqual([termchat.event_kind(item) for item in kept], ["message", "server:roster"])
+
+    def test_low_priority_is_refused_when_nothing_can_go(self):
+        pending = [{"event": "message"}, {"event": "connected"}]
+        self.assertEqual(termchat.shed_events(pending, {"event": "outbox"}), (pending, False))
+        self.assertEqual(termchat.shed_events(pending, {"event": "message"}), (pending, True))
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,45 @@
+import unittest
+
+import termchat
+
+
+class ExtractJsonTest(unittest.TestCase):
+    def test_several_objects_in_one_frame(self):
+        objects, invalid, rest = termchat.extract_json_objects('{"a": 1}{"b": 2}\n{"c": 3}')
+        self.assertEqual(objects, [{"a": 1}, {"b": 2}, {"c": 3}])
+        self.assertEqual((invalid, rest), ([], ""))
+
+    def test_split_across_frames(self):
+        objects, _, rest = termchat.extract_json_objects('{"content": "a } in a string", "n": {"x"')
+        self.assertEqual(objects, [])
+        objects, invalid, rest = termchat.extract_json_objects(rest + ': 1}}')
+        self.assertEqual(objects, [{"content": "a } in a string", "n": {"x": 1}}])
+        self.assertEqual((invalid, rest), ([], ""))
+
+    def test_trailing_partial_is_kept(self):
+        objects, _, rest = termchat.extract_json_objects('{"a": 1}{"b": "esc\\\\"')
+        self.assertEqual(objects, [{"a": 1}])
+        self.assertEqual(rest, '{"b": "esc\\\\"')
+
+    def test_garbage_and_bad_objects_are_reported(self):
+        objects, invalid, rest = termchat.extract_json_objects('oops {"a": 1} {"b": nope}')
+        self.assertEqual(objects, [{"a": 1}])
+        self.assertEqual(invalid, ["oops", '{"b": nope}'])
+
+    def test_runaway_partial_is_dropped(self):
+        huge = '{"a": "' + "x" * termchat.MAX_PENDING_JSON
+        objects, invalid, rest = termchat.extract_json_objects(huge)
+        self.assertEqual((objects, rest), ([], ""))
+        self.assertEqual(invalid, [huge])
+
+
+class EntryIndexTest(unittest.TestCase):
+    def test_index_by_id(self):
+        entries = [{"kind": "notice"}, {"kind": "message", "id": 1}, {"kind": "message", "id": 2}, {"kind": "message", "id": None}]
+        index = termchat.index_entries(entries)
+        self.assertEqual(set(index), {1, 2})
+        self.assertIs(index[2], entries[2])
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,32 @@
+import unittest
+
+import termchat
+
+
+class AsciiGlyphsTest(unittest.TestCase):
+    def setUp(self):
+        termchat.use_glyphs(termchat.Glyphs.ascii())
+        self.addCleanup(termchat.use_glyphs, termchat.Glyphs.unicode())
+
+    def test_every_glyph_is_ascii(self):
+        for name, value in vars(termchat.glyphs).items():
+            if isinstance(value, str):
+                with self.subTest(glyph=name):
+                    self.assertTrue(value.isascii(), value)
+
+    def test_generated_text_is_ascii(self):
+        outputs = [
+            termchat.format_session_summary(3, 2, 90),
+            termchat.display_content(None, termchat.E2E_PREFIX + "AAAA"),
+            termchat.format_sparkline([0.0, None, 0.5, 1.0]),
+        ]
+        for output in outputs:
+            with self.subTest(output=output):
+                self.assertTrue(output.isascii(), output)
+
+    def test_shortcodes_are_not_expanded(self):
+        self.assertEqual(termchat.expand_shortcodes("nice :tada:"), "nice :tada:")
+
+
+if __name__ == "__main__":
+    unittest.main()
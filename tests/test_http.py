@@ -0,0 +1,69 @@
+import unittest
+
+import aiohttp
+from aiohttp import web
+from aiohttp.test_utils import TestServer
+
+import termchat
+
+
+class HttpEndpointsTest(unittest.IsolatedAsyncioTestCase):
+    # Against a local HTTP server standing in for the chat server's HTTP side
+
+    async def asyncSetUp(self):
+        self.requests = []
+        self.routes = {}
+        app = web.Application()
+        app.router.add_route("GET", "/{path:.*}", self.handle)
+        self.server = TestServer(app)
+        await self.server.start_server()
+        self.addAsyncCleanup(self.server.close)
+        self.session = aiohttp.ClientSession()
+        self.addAsyncCleanup(self.session.close)
+        self.server_url = f"ws://{self.server.host}:{self.server.port}/ws"
+
+    async def handle(self, request):
+        self.requests.append(request.path)
+        return self.routes.get(request.path, web.Response(status=404))
+
+    async def test_general_count(self):
+        self.routes["/general-count"] = web.json_response({"userCount": 7})
+        self.assertEqual(await termchat.get_general_count(self.session, self.server_url), 7)
+
+    async def test_general_count_on_a_custom_path(self):
+        self.routes["/api/count"] = web.json_response({"userCount": 3})
+        self.assertEqual(await termchat.get_general_count(self.session, self.server_url, "/api/count"), 3)
+
+    async def test_general_count_failures_are_zero(self):
+        self.routes["/general-count"] = web.Response(status=500)
+        self.assertEqual(await termchat.get_general_count(self.session, self.server_url), 0)
+        self.assertEqual(await termchat.get_general_count(self.session, self.server_url, ""), 0)
+        self.assertEqual(self.requests, ["/general-count"])  # An empty path makes no request
+
+    async def test_banner(self):
+        self.routes["/banner"] = web.Response(text="  Welcome to Example Chat  \n\nBe nice.\n")
+        self.assertEqual(await termchat.get_banner(self.session, self.server_url), "Welcome to Example Chat\nBe nice.")
+
+    async def test_banner_failures_are_none(self):
+        self.routes["/motd"] = web.Response(status=503, text="down")
+        self.assertIsNone(await termchat.get_banner(self.session, self.server_url, "/motd"))
+        self.assertIsNone(await termchat.get_banner(self.session, self.server_url, "/missing"))
+        self.assertIsNone(await termchat.get_banner(self.session, self.server_url, ""))
+        self.assertEqual(self.requests, ["/motd", "/missing"])
+
+
+class CleanBannerTest(unittest.TestCase):
+    def test_three_lines_at_most(self):
+        self.assertEqual(termchat.clean_banner("one\n\n two \nthree\nfour"), "one\ntwo\nthree")
+
+    def test_long_banners_are_cut(self):
+        banner = termchat.clean_banner("x" * 500)
+        self.assertEqual(len(banner), termchat.MAX_BANNER_LENGTH + 3)
+        self.assertTrue(banner.endswith("..."))
+
+    def test_blank(self):
+        self.assertIsNone(termchat.clean_banner(" \n\t\n"))
+
+
+if __name__ == "__main__":
+    unittest.main()
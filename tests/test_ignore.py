@@ -0,0 +1,25 @@
+import unittest
+
+import termchat
+
+
+class IgnoreListTest(unittest.TestCase):
+    def test_case_insensitive(self):
+        self.assertTrue(termchat.is_ignored(["Bob"], "bob"))
+        self.assertFalse(termchat.is_ignored(["Bob"], "bobby"))
+        self.assertFalse(termchat.is_ignored(["Bob"], None))
+
+    def test_add_several(self):
+        ignored = ["Bob"]
+        self.assertEqual(termchat.add_ignores(ignored, ["bob", "carol", "dave", "Carol"]), ["carol", "dave"])
+        self.assertEqual(ignored, ["Bob", "carol", "dave"])
+
+    def test_remove_returns_the_stored_names(self):
+        ignored = ["Bob", "carol", "dave"]
+        self.assertEqual(termchat.remove_ignores(ignored, ["BOB", "dave", "erin"]), ["Bob", "dave"])
+        self.assertEqual(ignored, ["carol"])
+        self.assertEqual(termchat.remove_ignores(ignored, ["erin"]), [])
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,114 @@
+import unittest
+from collections import deque
+
+from textual.screen import ModalScreen
+
+import termchat
+from support import MockServer, RoomApp, isolate_config, joined, wait_until
+
+
+class EnterSendsTest(unittest.IsolatedAsyncioTestCase):
+    def setUp(self):
+        isolate_config(self)
+
+    async def sent_after_typing(self, enter_sends: bool, *keys) -> list:
+        server = MockServer()
+        app = RoomApp(server)
+        app.settings["enter_sends"] = enter_sends
+        async with app.run_test() as pilot:
+            await joined(pilot)
+            await pilot.press(*keys)
+            await pilot.pause(0.1)
+            return [data["content"] for data in server.transport.sent_of_type("message")]
+
+    async def test_enter_sends_a_single_line(self):
+        self.assertEqual(await self.sent_after_typing(True, "h", "i", "enter"), ["hi"])
+
+    async def test_enter_is_a_newline_when_enter_sends_is_off(self):
+        self.assertEqual(await self.sent_after_typing(False, "a", "enter", "b"), [])
+        self.assertEqual(await self.sent_after_typing(False, "a", "enter", "b", "ctrl+enter"), ["a\nb"])
+
+    async def test_ctrl_j_and_shift_enter_in_multiline_mode(self):
+        # Terminals commonly report Ctrl+Enter as Ctrl+J; Shift+Enter always inserts a newline
+        self.assertEqual(await self.sent_after_typing(False, "a", "shift+enter", "b", "ctrl+j"), ["a\nb"])
+
+
+class PlaceholderTest(unittest.IsolatedAsyncioTestCase):
+    def setUp(self):
+        isolate_config(self)
+
+    async def test_follows_the_connection_state(self):
+        server = MockServer()
+        async with RoomApp(server).run_test() as pilot:
+            screen = await joined(pilot)
+            message_input = screen.query_one("#message_input")
+            self.assertEqual(message_input.placeholder, termchat.input_placeholder("connected"))
+            server.transport.drop(1000)
+            await wait_until(pilot, lambda: message_input.placeholder == termchat.input_placeholder("disconnected"))
+            self.assertFalse(message_input.disabled)  # Still takes /reconnect
+
+
+class AutoFocusTest(unittest.IsolatedAsyncioTestCase):
+    def setUp(self):
+        isolate_config(self)
+        self.app = RoomApp(MockServer())
+
+    async def test_escape_blurs_and_a_send_refocuses(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            message_input = screen.query_one("#message_input")
+            self.assertIs(self.app.focused, message_input)
+            await pilot.press("escape")
+            self.assertIsNone(self.app.focused)
+            screen.focus_input()
+            self.assertIs(self.app.focused, message_input)
+
+    async def test_left_alone_when_turned_off(self):
+        self.app.settings["auto_focus"] = False
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            await pilot.press("escape")
+            screen.focus_input()
+            self.assertIsNone(self.app.focused)
+
+    async def test_never_taken_from_a_dialog(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            await self.app.push_screen(ModalScreen())
+            screen.focus_input()
+            self.assertIsNot(self.app.focused, screen.query_one("#message_input"))
+
+
+class NormalizeMessageTest(unittest.TestCase):
+    def test_trims_line_ends_and_outer_blank_lines(self):
+        self.assertEqual(termchat.normalize_message("\n\n  hello  \nworld\t\n\n"), "  hello\nworld")
+
+    def test_long_runs_of_blank_lines_become_one(self):
+        self.assertEqual(termchat.normalize_message("a\n\n\n\nb"), "a\n\nb")
+        self.assertEqual(termchat.normalize_message("a\n\n\nb"), "a\n\n\nb")
+
+    def test_indentation_is_kept(self):
+        self.assertEqual(termchat.normalize_message("def f():\n    return 1"), "def f():\n    return 1")
+
+    def test_nothing_left(self):
+        for text in ("", "   ", "\n \n\t\n"):
+            with self.subTest(text=text):
+                self.assertIsNone(termchat.normalize_message(text))
+
+
+class AgainTest(unittest.TestCase):
+    def test_nth_from_last(self):
+        history = ["first", "second", "third"]
+        self.assertEqual(termchat.history_lookup(history, ""), "third")
+        self.assertEqual(termchat.history_lookup(history, "3"), "first")
+        self.assertEqual(termchat.history_lookup(deque(history, maxlen=2), "2"), "second")
+
+    def test_no_such_message(self):
+        for args in ("0", "4", "-1", "two"):
+            with self.subTest(args=args):
+                self.assertIsNone(termchat.history_lookup(["only"] * 3, args))
+        self.assertIsNone(termchat.history_lookup([], ""))
+
+
+if __name__ == "__main__":
+    unittest.main()
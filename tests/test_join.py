@@ -0,0 +1,20 @@
+import unittest
+
+import termchat
+
+
+class JoinPayloadTest(unittest.TestCase):
+    def test_only_the_field_the_method_needs(self):
+        base = {"type": "join", "username": "alice", "chatname": "general"}
+        self.assertEqual(termchat.build_join_payload("alice", "general", "password", "pw"), {**base, "password": "pw"})
+        self.assertEqual(termchat.build_join_payload("alice", "general", "token", "tk"), {**base, "token": "tk"})
+        self.assertEqual(termchat.build_join_payload("alice", "general", "none", "ignored"), base)
+
+    def test_session_token_replaces_the_secret(self):
+        payload = termchat.build_join_payload("alice", "general", "password", "pw", session_token="session")
+        self.assertEqual(payload["session_token"], "session")
+        self.assertNotIn("password", payload)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,48 @@
+import argparse
+import contextlib
+import io
+import json
+import unittest
+from unittest import mock
+
+import termchat
+from support import MockServer, isolate_config
+
+
+class JsonEventTest(unittest.TestCase):
+    def test_server_fields_win_except_event(self):
+        line = json.loads(termchat.json_event("message", {"event": "spoofed", "ts": 1.0, "content": "hi"}))
+        self.assertEqual(line, {"event": "message", "ts": 1.0, "content": "hi"})
+
+
+class JsonModeTest(unittest.IsolatedAsyncioTestCase):
+    def setUp(self):
+        isolate_config(self)
+
+    async def run_script(self, server: MockServer, commands: list) -> list:
+        args = argparse.Namespace(username="bot", chat="general", password="pw", token=None,
+                                  e2e_key=None, server="wss://example.test", bind=None)
+        stdin = io.StringIO("".join(line + "\n" for line in commands))
+        stdout = io.StringIO()
+        with mock.patch.object(termchat.websockets, "connect", server.connect), \
+                mock.patch.object(termchat.sys, "stdin", stdin), contextlib.redirect_stdout(stdout):
+            status = await termchat.run_json_mode(args)
+        self.assertEqual(status, 0)
+        return [json.loads(line) for line in stdout.getvalue().splitlines()]
+
+    async def test_scripted_session(self):
+        server = MockServer()
+        commands = [json.dumps({"type": "message", "content": "hello"}), "not json", json.dumps({"type": "quit"})]
+        events = await self.run_script(server, commands)
+        self.assertEqual(server.transport.sent[0], {"type": "join", "username": "bot", "chatname": "general", "password": "pw"})
+        self.assertEqual(server.transport.sent[1], {"type": "message", "content": "hello"})
+        summary = [(event["event"], event.get("content") or event.get("message")) for event in events]
+        self.assertEqual(summary[0], ("connected", None))
+        self.assertEqual(summary[-1], ("disconnected", None))
+        # Commands and server events are handled concurrently, so only the ends are ordered
+        self.assertCountEqual(summary[1:-1], [("message", "hello"), ("error", "Invalid JSON command")])
+        self.assertEqual(events[0]["username"], "bot")
+
+
+if __name__ == "__main__":
+    unittest.main()
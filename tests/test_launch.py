@@ -0,0 +1,64 @@
+import json
+import os
+import tempfile
+import unittest
+
+import termchat
+
+
+class SecretArgsTest(unittest.TestCase):
+    def test_split(self):
+        public, secret = termchat.split_secret_args(["--ascii", "--password", "hunter2", "--e2e-key=abc", "--server", "wss://x"])
+        self.assertEqual(public, ["--ascii", "--server", "wss://x"])
+        self.assertEqual(secret, ["--password", "hunter2", "--e2e-key=abc"])
+
+    def test_round_trip_through_a_private_file(self):
+        flag, path = termchat.stash_secret_args(["--token", "t0k"])
+        self.assertEqual(flag, termchat.SECRET_ARGS_FLAG)
+        if os.name == "posix":
+            self.assertEqual(os.stat(path).st_mode & 0o777, 0o600)
+        self.assertEqual(termchat.restore_secret_args(["--ascii", flag, path]), ["--ascii", "--token", "t0k"])
+        self.assertFalse(os.path.exists(path))
+
+    def test_nothing_to_stash_or_restore(self):
+        self.assertEqual(termchat.stash_secret_args([]), [])
+        self.assertEqual(termchat.restore_secret_args(["--ascii"]), ["--ascii"])
+        self.assertEqual(termchat.restore_secret_args([termchat.SECRET_ARGS_FLAG, "/nonexistent/termchat.json"]), [])
+
+    def test_other_files_are_left_alone(self):
+        directory = tempfile.TemporaryDirectory()
+        self.addCleanup(directory.cleanup)
+        notes = os.path.join(directory.name, "termchat-notes.json")
+        with open(notes, "w", encoding="utf-8") as f:
+            json.dump(["--token", "t0k"], f)
+        self.assertEqual(termchat.restore_secret_args(["--ascii", termchat.SECRET_ARGS_FLAG, notes]), ["--ascii"])
+        self.assertTrue(os.path.exists(notes))
+        fd, unrelated = tempfile.mkstemp(prefix="notes-", suffix=".json")
+        os.close(fd)
+        self.addCleanup(os.remove, unrelated)
+        self.assertEqual(termchat.restore_secret_args([termchat.SECRET_ARGS_FLAG, unrelated]), [])
+        self.assertTrue(os.path.exists(unrelated))
+
+    def test_unreadable_stash_is_still_removed(self):
+        fd, path = tempfile.mkstemp(prefix="termchat-", suffix=".json")
+        with os.fdopen(fd, "w") as f:
+            f.write("not json")
+        self.assertEqual(termchat.restore_secret_args([termchat.SECRET_ARGS_FLAG, path]), [])
+        self.assertFalse(os.path.exists(path))
+
+
+class QuotingTest(unittest.TestCase):
+    def test_cmd_metacharacters_are_escaped(self):
+        self.assertEqual(termchat.cmd_quote("a&b"), '^"a^&b^"')
+        self.assertEqual(termchat.cmd_quote("100%|x"), '^"100^%^|x^"')
+
+    def test_cmd_quotes_and_backslashes(self):
+        self.assertEqual(termchat.cmd_quote('say "hi"'), '^"say \\^"hi\\^"^"')
+        self.assertEqual(termchat.cmd_quote("C:\\dir\\"), '^"C:\\dir\\\\^"')
+
+    def test_applescript(self):
+        self.assertEqual(termchat.applescript_string('say "hi" \\'), 'say \\"hi\\" \\\\')
+
+
+if __name__ == "__main__":
+    unittest.main()
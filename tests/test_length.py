@@ -0,0 +1,32 @@
+import unittest
+
+import termchat
+
+
+class MaxLengthTest(unittest.TestCase):
+    def test_servers_limit_when_sensible(self):
+        self.assertEqual(termchat.effective_max_length(500), 500)
+        for limit in (None, 0, -5, True, "500", 500.0):
+            with self.subTest(limit=limit):
+                self.assertEqual(termchat.effective_max_length(limit), termchat.DEFAULT_MAX_MESSAGE_LENGTH)
+
+    def test_levels(self):
+        self.assertEqual(termchat.length_level(79, 100), "ok")
+        self.assertEqual(termchat.length_level(80, 100), "warning")
+        self.assertEqual(termchat.length_level(100, 100), "full")
+        self.assertEqual(termchat.length_level(150, 100), "full")
+
+
+class WireLengthTest(unittest.TestCase):
+    def test_plain(self):
+        self.assertEqual(termchat.wire_length(None, "héllo"), 5)
+
+    def test_matches_the_ciphertext(self):
+        key = termchat.derive_e2e_key("hunter2", "general")
+        for content in ("", "a", "ab", "abc", "héllo ☕", "x" * 100):
+            with self.subTest(content=content):
+                self.assertEqual(termchat.wire_length(key, content), len(termchat.encrypt_content(key, content)))
+
+
+if __name__ == "__main__":
+    unittest.main()
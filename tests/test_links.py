@@ -0,0 +1,43 @@
+import time
+import unittest
+from urllib.parse import parse_qs
+
+import termchat
+
+
+class JoinUriTest(unittest.TestCase):
+    def test_round_trip(self):
+        uri = termchat.build_join_uri("wss://chat.example.com/ws", "tea & biscuits")
+        self.assertEqual(termchat.parse_join_uri(uri), {"server": "wss://chat.example.com/ws", "chat": "tea & biscuits"})
+
+    def test_missing_fields(self):
+        self.assertIsNone(termchat.parse_join_uri("termchat://join?server=wss://chat.example.com"))
+        self.assertIsNone(termchat.parse_join_uri("termchat://join?chat=general"))
+        self.assertIsNone(termchat.parse_join_uri("termchat://join?server=&chat=general"))
+
+    def test_malformed(self):
+        for uri in ("https://join?server=wss://x&chat=y", "termchat://leave?server=wss://x&chat=y",
+                    "termchat://join?server=http://x&chat=y", "termchat://join?server=wss://x&chat=%20", ""):
+            with self.subTest(uri=uri):
+                self.assertIsNone(termchat.parse_join_uri(uri))
+
+
+class MessageLinkTest(unittest.TestCase):
+    def test_fields_are_encoded(self):
+        link = termchat.build_message_link("wss://chat.example.com/ws", "tea & biscuits", 42)
+        scheme, _, query = link.partition("?")
+        self.assertEqual(scheme, "termchat://message")
+        self.assertEqual(parse_qs(query), {"server": ["wss://chat.example.com/ws"], "chat": ["tea & biscuits"], "id": ["42"]})
+
+
+class MessageMarkdownTest(unittest.TestCase):
+    def test_sender_and_time_in_front(self):
+        sent_at = time.mktime((2024, 5, 1, 14, 7, 0, 0, 0, -1))
+        self.assertEqual(termchat.format_message_markdown("bob", "**done**", sent_at), "**bob** (14:07): **done**")
+
+    def test_later_lines_are_indented(self):
+        self.assertEqual(termchat.format_message_markdown("bob", "one\ntwo", 0).split("\n")[1], "    two")
+
+
+if __name__ == "__main__":
+    unittest.main()
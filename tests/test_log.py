@@ -0,0 +1,102 @@
+import time
+import unittest
+
+import termchat
+
+
+class DividerTest(unittest.TestCase):
+    def test_first_message(self):
+        self.assertIsNone(termchat.divider_for(None, ("alice", 0), 5))
+
+    def test_same_sender_within_the_gap(self):
+        self.assertIsNone(termchat.divider_for(("alice", 0), ("alice", 300), 5))
+
+    def test_gap_wins_over_sender_change(self):
+        self.assertEqual(termchat.divider_for(("alice", 0), ("bob", 301), 5), "time")
+        self.assertEqual(termchat.divider_for(("alice", 0), ("alice", 301), 5), "time")
+
+    def test_sender_change(self):
+        self.assertEqual(termchat.divider_for(("alice", 0), ("bob", 10), 5), "sender")
+
+    def test_zero_gap_disables_time_dividers(self):
+        self.assertIsNone(termchat.divider_for(("alice", 0), ("alice", 86400), 0))
+        self.assertEqual(termchat.divider_for(("alice", 0), ("bob", 86400), 0), "sender")
+
+
+class CollapseTest(unittest.TestCase):
+    def notice(self, markup="Reconnecting...", kind="notice", at=100.0) -> dict:
+        return {"markup": markup, "kind": kind, "count": 1, "time": at}
+
+    def test_repeated_notice_within_the_window(self):
+        self.assertTrue(termchat.should_collapse(self.notice(), "Reconnecting...", "notice", 105.0, 10.0))
+
+    def test_different_text_old_notice_or_first_line(self):
+        self.assertFalse(termchat.should_collapse(self.notice(), "Reconnected", "notice", 105.0, 10.0))
+        self.assertFalse(termchat.should_collapse(self.notice(), "Reconnecting...", "notice", 111.0, 10.0))
+        self.assertFalse(termchat.should_collapse(None, "Reconnecting...", "notice", 105.0, 10.0))
+
+    def test_messages_never_collapse(self):
+        self.assertFalse(termchat.should_collapse(self.notice(kind="message"), "Reconnecting...", "message", 105.0, 10.0))
+
+    def test_counter_is_rendered(self):
+        entry = self.notice()
+        self.assertEqual(termchat.render_entry(entry), "Reconnecting...")
+        entry["count"] = 3
+        self.assertEqual(termchat.render_entry(entry), f"Reconnecting... [dim]({termchat.glyphs.times}3)[/dim]")
+
+
+class UndoBufferTest(unittest.TestCase):
+    def test_restored_within_the_window(self):
+        undo = termchat.UndoBuffer(window=30.0)
+        undo.store(["a", "b"], "bob", now=100.0)
+        self.assertEqual(undo.take(now=129.0), (["a", "b"], "bob"))
+        self.assertIsNone(undo.take(now=129.0))
+
+    def test_expired(self):
+        undo = termchat.UndoBuffer(window=30.0)
+        undo.store(["a"], None, now=100.0)
+        self.assertIsNone(undo.take(now=130.5))
+
+    def test_one_level_only(self):
+        undo = termchat.UndoBuffer()
+        undo.store(["first"], None, now=100.0)
+        undo.store(["second"], None, now=101.0)
+        self.assertEqual(undo.take(now=102.0), (["second"], None))
+
+    def test_nothing_saved(self):
+        self.assertIsNone(termchat.UndoBuffer().take(now=0.0))
+
+
+class PinnedTest(unittest.TestCase):
+    def test_tolerance_boundary(self):
+        # 100 lines in a 20 line view: the bottom is at 80
+        self.assertTrue(termchat.is_pinned(80, 100, 20, 2))
+        self.assertTrue(termchat.is_pinned(78, 100, 20, 2))
+        self.assertFalse(termchat.is_pinned(77, 100, 20, 2))
+        self.assertFalse(termchat.is_pinned(79, 100, 20, 0))
+
+    def test_content_shorter_than_the_view(self):
+        self.assertTrue(termchat.is_pinned(0, 5, 20, 0))
+
+
+class TimestampTest(unittest.TestCase):
+    def setUp(self):
+        self.sent_at = time.mktime((2024, 5, 1, 9, 5, 0, 0, 0, -1))
+
+    def test_group_boundaries(self):
+        self.assertTrue(termchat.starts_group(None, None))
+        self.assertTrue(termchat.starts_group(("alice", 0), "sender"))
+        self.assertTrue(termchat.starts_group(("alice", 0), "time"))
+        self.assertFalse(termchat.starts_group(("alice", 0), None))
+
+    def test_gutter_only_on_the_first_message_of_a_group(self):
+        self.assertEqual(termchat.add_timestamp("alice: hi", "gutter", self.sent_at, True), "alice: hi  [dim]09:05[/dim]")
+        self.assertEqual(termchat.add_timestamp("alice: again", "gutter", self.sent_at, False), "alice: again")
+
+    def test_inline_and_hidden(self):
+        self.assertEqual(termchat.add_timestamp("alice: hi", "inline", self.sent_at, False), "[dim]\\[09:05][/dim] alice: hi")
+        self.assertEqual(termchat.add_timestamp("alice: hi", "hidden", self.sent_at, True), "alice: hi")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,54 @@
+import unittest
+
+import termchat
+
+
+class InlineMarkdownTest(unittest.TestCase):
+    def test_styles(self):
+        cases = {
+            "**bold**": "[bold]bold[/bold]",
+            "~~gone~~": "[strike]gone[/strike]",
+            "*soft* and _soft_": "[italic]soft[/italic] and [italic]soft[/italic]",
+            "run `ls -la` now": "run [reverse]ls -la[/reverse] now",
+        }
+        for text, markup in cases.items():
+            with self.subTest(text=text):
+                self.assertEqual(termchat.parse_inline_markdown(text), markup)
+
+    def test_nesting(self):
+        self.assertEqual(termchat.parse_inline_markdown("**very _much_**"), "[bold]very [italic]much[/italic][/bold]")
+
+    def test_code_is_literal(self):
+        self.assertEqual(termchat.parse_inline_markdown("`**not bold**`"), "[reverse]**not bold**[/reverse]")
+
+    def test_left_alone(self):
+        for text in ("2 * 3 * 4", "snake_case_name", "a ** b", "unclosed `tick"):
+            with self.subTest(text=text):
+                self.assertEqual(termchat.parse_inline_markdown(text), text)
+
+    def test_markup_in_the_text_is_escaped(self):
+        self.assertEqual(termchat.parse_inline_markdown("[bold]x **y**"), "\\[bold]x [bold]y[/bold]")
+
+
+class CodeBlockTest(unittest.TestCase):
+    def test_fenced_block_with_language(self):
+        text = "look:\n```python\nprint(1)\n\n    x = 2\n```\ndone"
+        self.assertEqual(termchat.split_code_blocks(text), [
+            ("text", "look:", None), ("code", "print(1)\n\n    x = 2", "python"), ("text", "done", None)])
+
+    def test_one_line_fence(self):
+        self.assertEqual(termchat.split_code_blocks("```ls -la```\nthen"), [("code", "ls -la", None), ("text", "then", None)])
+
+    def test_closed_at_the_end_of_a_code_line(self):
+        self.assertEqual(termchat.split_code_blocks("```\nmake all```"), [("code", "make all", None)])
+
+    def test_fences_mid_line_are_prose(self):
+        self.assertEqual(termchat.split_code_blocks("use ```x``` here"), [("text", "use ```x``` here", None)])
+
+    def test_unterminated_runs_to_the_end(self):
+        self.assertEqual(termchat.split_code_blocks("hi\n```sh\nrm -rf build"), [("text", "hi", None), ("code", "rm -rf build", "sh")])
+        self.assertEqual(termchat.split_code_blocks("```"), [("code", "", None)])
+
+
+if __name__ == "__main__":
+    unittest.main()
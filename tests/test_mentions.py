@@ -0,0 +1,30 @@
+import unittest
+
+import termchat
+
+
+class MentionQueryTest(unittest.TestCase):
+    def test_partial_name_before_the_cursor(self):
+        self.assertEqual(termchat.mention_query("@al"), "al")
+        self.assertEqual(termchat.mention_query("thanks @bo"), "bo")
+        self.assertEqual(termchat.mention_query("hi @"), "")
+
+    def test_not_a_mention(self):
+        for text in ("", "hello", "mail me@example", "@bob done", "@@"):
+            with self.subTest(text=text):
+                self.assertIsNone(termchat.mention_query(text))
+
+
+class MentionCandidatesTest(unittest.TestCase):
+    def test_prefix_match_without_ourselves(self):
+        roster = {"alice", "Albert", "bob", "alfie"}
+        self.assertEqual(termchat.mention_candidates("AL", roster, "alice"), ["Albert", "alfie"])
+        self.assertEqual(termchat.mention_candidates("", roster, "alice"), ["Albert", "alfie", "bob"])
+
+    def test_capped(self):
+        roster = {f"user{number}" for number in range(20)}
+        self.assertEqual(len(termchat.mention_candidates("user", roster, "me")), termchat.MAX_MENTION_CANDIDATES)
+
+
+if __name__ == "__main__":
+    unittest.main()
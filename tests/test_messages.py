@@ -0,0 +1,28 @@
+import unittest
+
+import termchat
+
+
+class SystemMessageTest(unittest.TestCase):
+    def test_parameters(self):
+        self.assertEqual(termchat.sys_msg("user_joined", username="bob"), "A wild bob has appeared.")
+        self.assertEqual(termchat.sys_msg("disconnected", reason="Server restarting (1012)"), "Disconnected: Server restarting (1012)")
+        self.assertEqual(
+            termchat.sys_msg("unsent_kept", count=2, s="s", chat="general"),
+            "2 unsent messages will be sent when you go back to 'general'.",
+        )
+
+    def test_unknown_key_falls_back_to_the_key(self):
+        self.assertEqual(termchat.sys_msg("no_such_message", reason="x"), "no_such_message")
+
+    def test_missing_parameter_leaves_the_template(self):
+        self.assertEqual(termchat.sys_msg("user_joined"), termchat.SYSTEM_MESSAGES["user_joined"])
+
+    def test_every_message_is_a_string(self):
+        for key, template in termchat.SYSTEM_MESSAGES.items():
+            with self.subTest(key=key):
+                self.assertIsInstance(template, str)
+
+
+if __name__ == "__main__":
+    unittest.main()
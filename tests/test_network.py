@@ -0,0 +1,55 @@
+import argparse
+import errno
+import unittest
+
+import termchat
+
+
+class BindAddressTest(unittest.TestCase):
+    def test_literal_addresses(self):
+        self.assertEqual(termchat.parse_bind_address("192.168.1.20"), "192.168.1.20")
+        self.assertEqual(termchat.parse_bind_address("fe80:0:0::1"), "fe80::1")
+
+    def test_hostnames_are_refused(self):
+        for value in ("localhost", "192.168.1", ""):
+            with self.subTest(value=value), self.assertRaises(argparse.ArgumentTypeError):
+                termchat.parse_bind_address(value)
+
+    def test_connect_options(self):
+        self.assertEqual(termchat.bind_options("10.0.0.5"), {"local_addr": ("10.0.0.5", 0)})
+        self.assertEqual(termchat.bind_options(None), {})
+
+    def test_bind_errors_only_when_binding(self):
+        error = OSError(errno.EADDRNOTAVAIL, "Cannot assign requested address")
+        self.assertTrue(termchat.is_bind_error(error, "10.0.0.5"))
+        self.assertFalse(termchat.is_bind_error(error, None))
+        self.assertFalse(termchat.is_bind_error(OSError(errno.ECONNREFUSED, "refused"), "10.0.0.5"))
+
+
+class ServerUrlTest(unittest.TestCase):
+    def test_bare_hosts_get_wss(self):
+        self.assertEqual(termchat.parse_server_url(" chat.example.com:8443 "), "wss://chat.example.com:8443")
+        self.assertEqual(termchat.parse_server_url("ws://localhost:8080/ws"), "ws://localhost:8080/ws")
+
+    def test_other_schemes_are_refused(self):
+        for value in ("https://chat.example.com", "wss://", "ftp://host"):
+            with self.subTest(value=value), self.assertRaises(argparse.ArgumentTypeError):
+                termchat.parse_server_url(value)
+
+    def test_path_kept_or_defaulted(self):
+        self.assertEqual(termchat.resolve_server_url("wss://host/chat/", "/ws"), "wss://host/chat")
+        self.assertEqual(termchat.resolve_server_url("wss://host", "/ws"), "wss://host/ws")
+        self.assertEqual(termchat.resolve_server_url("wss://host/", ""), "wss://host")
+        self.assertEqual(termchat.resolve_server_url("ws://host:81?room=a", ""), "ws://host:81?room=a")
+
+    def test_http_endpoints_ignore_the_websocket_path(self):
+        self.assertEqual(termchat.http_endpoint("wss://host/chat/ws", "/health"), "https://host/health")
+        self.assertEqual(termchat.http_endpoint("ws://host:8080", "/health"), "http://host:8080/health")
+
+    def test_tls_only_for_wss(self):
+        self.assertIn("ssl", termchat.tls_options("wss://host/ws"))
+        self.assertEqual(termchat.tls_options("ws://localhost:8080"), {})
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,47 @@
+import unittest
+
+import termchat
+
+
+class ParsePollTest(unittest.TestCase):
+    def test_quoted_parts(self):
+        self.assertEqual(termchat.parse_poll_command('"Lunch?" "Pizza" "Sushi place"'), ("Lunch?", ["Pizza", "Sushi place"]))
+
+    def test_refused(self):
+        too_many = '"Q" ' + " ".join(f'"{number}"' for number in range(termchat.MAX_POLL_OPTIONS + 1))
+        for args in ('"Lunch?" "Pizza"', '"Lunch?" "Pizza" "  "', '"Lunch? "Pizza" "Sushi"', too_many):
+            with self.subTest(args=args):
+                self.assertIsNone(termchat.parse_poll_command(args))
+
+
+class PollTest(unittest.TestCase):
+    def test_changing_a_vote_moves_it(self):
+        poll = termchat.Poll(7, "Lunch?", ["Pizza", "Sushi"], votes=[2, 1])
+        self.assertTrue(poll.vote(0))
+        self.assertEqual(poll.votes, [3, 1])
+        self.assertFalse(poll.vote(0))
+        self.assertTrue(poll.vote(1))
+        self.assertEqual((poll.votes, poll.my_vote), ([2, 2], 1))
+
+    def test_bad_tallies_are_ignored(self):
+        poll = termchat.Poll(7, "Lunch?", ["Pizza", "Sushi"], votes=[2, 1])
+        for votes in ([1], [1, -1], [True, 0], "2,1"):
+            with self.subTest(votes=votes):
+                poll.update(votes, my_vote=5)
+                self.assertEqual((poll.votes, poll.my_vote), ([2, 1], None))
+
+    def test_clickable_ids(self):
+        for poll_id in (7, "abc-1_2"):
+            with self.subTest(poll_id=poll_id):
+                self.assertTrue(termchat.is_clickable_poll_id(poll_id))
+        for poll_id in (True, "a)]", "", None, 1.5):
+            with self.subTest(poll_id=poll_id):
+                self.assertFalse(termchat.is_clickable_poll_id(poll_id))
+
+    def test_unsafe_ids_get_no_click_markup(self):
+        self.assertNotIn("@click", termchat.Poll('x")] [@click=app.quit]', "Q", ["A", "B"]).markup())
+        self.assertIn("@click=screen.vote(7, 1)", termchat.Poll(7, "Q", ["A", "B"]).markup())
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,44 @@
+import unittest
+from unittest import mock
+
+import termchat
+from support import MockServer, RoomApp, isolate_config, joined, wait_until
+
+
+class PresenceTest(unittest.TestCase):
+    def test_summarized_only_above_the_threshold(self):
+        self.assertFalse(termchat.summarize_presence(100, 100))
+        self.assertTrue(termchat.summarize_presence(101, 100))
+        self.assertFalse(termchat.summarize_presence(5000, 0))
+
+    def test_window_counts_recent_joins_and_leaves(self):
+        window = termchat.PresenceWindow(window=60.0)
+        window.record("join", now=0.0)
+        window.record("join", now=30.0)
+        window.record("leave", now=50.0)
+        self.assertEqual(window.counts(now=55.0), (2, 1))
+        self.assertEqual(window.counts(now=85.0), (1, 1))
+        self.assertEqual(window.counts(now=200.0), (0, 0))
+
+
+class PresenceSummaryTest(unittest.IsolatedAsyncioTestCase):
+    def setUp(self):
+        isolate_config(self)
+        self.app = RoomApp(MockServer())
+        self.app.settings["presence_summary_threshold"] = 2
+
+    async def test_busy_room_gets_one_summary(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            await screen.handle_message({"type": "roster", "users": ["alice", "bob", "carol"]})
+            entries = len(screen.entries)
+            with mock.patch.object(termchat, "PRESENCE_SUMMARY_DELAY", 0.05):
+                await screen.handle_message({"type": "join", "username": "dave"})
+                await screen.handle_message({"type": "leave", "username": "bob"})
+            await wait_until(pilot, lambda: len(screen.entries) > entries)
+            self.assertEqual(len(screen.entries), entries + 1)
+            self.assertIn(termchat.sys_msg("presence_summary", count=3, joins=1, leaves=1), screen.entries[-1]["markup"])
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,45 @@
+import unittest
+
+import termchat
+from support import isolate_config
+
+
+class ProfilesTest(unittest.TestCase):
+    def setUp(self):
+        self.profiles = []
+        termchat.add_profile(self.profiles, termchat.make_profile("Work", "wss://chat.example.com", "team", "alice"))
+
+    def test_find_is_case_insensitive(self):
+        self.assertEqual(termchat.find_profile(self.profiles, "work")["url"], "wss://chat.example.com")
+        self.assertIsNone(termchat.find_profile(self.profiles, "home"))
+
+    def test_duplicate_names_are_refused(self):
+        with self.assertRaises(ValueError):
+            termchat.add_profile(self.profiles, termchat.make_profile("WORK", "wss://other.example.com"))
+        self.assertEqual(len(self.profiles), 1)
+
+    def test_url_must_be_a_websocket(self):
+        with self.assertRaises(ValueError):
+            termchat.make_profile("web", "https://chat.example.com")
+
+    def test_edit_and_remove(self):
+        termchat.edit_profile(self.profiles, termchat.make_profile("Work", "wss://new.example.com"))
+        self.assertEqual(self.profiles[0]["url"], "wss://new.example.com")
+        with self.assertRaises(ValueError):
+            termchat.edit_profile(self.profiles, termchat.make_profile("home", "wss://x"))
+        self.assertTrue(termchat.remove_profile(self.profiles, "WORK"))
+        self.assertFalse(termchat.remove_profile(self.profiles, "work"))
+
+    def test_profiles_never_hold_passwords(self):
+        self.assertEqual(set(self.profiles[0]), {"name", "url", "default_chat", "default_username"})
+
+    def test_saved_and_loaded(self):
+        isolate_config(self)
+        settings = termchat.load_settings()
+        settings["profiles"] = self.profiles
+        termchat.save_settings(settings)
+        self.assertEqual(termchat.load_settings()["profiles"], self.profiles)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,92 @@
+import asyncio
+import socket
+import unittest
+from types import SimpleNamespace
+
+import termchat
+
+
+class CloseCodeTest(unittest.TestCase):
+    def test_describe_close(self):
+        self.assertEqual(termchat.describe_close(1008, "banned"), "banned (1008)")
+        self.assertEqual(termchat.describe_close(1012, ""), "Server restarting (1012)")
+        self.assertEqual(termchat.describe_close(None, None), "Connection dropped (1006)")
+        self.assertEqual(termchat.describe_close(4000, None), "Connection closed (4000)")
+
+    def test_only_transient_closes_reconnect(self):
+        for code in (None, 1001, 1006, 1011, 1012, 1013):
+            self.assertTrue(termchat.should_auto_reconnect(code), code)
+        for code in (1000, 1008, 1009, 4000):
+            self.assertFalse(termchat.should_auto_reconnect(code), code)
+
+    def test_backoff_doubles_up_to_the_cap(self):
+        delays = [termchat.reconnect_delay(attempt) for attempt in range(6)]
+        self.assertEqual(delays, [2.0, 4.0, 8.0, 16.0, 30.0, 30.0])
+
+
+class FatalConnectErrorTest(unittest.TestCase):
+    def test_rejected_joins_and_forbidden_are_fatal(self):
+        forbidden = Exception("server rejected WebSocket connection: HTTP 403")
+        for error in (termchat.JoinRejected("Wrong password"), forbidden):
+            self.assertIn(termchat.classify_connect_error(error), termchat.FATAL_CONNECT_ERRORS)
+
+    def test_network_errors_are_retried(self):
+        for error in (socket.gaierror(-2, "Name or service not known"), ConnectionRefusedError(), TimeoutError()):
+            self.assertNotIn(termchat.classify_connect_error(error), termchat.FATAL_CONNECT_ERRORS)
+
+
+class ClassifyConnectErrorTest(unittest.TestCase):
+    def test_by_type(self):
+        cases = [
+            (socket.gaierror(-2, "Name or service not known"), "dns"),
+            (ConnectionRefusedError(111, "Connection refused"), "refused"),
+            (asyncio.TimeoutError(), "timeout"),
+            (socket.timeout(), "timeout"),
+            (termchat.JoinRejected("Wrong password"), "auth"),
+        ]
+        for error, kind in cases:
+            with self.subTest(error=error):
+                self.assertEqual(termchat.classify_connect_error(error), kind)
+
+    def test_by_status_code(self):
+        self.assertEqual(termchat.classify_connect_error(SimpleNamespace(status_code=401)), "forbidden")
+        self.assertEqual(termchat.classify_connect_error(SimpleNamespace(response=SimpleNamespace(status_code=403))), "forbidden")
+
+    def test_wrapped_errors_by_message(self):
+        cases = [
+            (OSError("[Errno 11001] getaddrinfo failed"), "dns"),
+            (OSError("Multiple exceptions: [Errno 111] Connect call failed: Connection refused"), "refused"),
+            (Exception("server rejected WebSocket connection: HTTP 401"), "forbidden"),
+            (Exception("server rejected WebSocket connection: HTTP 500"), "other"),
+            (ValueError("something else"), "other"),
+        ]
+        for error, kind in cases:
+            with self.subTest(error=error):
+                self.assertEqual(termchat.classify_connect_error(error), kind)
+
+
+class WaitUntilReachableTest(unittest.IsolatedAsyncioTestCase):
+    async def test_polls_until_reachable(self):
+        answers, sleeps = [False, False, True], []
+
+        async def reachable() -> bool:
+            return answers.pop(0)
+
+        async def sleep(delay: float):
+            sleeps.append(delay)
+
+        self.assertTrue(await termchat.wait_until_reachable(reachable, poll_interval=4.0, sleep=sleep))
+        self.assertEqual(sleeps, [4.0, 4.0])
+
+    async def test_reachable_straight_away(self):
+        async def reachable() -> bool:
+            return True
+
+        async def sleep(delay: float):
+            raise AssertionError("slept while reachable")
+
+        self.assertFalse(await termchat.wait_until_reachable(reachable, sleep=sleep))
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,75 @@
+import unittest
+
+import termchat
+
+
+def message(message_id, username="bob", content="hi", reply_to=None) -> dict:
+    return {"id": message_id, "username": username, "content": content, "reply_to": reply_to}
+
+
+class FindEntryTest(unittest.TestCase):
+    def test_newest_first(self):
+        older, newer = message(1, content="old"), message(1, content="new")
+        self.assertIs(termchat.find_entry([older, message(2), newer], 1), newer)
+
+    def test_missing(self):
+        self.assertIsNone(termchat.find_entry([message(1)], 5))
+        self.assertIsNone(termchat.find_entry([{"markup": "notice", "id": None}], None))
+
+
+class ReplyHeaderTest(unittest.TestCase):
+    def test_snippet_is_one_short_line(self):
+        self.assertEqual(termchat.reply_snippet("line one\n  line two"), "line one line two")
+        self.assertEqual(termchat.reply_snippet("word " * 20, limit=12), "word word wo" + termchat.glyphs.ellipsis)
+
+    def test_header(self):
+        header = termchat.format_reply_header(message(1, username="bob", content="lunch?"))
+        self.assertEqual(header, f"[dim]  {termchat.glyphs.reply} replying to bob: lunch?[/dim]")
+
+    def test_parent_no_longer_in_the_log(self):
+        self.assertIn("replying to an earlier message", termchat.format_reply_header(None))
+
+
+class ThreadTest(unittest.TestCase):
+    def setUp(self):
+        self.entries = [
+            message(1, content="root"),
+            message(2, content="unrelated"),
+            message(3, content="reply", reply_to=1),
+            {"markup": "a notice", "id": None},
+            message(4, content="reply to the reply", reply_to=3),
+            message(5, content="other thread", reply_to=2),
+        ]
+
+    def test_root_and_every_reply_under_it(self):
+        self.assertEqual([entry["content"] for entry in termchat.thread_entries(self.entries, 1)], ["root", "reply", "reply to the reply"])
+        self.assertEqual(termchat.thread_entries(self.entries, 99), [])
+
+    def test_root_found_from_a_nested_reply(self):
+        self.assertIs(termchat.thread_root(self.entries, self.entries[4]), self.entries[0])
+
+    def test_root_stops_where_the_log_does(self):
+        orphan = message(6, reply_to=42)
+        self.assertIs(termchat.thread_root(self.entries + [orphan], orphan), orphan)
+
+    def test_reply_loops_end(self):
+        looped = [message(7, reply_to=8), message(8, reply_to=7)]
+        self.assertIn(termchat.thread_root(looped, looped[0]), looped)
+
+
+class EditEntryTest(unittest.TestCase):
+    def test_edited_in_place(self):
+        entries = [message(1, content="helo"), message(2)]
+        entry = termchat.edit_entry(entries, 1, "hello", edited_at=50.0)
+        self.assertIs(entry, entries[0])
+        self.assertEqual((entry["content"], entry["edited_at"]), ("hello", 50.0))
+
+    def test_not_in_the_log(self):
+        entries = [message(1, content="helo")]
+        self.assertIsNone(termchat.edit_entry(entries, 9, "hello", edited_at=50.0))
+        self.assertIsNone(termchat.edit_entry(entries, 1, None, edited_at=50.0))
+        self.assertEqual(entries[0]["content"], "helo")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,104 @@
+import time
+import unittest
+
+import termchat
+from support import MockServer, RoomApp, isolate_config, joined
+
+
+class RoomInfoTest(unittest.TestCase):
+    def test_full_payload(self):
+        created = time.mktime((2024, 5, 1, 9, 30, 0, 0, 0, -1)) * 1000
+        lines = termchat.format_room_info({"member_count": 12, "topic": "Tea", "created_ms": created, "protected": True})
+        self.assertEqual(lines, ["Members: 12", "Topic: Tea", "Created: 2024-05-01 09:30", "Password protected: yes"])
+
+    def test_every_field_is_optional(self):
+        self.assertEqual(termchat.format_room_info({}), ["Topic: (none)"])
+        self.assertEqual(termchat.format_room_info({"member_count": "many", "protected": False}),
+                         ["Topic: (none)", "Password protected: no"])
+
+
+class RecentRoomsTest(unittest.TestCase):
+    def test_newest_first_with_a_fresh_timestamp(self):
+        recent = termchat.touch_recent_room([], "wss://a", "one", 1.0)
+        recent = termchat.touch_recent_room(recent, "wss://a", "two", 2.0)
+        recent = termchat.touch_recent_room(recent, "wss://a", "one", 3.0)
+        self.assertEqual([(entry["chat"], entry["last_joined"]) for entry in recent], [("one", 3.0), ("two", 2.0)])
+
+    def test_same_name_on_another_server_is_another_room(self):
+        recent = termchat.touch_recent_room([], "wss://a", "general", 1.0)
+        recent = termchat.touch_recent_room(recent, "wss://b", "general", 2.0)
+        self.assertEqual(len(recent), 2)
+
+    def test_least_recent_is_evicted(self):
+        recent = []
+        for index in range(5):
+            recent = termchat.touch_recent_room(recent, "wss://a", f"room{index}", float(index), limit=3)
+        self.assertEqual([entry["chat"] for entry in recent], ["room4", "room3", "room2"])
+
+    def test_description(self):
+        joined = time.mktime((2024, 5, 1, 9, 30, 0, 0, 0, -1))
+        entry = {"server": "wss://chat.example.com/ws", "chat": "general", "last_joined": joined}
+        self.assertEqual(termchat.describe_recent_room(entry), "general @ chat.example.com (2024-05-01 09:30)")
+
+
+class RoomCycleTest(unittest.TestCase):
+    server = "wss://chat.example.com"
+
+    def recent(self, *rooms, server=None) -> list:
+        return [{"server": server or self.server, "chat": chat, "last_joined": 0} for chat in rooms]
+
+    def test_rooms_on_this_server_only(self):
+        recent = self.recent("random", "General", "art") + self.recent("elsewhere", server="wss://other.example.com")
+        blocked = [{"server": self.server, "chat": "art"}]
+        self.assertEqual(termchat.rooms_on_server(recent, self.server, blocked), ["General", "random"])
+
+    def test_wraps_both_ways(self):
+        rooms = ["art", "general", "random"]
+        self.assertEqual(termchat.room_cycle(rooms, "random", 1), "art")
+        self.assertEqual(termchat.room_cycle(rooms, "art", -1), "random")
+        self.assertEqual(termchat.room_cycle(rooms, "art", 2), "random")
+
+    def test_current_room_not_in_the_list(self):
+        rooms = ["art", "general"]
+        self.assertEqual(termchat.room_cycle(rooms, "new", 1), "art")
+        self.assertEqual(termchat.room_cycle(rooms, "new", -1), "general")
+
+    def test_nowhere_else_to_go(self):
+        self.assertIsNone(termchat.room_cycle(["general"], "general", 1))
+        self.assertIsNone(termchat.room_cycle([], "general", -1))
+
+
+class BlockedRoomTest(unittest.TestCase):
+    def test_block_and_unblock(self):
+        blocked = []
+        self.assertTrue(termchat.block_room(blocked, "wss://chat.example.com/", "general"))
+        self.assertFalse(termchat.block_room(blocked, "wss://chat.example.com", "general"))
+        self.assertTrue(termchat.is_room_blocked(blocked, "wss://chat.example.com", "general"))
+        self.assertFalse(termchat.is_room_blocked(blocked, "wss://other.example.com", "general"))
+        self.assertFalse(termchat.is_room_blocked(blocked, "wss://chat.example.com", "General"))
+        self.assertTrue(termchat.unblock_room(blocked, "wss://chat.example.com", "general"))
+        self.assertFalse(termchat.unblock_room(blocked, "wss://chat.example.com", "general"))
+        self.assertEqual(blocked, [])
+
+    def test_blocked_rooms_are_not_cycled_through(self):
+        recent = [{"server": "wss://a", "chat": chat} for chat in ("general", "random", "ops")]
+        blocked = [{"server": "wss://a", "chat": "random"}]
+        self.assertEqual(termchat.rooms_on_server(recent, "wss://a", blocked), ["general", "ops"])
+
+
+class UnblockCommandTest(unittest.IsolatedAsyncioTestCase):
+    async def test_room_names_with_spaces(self):
+        isolate_config(self)
+        app = RoomApp(MockServer())
+        async with app.run_test() as pilot:
+            screen = await joined(pilot)
+            blocked = app.settings["blocked_rooms"]
+            termchat.block_room(blocked, app.server_url, "book club")
+            termchat.block_room(blocked, "wss://other.example.com", "late night")
+            await screen.submit_message("/unblock-room book club")
+            await screen.submit_message("/unblock-room wss://other.example.com late night")
+            self.assertEqual(blocked, [])
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,41 @@
+import asyncio
+import unittest
+from unittest import mock
+
+import termchat
+from support import isolate_config, wait_until
+
+
+async def refuse_connection(*args, **kwargs):
+    raise OSError("no server in tests")
+
+
+class SplashTest(unittest.IsolatedAsyncioTestCase):
+    def setUp(self):
+        isolate_config(self)
+        self.count_ready = asyncio.Event()
+
+        async def slow_count(app) -> int:
+            await self.count_ready.wait()
+            return 3
+        for target, name, value in ((termchat.TermchatApp, "fetch_general_count", slow_count),
+                                    (termchat.websockets, "connect", refuse_connection)):
+            patcher = mock.patch.object(target, name, value)
+            patcher.start()
+            self.addCleanup(patcher.stop)
+
+    async def test_a_key_skips_before_the_count_arrives(self):
+        app = termchat.TermchatApp(server_url="wss://example.test")
+        async with app.run_test() as pilot:
+            await wait_until(pilot, lambda: isinstance(app.screen, termchat.SplashScreen))
+            await pilot.press("space")
+            await wait_until(pilot, lambda: isinstance(app.screen, termchat.ConnectionScreen))
+            self.count_ready.set()
+            await pilot.pause(0.1)
+            self.assertIsInstance(app.screen, termchat.ConnectionScreen)
+            # The splash moved on once, not again when the count came in
+            self.assertEqual(sum(isinstance(screen, termchat.ConnectionScreen) for screen in app.screen_stack), 1)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,70 @@
+import re
+import unittest
+
+import termchat
+from support import MockServer, RoomApp, isolate_config, joined
+
+
+def message(username: str, content: str) -> dict:
+    return {"kind": "message", "username": username, "content": content}
+
+
+class SearchTest(unittest.TestCase):
+    def setUp(self):
+        self.entries = [
+            message("alice", "Deploy at 3.14?"),
+            {"kind": "notice", "markup": "deploy notice"},
+            message("bob", "deploy done"),
+            message("deployer", "hi"),
+        ]
+
+    def test_literal_is_case_insensitive_and_matches_senders(self):
+        matches = termchat.search_entries(self.entries, termchat.search_pattern("DEPLOY", False))
+        self.assertEqual([entry["username"] for entry in matches], ["alice", "bob", "deployer"])
+
+    def test_literal_special_characters(self):
+        self.assertEqual(len(termchat.search_entries(self.entries, termchat.search_pattern("3.14?", False))), 1)
+        self.assertEqual(termchat.search_entries(self.entries, termchat.search_pattern("3x14", False)), [])
+
+    def test_regex(self):
+        matches = termchat.search_entries(self.entries, termchat.search_pattern(r"^deploy \w+$", True))
+        self.assertEqual([entry["username"] for entry in matches], ["bob"])
+
+    def test_invalid_regex(self):
+        with self.assertRaises(re.error):
+            termchat.search_pattern("(unclosed", True)
+        termchat.search_pattern("(unclosed", False)
+
+
+class FindNextPrevTest(unittest.IsolatedAsyncioTestCase):
+    def setUp(self):
+        isolate_config(self)
+        self.app = RoomApp(MockServer())
+
+    async def test_steps_from_the_newest_match_and_wraps(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            for number, content in enumerate(["tea?", "coffee", "more tea"]):
+                await screen.handle_message({"type": "message", "username": "bob", "content": content, "id": number})
+            screen.find_messages("tea")
+            self.assertEqual(screen.search_matches[screen.search_index]["content"], "more tea")
+            screen.search_step(-1)
+            self.assertEqual(screen.search_matches[screen.search_index]["content"], "tea?")
+            screen.search_step(-1)
+            self.assertEqual(screen.search_matches[screen.search_index]["content"], "more tea")
+            screen.search_step(1)
+            self.assertEqual(screen.search_matches[screen.search_index]["content"], "tea?")
+
+    async def test_bad_regex_and_no_matches(self):
+        async with self.app.run_test() as pilot:
+            screen = await joined(pilot)
+            screen.find_messages("-r (")
+            self.assertIn("red", screen.entries[-1]["markup"])
+            screen.find_messages("nothing like this")
+            self.assertEqual(screen.search_matches, [])
+            screen.search_step(1)
+            self.assertIn(termchat.sys_msg("no_search"), screen.entries[-1]["markup"])
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,32 @@
+import unittest
+
+import termchat
+
+
+class SendStateTest(unittest.TestCase):
+    def test_transitions(self):
+        cases = [
+            ("pending", "echo", "sent"),
+            ("pending", "timeout", "failed"),
+            ("pending", "error", "failed"),
+            ("failed", "echo", "sent"),  # A late echo still confirms it
+            ("failed", "retry", "pending"),
+            ("sent", "timeout", "sent"),
+            ("sent", "retry", "sent"),
+            ("pending", "retry", "pending"),
+        ]
+        for state, event, expected in cases:
+            with self.subTest(state=state, event=event):
+                self.assertEqual(termchat.next_send_state(state, event), expected)
+
+    def test_marker_follows_the_entry(self):
+        entry = {"markup": "alice: hi", "count": 1, "state": "pending"}
+        self.assertEqual(termchat.render_entry(entry), f"alice: hi [dim]{termchat.glyphs.ellipsis}[/dim]")
+        entry["state"] = "failed"
+        self.assertIn(termchat.glyphs.cross, termchat.render_entry(entry))
+        entry["state"] = None
+        self.assertEqual(termchat.render_entry(entry), "alice: hi")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,100 @@
+import json
+import time
+import unittest
+
+import termchat
+from support import isolate_config
+
+
+class SettingsFileTest(unittest.TestCase):
+    def setUp(self):
+        isolate_config(self)
+
+    def test_defaults_without_a_file(self):
+        self.assertEqual(termchat.load_settings(), termchat.DEFAULT_SETTINGS)
+
+    def test_round_trip(self):
+        settings = termchat.load_settings()
+        settings["enter_sends"] = False
+        termchat.save_settings(settings)
+        self.assertFalse(termchat.load_settings()["enter_sends"])
+
+    def test_wrong_types_and_unknown_keys_fall_back(self):
+        with open(termchat.SETTINGS_PATH, "w", encoding="utf-8") as f:
+            json.dump({"enter_sends": "no", "not_a_setting": 1}, f)
+        settings = termchat.load_settings()
+        self.assertTrue(settings["enter_sends"])
+        self.assertNotIn("not_a_setting", settings)
+
+    def test_corrupt_file_falls_back(self):
+        with open(termchat.SETTINGS_PATH, "w", encoding="utf-8") as f:
+            f.write("{not json")
+        self.assertEqual(termchat.load_settings(), termchat.DEFAULT_SETTINGS)
+
+
+class SettingParsersTest(unittest.TestCase):
+    def test_boolean(self):
+        parse = termchat.SETTING_PARSERS["enter_sends"]
+        self.assertTrue(parse("ON"))
+        self.assertFalse(parse("no"))
+        with self.assertRaises(ValueError):
+            parse("maybe")
+
+    def test_number_in_range(self):
+        parse = termchat.SETTING_PARSERS["scroll_tolerance_lines"]
+        self.assertEqual(parse("0"), 0)
+        self.assertEqual(parse("50"), 50)
+        for value in ("51", "-1", "three"):
+            with self.assertRaises(ValueError):
+                parse(value)
+
+    def test_choice(self):
+        parse = termchat.SETTING_PARSERS["timestamp_style"]
+        self.assertEqual(parse("Gutter"), "gutter")
+        with self.assertRaises(ValueError):
+            parse("sideways")
+
+    def test_every_parser_accepts_its_default(self):
+        for key, parse in termchat.SETTING_PARSERS.items():
+            with self.subTest(key=key):
+                self.assertEqual(parse(termchat.format_setting(termchat.DEFAULT_SETTINGS[key])), termchat.DEFAULT_SETTINGS[key])
+
+    def test_format(self):
+        self.assertEqual(termchat.format_setting(True), "on")
+        self.assertEqual(termchat.format_setting(""), "off")
+        self.assertEqual(termchat.format_setting(5), "5")
+
+
+class QuietHoursTest(unittest.TestCase):
+    def at(self, hour: int, minute: int) -> time.struct_time:
+        return time.struct_time((2024, 5, 1, hour, minute, 0, 2, 122, -1))
+
+    def test_parse(self):
+        self.assertEqual(termchat.parse_quiet_hours("9:30-17:00"), (570, 1020))
+        for schedule in ("24:00-08:00", "22:00", "22:60-08:00", "ten-six"):
+            with self.subTest(schedule=schedule):
+                self.assertIsNone(termchat.parse_quiet_hours(schedule))
+
+    def test_same_day_window(self):
+        self.assertTrue(termchat.in_quiet_hours("09:00-17:00", self.at(9, 0)))
+        self.assertFalse(termchat.in_quiet_hours("09:00-17:00", self.at(17, 0)))
+
+    def test_window_wrapping_past_midnight(self):
+        for hour, minute, quiet in ((23, 30, True), (0, 0, True), (7, 59, True), (8, 0, False), (21, 59, False)):
+            with self.subTest(time=f"{hour}:{minute:02}"):
+                self.assertEqual(termchat.in_quiet_hours("22:00-08:00", self.at(hour, minute)), quiet)
+
+    def test_empty_or_no_schedule(self):
+        self.assertFalse(termchat.in_quiet_hours("", self.at(12, 0)))
+        self.assertFalse(termchat.in_quiet_hours("12:00-12:00", self.at(12, 0)))
+
+    def test_setting(self):
+        parse = termchat.SETTING_PARSERS["dnd_schedule"]
+        self.assertEqual(parse(" 22:00-08:00 "), "22:00-08:00")
+        self.assertEqual(parse("off"), "")
+        with self.assertRaises(ValueError):
+            parse("late")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,60 @@
+import time
+import unittest
+
+from cryptography.hazmat.primitives.asymmetric.ed25519 import Ed25519PrivateKey
+
+import termchat
+
+
+class SigningTest(unittest.TestCase):
+    def setUp(self):
+        self.key = Ed25519PrivateKey.generate()
+        self.trusted = {"alice": termchat.public_key_text(self.key)}
+        self.now = time.time()
+        self.signed_at = int(self.now * 1000)
+
+    def message(self, message_id=1, signed_at=None) -> dict:
+        signed_at = self.signed_at if signed_at is None else signed_at
+        return {"username": "alice", "content": "hello", "id": message_id, "signed_at": signed_at,
+                "signature": termchat.sign_message(self.key, "alice", "hello", signed_at)}
+
+    def test_round_trip(self):
+        self.assertEqual(termchat.signature_state(self.message(), self.trusted, {}, self.now), "verified")
+
+    def test_tampered(self):
+        for changes in ({"content": "hellO"}, {"username": "mallory"}, {"signed_at": 5}, {"signature": "not base64!"}):
+            with self.subTest(changes=changes):
+                # Changed after signing; mallory trusts alice's key too, so only the signature can fail
+                data = {**self.message(), **changes}
+                self.assertEqual(termchat.signature_state(data, {**self.trusted, "mallory": self.trusted["alice"]}), "unverified")
+
+    def test_only_trusted_keys_verify(self):
+        # A key someone merely announced isn't trusted until /trust, so an impostor's own key gets no ✓
+        impostor = Ed25519PrivateKey.generate()
+        self.assertEqual(termchat.signature_state(self.message(), {}), "unverified")
+        self.assertEqual(termchat.signature_state(self.message(), {"alice": termchat.public_key_text(impostor)}), "unverified")
+
+    def test_unsigned(self):
+        self.assertIsNone(termchat.signature_state({"username": "alice", "content": "hi"}, self.trusted))
+
+    def test_replay_under_another_id(self):
+        seen = {}
+        self.assertEqual(termchat.signature_state(self.message(message_id=1), self.trusted, seen, self.now), "verified")
+        self.assertEqual(termchat.signature_state(self.message(message_id=1), self.trusted, seen, self.now), "verified")
+        self.assertEqual(termchat.signature_state(self.message(message_id=2), self.trusted, seen, self.now), "unverified")
+        self.assertEqual(termchat.signature_state(self.message(message_id=None), self.trusted, seen, self.now), "unverified")
+
+    def test_stale_live_messages(self):
+        old = self.signed_at - (termchat.SIGNATURE_MAX_AGE + 1) * 1000
+        self.assertEqual(termchat.signature_state(self.message(signed_at=old), self.trusted, {}, self.now), "unverified")
+        self.assertEqual(termchat.signature_state(self.message(signed_at=old), self.trusted, {}, None), "verified")  # Backlog
+
+    def test_seen_signatures_are_bounded(self):
+        seen = {("alice", number): number for number in range(termchat.SEEN_SIGNATURES_LIMIT)}
+        termchat.signature_state(self.message(), self.trusted, seen, self.now)
+        self.assertEqual(len(seen), termchat.SEEN_SIGNATURES_LIMIT)
+        self.assertNotIn(("alice", 0), seen)
+
+
+if __name__ == "__main__":
+    unittest.main()
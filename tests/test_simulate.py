@@ -0,0 +1,33 @@
+import unittest
+
+import termchat
+
+
+class SimulatedEventTest(unittest.TestCase):
+    def test_each_kind_gives_its_frame(self):
+        types = {"message": "message", "join": "join", "leave": "leave", "theme": "colourshift",
+                 "broadcast": "broadcast", "slowmode": "slowmode", "kick": "kicked"}
+        for kind, frame_type in types.items():
+            with self.subTest(kind=kind):
+                self.assertEqual(termchat.simulated_event(kind, 0)["type"], frame_type)
+        self.assertIsNone(termchat.simulated_event("chatter", 0))
+
+    def test_same_step_same_event(self):
+        self.assertEqual(termchat.simulated_event("message", 3), termchat.simulated_event("message", 3))
+        self.assertNotEqual(termchat.simulated_event("message", 3), termchat.simulated_event("message", 4))
+
+    def test_slowmode_alternates(self):
+        self.assertEqual([termchat.simulated_event("slowmode", step)["seconds"] for step in range(3)], [5, 0, 5])
+
+
+class DevCommandsTest(unittest.TestCase):
+    def test_simulate_only_with_dev(self):
+        self.assertIsNone(termchat.find_command("/simulate join"))
+        commands = list(termchat.COMMANDS)
+        self.addCleanup(termchat.COMMANDS.__setitem__, slice(None), commands)
+        termchat.enable_dev_commands()
+        self.assertEqual(termchat.find_command("/simulate join").name, "simulate")
+
+
+if __name__ == "__main__":
+    unittest.main()
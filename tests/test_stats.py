@@ -0,0 +1,91 @@
+import time
+import unittest
+
+import termchat
+
+
+class SessionSummaryTest(unittest.TestCase):
+    def test_plurals_and_duration(self):
+        dash = termchat.glyphs.dash
+        self.assertEqual(termchat.format_session_summary(1, 1, 59), f"Session ended {dash} 1 message, 1 user seen, 59s connected")
+        self.assertEqual(termchat.format_session_summary(12, 3, 3725), f"Session ended {dash} 12 messages, 3 users seen, 1h 2m connected")
+
+    def test_empty_session(self):
+        self.assertEqual(termchat.format_session_summary(0, 0, 0), f"Session ended {termchat.glyphs.dash} 0 messages, 0 users seen, 0s connected")
+
+    def test_counts_sent_and_received_but_not_ourselves(self):
+        stats = termchat.SessionStats("alice", now=1000)
+        stats.sent += 2
+        stats.count_message("bob")
+        stats.see("alice")
+        stats.see("carol")
+        self.assertIn("3 messages, 2 users seen, 2m connected", stats.summary(now=1150))
+        stats.reset(now=2000)
+        self.assertIn("0 messages, 0 users seen, 0s connected", stats.summary(now=2000))
+
+
+class WhoisTest(unittest.TestCase):
+    def test_someone_seen_this_session(self):
+        first_seen = time.mktime((2024, 5, 1, 9, 30, 0, 0, 0, -1))
+        lines = termchat.format_whois("bob", True, "cyan", "lunch", first_seen, 3, first_seen + 600)
+        self.assertEqual(lines, [
+            "[cyan]bob[/cyan]",
+            "  Presence: in the room",
+            "  Status: lunch",
+            "  Color: cyan",
+            "  First seen: 09:30 (10m ago)",
+            "  Messages this session: 3",
+        ])
+
+    def test_someone_never_seen(self):
+        self.assertEqual(termchat.format_whois("eve", False, "red", "", None, 0, 0), ["No one called eve has been seen this session."])
+
+    def test_lookalike_names_are_flagged(self):
+        lines = termchat.format_whois("аlice", True, "red", "", None, 0, 0)
+        self.assertIn("Possible impersonation", lines[1])
+
+
+class ConfusableTest(unittest.TestCase):
+    def test_ordinary_names(self):
+        for name in ("alice", "田中さん", "Алексей", "Σωκράτης", "José", "bob_42", "김민준",
+                     "Jose\u0301"):  # José typed with a combining accent, as macOS often sends it
+            with self.subTest(name=name):
+                self.assertFalse(termchat.is_potentially_confusable(name))
+
+    def test_lookalike_names(self):
+        for name in ("pаypal",  # Cyrillic а among Latin letters
+                     "аре",  # All Cyrillic, but every letter could pass for Latin
+                     "ali\u200bce", "ａｌｉｃｅ", "ﾀﾅｶ"):  # Half-width katakana passing for タナカ
+            with self.subTest(name=name):
+                self.assertTrue(termchat.is_potentially_confusable(name))
+
+
+class UserHistoryTest(unittest.TestCase):
+    def test_survives_a_reset(self):
+        stats = termchat.SessionStats("alice", now=1000)
+        stats.see("bob", now=1005)
+        stats.count_message("bob")
+        stats.activity("carol", now=1010)
+        stats.reset(now=2000)
+        stats.see("bob", now=2001)
+        self.assertEqual(stats.users, {"bob"})
+        self.assertEqual(stats.history.message_counts, {"bob": 1})
+        self.assertEqual(stats.history.first_seen["carol"], 1010)
+        self.assertEqual(stats.history.last_active["carol"], 1010)
+        self.assertEqual(stats.history.first_seen["bob"], 1005)
+
+
+class RelativeTimeTest(unittest.TestCase):
+    def test_largest_whole_unit(self):
+        cases = [(0, "just now"), (9, "just now"), (10, "10 seconds ago"), (60, "1 minute ago"), (119, "1 minute ago"),
+                 (7200, "2 hours ago"), (86400 * 3 + 5, "3 days ago")]
+        for seconds, text in cases:
+            with self.subTest(seconds=seconds):
+                self.assertEqual(termchat.format_relative(1000000 + seconds, 1000000), text)
+
+    def test_clock_skew_reads_as_just_now(self):
+        self.assertEqual(termchat.format_relative(1000, 1060), "just now")
+
+
+if __name__ == "__main__":
+    unittest.main()
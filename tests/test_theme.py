@@ -0,0 +1,60 @@
+import unittest
+
+import termchat
+
+
+class ThemeTest(unittest.TestCase):
+    def test_presets(self):
+        self.assertEqual(termchat.resolve_theme("Amber"), termchat.THEMES["amber"])
+        self.assertEqual(termchat.resolve_theme("default"), ("#87CEEB", "#000000"))
+
+    def test_hex_color_on_the_default_background(self):
+        self.assertEqual(termchat.resolve_theme("#ff8800"), ("#FF8800", termchat.DEFAULT_BACKGROUND_COLOR))
+
+    def test_invalid(self):
+        for value in ("#ff88", "ff8800", "#gg8800", "neon"):
+            with self.subTest(value=value):
+                self.assertIsNone(termchat.resolve_theme(value))
+
+    def test_setting(self):
+        self.assertEqual(termchat.parse_theme_setting("AUTO"), "auto")
+        self.assertEqual(termchat.parse_theme_setting("Mono"), "mono")
+        with self.assertRaises(ValueError):
+            termchat.parse_theme_setting("neon")
+
+
+class ContrastTest(unittest.TestCase):
+    def test_every_user_color_readable_on_black_and_white(self):
+        for name, rgb in termchat.USER_COLOR_RGB.items():
+            for background in ((0, 0, 0), (255, 255, 255)):
+                with self.subTest(color=name, background=background):
+                    self.assertGreaterEqual(termchat.contrast_ratio(termchat.readable_color(rgb, background), background), termchat.MIN_CONTRAST)
+
+    def test_readable_colors_are_left_alone(self):
+        self.assertEqual(termchat.readable_color((255, 255, 0), (0, 0, 0)), (255, 255, 0))
+
+    def test_ratio_extremes(self):
+        self.assertAlmostEqual(termchat.contrast_ratio((255, 255, 255), (0, 0, 0)), 21.0)
+        self.assertAlmostEqual(termchat.contrast_ratio((90, 90, 90), (90, 90, 90)), 1.0)
+
+
+class PickThemeTest(unittest.TestCase):
+    def test_precedence(self):
+        self.assertEqual(termchat.pick_theme("amber", "light", "dark"), "amber")
+        self.assertEqual(termchat.pick_theme(None, "amber", "light"), "amber")
+        self.assertEqual(termchat.pick_theme(None, "auto", "light"), "light")
+
+    def test_auto_falls_back_to_dark(self):
+        for scheme in ("dark", None):
+            with self.subTest(scheme=scheme):
+                self.assertEqual(termchat.pick_theme(None, "auto", scheme), "default")
+
+    def test_text_color(self):
+        self.assertEqual(termchat.text_color_for("#FFFFFF"), "black")
+        self.assertEqual(termchat.text_color_for("#F5F5DC"), "black")
+        self.assertEqual(termchat.text_color_for("#000000"), "white")
+        self.assertEqual(termchat.text_color_for("not a color"), "white")
+
+
+if __name__ == "__main__":
+    unittest.main()
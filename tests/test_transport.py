@@ -0,0 +1,90 @@
+import unittest
+from types import SimpleNamespace
+
+import termchat
+from support import MockServer, RoomApp, fast_reconnects, isolate_config, joined, wait_until
+
+
+class MockTransportTest(unittest.IsolatedAsyncioTestCase):
+    async def test_iteration_ends_when_the_server_closes(self):
+        transport = await MockServer().connect("wss://example.test/ws")
+        transport.push({"type": "message", "content": "one"})
+        transport.drop(1001, "going away")
+        frames = [frame async for frame in transport]
+        self.assertEqual(len(frames), 1)
+        self.assertEqual((transport.close_code, transport.close_reason), (1001, "going away"))
+        with self.assertRaises(termchat.TransportClosed):
+            await transport.send('{"type": "message"}')
+
+
+class RoundTripTest(unittest.IsolatedAsyncioTestCase):
+    def setUp(self):
+        isolate_config(self)
+
+    async def test_connect_join_and_message(self):
+        server = MockServer()
+        app = RoomApp(server, username="alice", chat="general")
+        async with app.run_test() as pilot:
+            screen = await joined(pilot)
+            join = server.transport.sent[0]
+            self.assertEqual(join, {"type": "join", "username": "alice", "chatname": "general", "password": "secret"})
+
+            await screen.send_message("hello")
+            self.assertEqual(server.transport.sent_of_type("message")[-1]["content"], "hello")
+            await wait_until(pilot, lambda: any(entry.get("content") == "hello" for entry in screen.entries))
+
+            server.transport.push({"type": "message", "username": "bob", "content": "hi alice", "id": 42})
+            await wait_until(pilot, lambda: any(entry.get("id") == 42 for entry in screen.entries))
+            entry = next(entry for entry in screen.entries if entry.get("id") == 42)
+            self.assertEqual((entry["username"], entry["content"]), ("bob", "hi alice"))
+
+    async def test_plain_ws_server(self):
+        server = MockServer()
+        app = RoomApp(server, server_url="ws://localhost:8080")
+        async with app.run_test() as pilot:
+            screen = await joined(pilot)
+            self.assertEqual(server.urls, ["ws://localhost:8080"])
+            await screen.send_message("hello")
+            self.assertEqual(server.transport.sent_of_type("message")[-1]["content"], "hello")
+
+
+class OversizedFrameTest(unittest.IsolatedAsyncioTestCase):
+    def test_library_close_reports_the_code_it_sent(self):
+        transport = termchat.WebSocketTransport(SimpleNamespace(close_code=1006, close_reason=""))
+        error = SimpleNamespace(rcvd=None, sent=SimpleNamespace(code=1009, reason="frame too big"))
+        closed = transport.closed(error)
+        self.assertEqual((closed.code, closed.reason), (1009, "frame too big"))
+        self.assertEqual(transport.close_code, 1009)
+
+    def test_server_close_wins_when_it_answered(self):
+        transport = termchat.WebSocketTransport(SimpleNamespace(close_code=1001, close_reason="going away"))
+        error = SimpleNamespace(rcvd=SimpleNamespace(code=1001, reason="going away"), sent=SimpleNamespace(code=1000, reason=""))
+        self.assertEqual(transport.closed(error).code, 1001)
+
+    async def test_shown_and_not_reconnected(self):
+        isolate_config(self)
+        server = MockServer()
+        fast_reconnects(self, server)
+        app = RoomApp(server)
+        async with app.run_test() as pilot:
+            screen = await joined(pilot)
+            server.transport.drop(1009)
+            await wait_until(pilot, lambda: screen.connection_state == "disconnected")
+            await pilot.pause(0.1)
+            self.assertEqual(len(server.transports), 1)
+            self.assertTrue(any(termchat.CLOSE_CODE_MESSAGES[1009] in entry["markup"] for entry in screen.entries))
+
+    async def test_unfinished_json_is_dropped_past_the_limit(self):
+        isolate_config(self)
+        server = MockServer()
+        app = RoomApp(server)
+        async with app.run_test() as pilot:
+            screen = await joined(pilot)
+            server.transport.push('{"type": "message", "content": "' + "x" * termchat.MAX_FRAME_SIZE)
+            await wait_until(pilot, lambda: screen.frame_buffer == "" and "incomplete JSON" in screen.entries[-1]["markup"])
+            server.transport.push({"type": "message", "username": "bob", "content": "still here", "id": 1})
+            await wait_until(pilot, lambda: termchat.find_entry(screen.entries, 1) is not None)
+
+
+if __name__ == "__main__":
+    unittest.main()